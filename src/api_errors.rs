@@ -1,17 +1,33 @@
-use std::{fmt, error::Error};
+use std::{error::Error, fmt, time::Duration};
+
+use reqwest::StatusCode;
 
 #[derive(Debug)]
 /// Error returned by API containing information from the server
 pub struct ResponseError {
     details: String,
+    status: Option<StatusCode>,
 }
 
 impl ResponseError {
     pub fn new(msg: &str) -> Self {
         Self {
             details: msg.to_string(),
+            status: None,
+        }
+    }
+
+    /// Error for the response with given status code
+    pub fn with_status(status: StatusCode, msg: &str) -> Self {
+        Self {
+            details: msg.to_string(),
+            status: Some(status),
         }
     }
+
+    pub fn status(&self) -> Option<StatusCode> {
+        self.status
+    }
 }
 
 impl fmt::Display for ResponseError {
@@ -29,7 +45,6 @@ impl Error for ResponseError {
 #[derive(Debug)]
 pub struct InvalidQueryError {
     details: String,
-
 }
 
 impl InvalidQueryError {
@@ -50,4 +65,251 @@ impl Error for InvalidQueryError {
     fn description(&self) -> &str {
         &self.details
     }
-}
\ No newline at end of file
+}
+
+#[derive(Debug)]
+/// Replayed request has no recorded response
+pub struct MissingFixtureError {
+    details: String,
+}
+
+impl MissingFixtureError {
+    pub fn new(msg: &str) -> Self {
+        Self {
+            details: msg.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for MissingFixtureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.details)
+    }
+}
+
+impl Error for MissingFixtureError {
+    fn description(&self) -> &str {
+        &self.details
+    }
+}
+
+#[derive(Debug)]
+/// Requested entity does not exist, or token has no access to it
+pub struct NotFoundError {
+    details: String,
+}
+
+impl NotFoundError {
+    pub fn new(msg: &str) -> Self {
+        Self {
+            details: msg.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for NotFoundError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.details)
+    }
+}
+
+impl Error for NotFoundError {
+    fn description(&self) -> &str {
+        &self.details
+    }
+}
+
+#[derive(Debug)]
+/// Search found fewer repos than requested, error only if the query is strict about the count
+pub struct ShortfallError {
+    details: String,
+}
+
+impl ShortfallError {
+    pub fn new(msg: &str) -> Self {
+        Self {
+            details: msg.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ShortfallError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.details)
+    }
+}
+
+impl Error for ShortfallError {
+    fn description(&self) -> &str {
+        &self.details
+    }
+}
+
+#[derive(Debug)]
+/// Token was rejected with 401, it's invalid or expired
+pub struct AuthError {
+    details: String,
+}
+
+impl AuthError {
+    pub fn new(msg: &str) -> Self {
+        Self {
+            details: msg.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.details)
+    }
+}
+
+impl Error for AuthError {
+    fn description(&self) -> &str {
+        &self.details
+    }
+}
+
+#[derive(Debug)]
+/// Token is valid, but lacks permissions needed for the request
+pub struct PermissionError {
+    details: String,
+}
+
+impl PermissionError {
+    pub fn new(msg: &str) -> Self {
+        Self {
+            details: msg.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for PermissionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.details)
+    }
+}
+
+impl Error for PermissionError {
+    fn description(&self) -> &str {
+        &self.details
+    }
+}
+
+#[derive(Debug)]
+/// Processing of a single repo took longer than allowed
+pub struct RepoTimeoutError {
+    details: String,
+}
+
+impl RepoTimeoutError {
+    pub fn new(msg: &str) -> Self {
+        Self {
+            details: msg.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for RepoTimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.details)
+    }
+}
+
+impl Error for RepoTimeoutError {
+    fn description(&self) -> &str {
+        &self.details
+    }
+}
+
+#[derive(Debug)]
+/// Primary rate limit is exhausted, no more requests until the quota resets
+pub struct RateLimitError {
+    details: String,
+    wait: Duration,
+}
+
+impl RateLimitError {
+    pub fn new(msg: &str, wait: Duration) -> Self {
+        Self {
+            details: msg.to_string(),
+            wait,
+        }
+    }
+
+    /// How long server asked to wait before next request
+    pub fn wait(&self) -> Duration {
+        self.wait
+    }
+}
+
+impl fmt::Display for RateLimitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.details)
+    }
+}
+
+impl Error for RateLimitError {
+    fn description(&self) -> &str {
+        &self.details
+    }
+}
+
+#[derive(Debug)]
+/// Secondary rate limit was hit, server asks to slow down
+pub struct AbuseError {
+    details: String,
+    wait: Duration,
+}
+
+impl AbuseError {
+    pub fn new(msg: &str, wait: Duration) -> Self {
+        Self {
+            details: msg.to_string(),
+            wait,
+        }
+    }
+
+    /// How long server asked to wait before next request
+    pub fn wait(&self) -> Duration {
+        self.wait
+    }
+}
+
+impl fmt::Display for AbuseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.details)
+    }
+}
+
+impl Error for AbuseError {
+    fn description(&self) -> &str {
+        &self.details
+    }
+}
+
+/// Error type of the crate. It's Send + Sync + 'static, so futures of the api can be
+/// spawned with tokio::spawn, and their errors returned out of the task
+pub type SendError = Box<dyn Error + Send + Sync + 'static>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_send_sync<T: Send + Sync + 'static>() {}
+
+    #[test]
+    fn errors_are_send_sync() {
+        assert_send_sync::<SendError>();
+        assert_send_sync::<ResponseError>();
+        assert_send_sync::<InvalidQueryError>();
+        assert_send_sync::<MissingFixtureError>();
+        assert_send_sync::<NotFoundError>();
+        assert_send_sync::<AuthError>();
+        assert_send_sync::<PermissionError>();
+        assert_send_sync::<RepoTimeoutError>();
+        assert_send_sync::<RateLimitError>();
+        assert_send_sync::<AbuseError>();
+    }
+}
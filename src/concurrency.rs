@@ -0,0 +1,240 @@
+use std::{sync::Mutex, time::Duration};
+
+use tokio::{sync::Notify, time::Instant};
+
+use crate::api_errors::InvalidQueryError;
+
+#[derive(Debug, Clone)]
+/// Parameters of the adaptive concurrency, AIMD style: limit grows slowly
+/// with every successful request, and is cut down on every throttled one
+pub struct AdaptiveConfig {
+    /// Limit of requests in flight at the start
+    pub initial: usize,
+    /// Limit never drops below that
+    pub min: usize,
+    /// Limit never grows above that
+    pub max: usize,
+    /// Added to the limit after each successful request, 0.1 means
+    /// one more request in flight after 10 successful ones
+    pub increase: f64,
+    /// Limit is multiplied by that on throttling
+    pub decrease: f64,
+}
+
+impl Default for AdaptiveConfig {
+    fn default() -> Self {
+        Self {
+            initial: 5,
+            min: 1,
+            max: 20,
+            increase: 0.1,
+            decrease: 0.5,
+        }
+    }
+}
+
+struct State {
+    limit: f64,
+    in_flight: usize,
+}
+
+/// Limits number of requests in flight, adjusting the limit to the throttling signals
+pub struct AdaptiveConcurrency {
+    config: AdaptiveConfig,
+    state: Mutex<State>,
+    released: Notify,
+}
+
+/// Slot for a single request, given back when dropped
+pub struct Permit<'a> {
+    owner: &'a AdaptiveConcurrency,
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        self.owner.state.lock().unwrap().in_flight -= 1;
+        self.owner.released.notify_waiters();
+    }
+}
+
+impl AdaptiveConcurrency {
+    pub fn new(config: &AdaptiveConfig) -> Self {
+        let min = config.min.max(1);
+        let max = config.max.max(min);
+
+        Self {
+            config: AdaptiveConfig {
+                min,
+                max,
+                ..config.clone()
+            },
+            state: Mutex::new(State {
+                limit: config.initial.clamp(min, max) as f64,
+                in_flight: 0,
+            }),
+            released: Notify::new(),
+        }
+    }
+
+    /// Current limit of requests in flight
+    pub fn limit(&self) -> usize {
+        self.state.lock().unwrap().limit as usize
+    }
+
+    /// Upper bound of the limit, more workers than that would only wait
+    pub fn max(&self) -> usize {
+        self.config.max
+    }
+
+    /// Waits until request fits in the limit
+    pub async fn acquire(&self) -> Permit<'_> {
+        loop {
+            // Created before the check, so release in between is not missed
+            let released = self.released.notified();
+
+            {
+                let mut state = self.state.lock().unwrap();
+                if state.in_flight < state.limit as usize {
+                    state.in_flight += 1;
+                    return Permit { owner: self };
+                }
+            }
+
+            released.await;
+        }
+    }
+
+    /// Request went through, limit ramps up
+    pub fn on_success(&self) {
+        let grown = {
+            let mut state = self.state.lock().unwrap();
+            let before = state.limit as usize;
+            state.limit = (state.limit + self.config.increase).min(self.config.max as f64);
+
+            state.limit as usize > before
+        };
+
+        if grown {
+            trace!("Concurrency raised to {}", self.limit());
+            // Waiters may fit now
+            self.released.notify_waiters();
+        }
+    }
+
+    /// Request got throttled, limit is cut down
+    pub fn on_throttle(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.limit = (state.limit * self.config.decrease).max(self.config.min as f64);
+
+        debug!("Throttled, concurrency lowered to {}", state.limit as usize);
+    }
+}
+
+/// Spaces requests evenly, so all tasks sharing it together stay within the rate,
+/// unlike a delay after each request, which bursts as many requests as there are workers
+pub struct RateLimiter {
+    interval: Duration,
+    /// When the next request may be sent, None before the first one
+    next: Mutex<Option<Instant>>,
+}
+
+impl RateLimiter {
+    /// Rate in requests per second, fails unless it's positive and finite
+    pub fn new(rate: f64) -> Result<Self, InvalidQueryError> {
+        if !(rate > 0.0 && rate.is_finite()) {
+            return Err(InvalidQueryError::new(&format!(
+                "Rate of {} requests per second is not positive",
+                rate
+            )));
+        }
+
+        Ok(Self::every(Duration::from_secs_f64(1.0 / rate)))
+    }
+
+    /// Turns are at least the interval apart
+    pub fn every(interval: Duration) -> Self {
+        Self {
+            interval,
+            next: Mutex::new(None),
+        }
+    }
+
+    /// Waits for the turn of the next request. Turns are handed out in order of the calls,
+    /// unused turn is not saved for later, there are no bursts
+    pub async fn acquire(&self) {
+        let turn = {
+            let mut next = self.next.lock().unwrap();
+            let now = Instant::now();
+            let turn = next.map_or(now, |next| next.max(now));
+            *next = Some(turn + self.interval);
+            turn
+        };
+
+        tokio::time::sleep_until(turn).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn controller(initial: usize) -> AdaptiveConcurrency {
+        AdaptiveConcurrency::new(&AdaptiveConfig {
+            initial,
+            min: 2,
+            max: 10,
+            increase: 0.5,
+            decrease: 0.5,
+        })
+    }
+
+    #[test]
+    fn throttle_halves_limit_down_to_min() {
+        let controller = controller(8);
+
+        controller.on_throttle();
+        assert_eq!(controller.limit(), 4);
+
+        controller.on_throttle();
+        controller.on_throttle();
+        assert_eq!(controller.limit(), 2);
+    }
+
+    #[test]
+    fn success_ramps_limit_up_to_max() {
+        let controller = controller(8);
+
+        controller.on_success();
+        assert_eq!(controller.limit(), 8);
+        controller.on_success();
+        assert_eq!(controller.limit(), 9);
+
+        for _ in 0..10 {
+            controller.on_success();
+        }
+        assert_eq!(controller.limit(), 10);
+    }
+
+    #[test]
+    fn initial_limit_is_within_bounds() {
+        assert_eq!(controller(0).limit(), 2);
+        assert_eq!(controller(50).limit(), 10);
+    }
+
+    #[tokio::test]
+    async fn acquire_waits_for_free_slot() {
+        let controller = controller(2);
+
+        let first = controller.acquire().await;
+        let _second = controller.acquire().await;
+
+        let third =
+            tokio::time::timeout(std::time::Duration::from_millis(50), controller.acquire());
+        assert!(third.await.is_err());
+
+        drop(first);
+        let third =
+            tokio::time::timeout(std::time::Duration::from_millis(50), controller.acquire());
+        assert!(third.await.is_ok());
+    }
+}
@@ -1,336 +1,4924 @@
-use std::collections::VecDeque;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
-use std::fmt::Debug;
+use std::fmt::{self, Debug};
+use std::fs;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
 use std::time::Duration;
 
-use futures::Future;
+use chrono::{DateTime, Utc};
+use futures::{StreamExt, TryStreamExt};
+use reqwest::StatusCode;
+use serde::{Deserialize, Deserializer, Serialize};
+use tokio::sync::OnceCell;
+use tokio::task::{JoinError, JoinHandle};
 
-use crate::api_errors::InvalidQueryError;
-use crate::github_client::GithubClient;
-use crate::github_data::{Contributions, Repos, RepoData};
+use crate::api_errors::{
+    AbuseError, AuthError, InvalidQueryError, NotFoundError, PermissionError, RateLimitError,
+    RepoTimeoutError, ResponseError, SendError, ShortfallError,
+};
+use crate::github_client::{self, GithubClient, RequestStats, TransportConfig};
+use crate::github_data::{
+    self, Commits, Contributions, Issues, Members, RateLimitData, RateLimits, RepoData, Repos,
+};
+use crate::progress::ProgressEvent;
+use crate::retry::RetryPolicy;
+use crate::score::Scorer;
+use crate::share::{self, RepoShare, ShareBasis, ShareSource};
+use crate::timing::Latencies;
+use crate::warnings::{RunWarning, WarningCategory, Warnings};
 
 // Max number of elements that fits on the page
 const PAGE_LIMIT: u32 = 100;
+/// Search gives no more than that many results for a query, whatever the page
+const SEARCH_RESULTS_CAP: u32 = 1000;
 const REPO_ENDPONT: &str = "https://api.github.com/search/repositories";
+// Path of the search endpoint, for search base other than the api
+const SEARCH_PATH: &str = "search/repositories";
+// Path of the search of issues, pull requests included, relative to the search base
+const ISSUE_SEARCH_PATH: &str = "search/issues";
+// Relative contributor and repo urls are resolved against that
+const API_BASE: &str = "https://api.github.com";
+// Cheap endpoint that does not count against the rate limit
+const RATE_LIMIT_ENDPOINT: &str = "https://api.github.com/rate_limit";
+// Start of the message github sends when token lacks permissions, fine-grained
+// tokens end it with "personal access token", apps with "integration"
+const NOT_ACCESSIBLE_MESSAGE: &str = "Resource not accessible by";
+// Search API has its own, much lower rate limit, for authenticated users
+const SEARCH_REQUESTS_PER_MINUTE: u32 = 30;
+// Time given to the search before asking again for the page with incomplete results
+const INCOMPLETE_RETRY_DELAY: Duration = Duration::from_secs(1);
+// Contributor pages of a single repo requested at once
+const CONTRIBUTOR_PAGES_AT_ONCE: usize = 4;
+// Limit for a single repo check, slow answer does not mean repo is gone
+const VERIFY_TIMEOUT: Duration = Duration::from_secs(10);
+// With auto threshold, repos with leader share above that part of results are reported
+const AUTO_THRESHOLD_PERCENTILE: f64 = 0.9;
+// Github lists with details only that many top contributors of the repo
+const MAX_USERS_TO_CONSIDER: u32 = 500;
+/// Order in which search returns repositories
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RepoSort {
+    /// Most starred first
+    Stars,
+    /// GitHub's relevance ranking, the default when no sort is given.
+    /// There is no stable sort key, so ordering between runs (and even
+    /// between pages of the same run) is less deterministic than for stars.
+    BestMatch,
+    /// Repos with most open issues labeled help-wanted first, those looking for contributors
+    HelpWantedIssues,
+}
+
+impl FromStr for RepoSort {
+    type Err = InvalidQueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stars" => Ok(RepoSort::Stars),
+            "best-match" => Ok(RepoSort::BestMatch),
+            "help-wanted-issues" => Ok(RepoSort::HelpWantedIssues),
+            _ => Err(InvalidQueryError::new(&format!(
+                "Unknown repo sort '{}', expected one of: stars, best-match, help-wanted-issues",
+                s
+            ))),
+        }
+    }
+}
+
 /// Contains parameters used for searching repositories
 #[derive(Debug)]
 pub struct RepoQuery<'a> {
-    pub language: &'a str,
+    /// None means any language
+    pub language: Option<&'a str>,
     pub count: u32,
     pub delay_sec: f64,
+    pub sort: RepoSort,
+    /// Only repos with at least that many stars
+    pub min_stars: Option<u64>,
+    /// Only repos with at most that many stars
+    pub max_stars: Option<u64>,
+    /// Only repos tagged with the topic
+    pub topic: Option<&'a str>,
+    /// Finding fewer repos than count fails with ShortfallError, otherwise it's only logged
+    pub strict_count: bool,
+    /// Drop repos whose detected language is not the requested one, ignoring case and
+    /// aliases like cpp for C++. Search matches more loosely, so there may be fewer repos
+    /// than count then, with strict_count that fails whenever any repo is dropped
+    pub strict_language: bool,
+}
+
+impl Default for RepoQuery<'_> {
+    /// Query for repos in any language, set at least count
+    fn default() -> Self {
+        Self {
+            language: None,
+            count: 0,
+            delay_sec: 0.0,
+            sort: RepoSort::Stars,
+            min_stars: None,
+            max_stars: None,
+            topic: None,
+            strict_count: false,
+            strict_language: false,
+        }
+    }
 }
 
+#[derive(Clone)]
 /// Parameters to characterize bus_factor calculation
 pub struct BusFactorQuery {
+    /// Share of the leader above which repo is considered at risk,
+    /// results are not filtered by it, that's up to the reporting side
     pub bus_threshold: f64,
     pub users_to_consider: u32,
     pub delay_sec: f64,
-
+    /// Repos with less contributors than that are not reported
+    pub min_contributors: u32,
+    /// What is counted as contribution, commits or merged pull requests, see ShareBasis
+    pub basis: ShareBasis,
+    /// Check if repos still exist before fetching contributors, costs one request per repo
+    pub verify_repos: bool,
+    /// If set, only contributions of these logins are considered, see GithubApi::get_members.
+    /// Logins are compared case-insensitively, as github_data::login_key, the builder makes them such
+    pub members: Option<HashSet<String>>,
+    /// Make shares relative to all commits in the repo, not only to those of
+    /// users_to_consider. Costs one more request per repo, and lowers the numbers
+    pub normalize: bool,
+    /// Repo that is not done in that time is skipped. Applies to requests for the repo
+    /// only, delay_sec between repos is not counted
+    pub timeout_per_repo: Option<Duration>,
+    /// Report how many requests each repo cost, in BusFactor::requests
+    pub count_requests: bool,
+    /// No repo fetches more contributor pages than that, even if users_to_consider needs more.
+    /// Share of such repo is approximate
+    pub max_contributor_pages: u32,
+    /// Forks carry history of the parent, so their bus factor is the parent's one,
+    /// they are skipped unless that is set
+    pub include_forks: bool,
+    /// Scores results, threshold is compared with the score then. None compares leader's
+    /// share, like score::LeaderShare, without adding the score to results
+    pub scorer: Option<Arc<dyn Scorer>>,
+    /// Fail on the first repo that fails, timeouts included. Otherwise such repos are
+    /// skipped and counted, see GithubApi::take_skipped_repos. Errors that would fail
+    /// every other repo too, like rate limit or missing permissions, fail anyway
+    pub fail_fast: bool,
+    /// Rounds in which repos that failed or timed out are tried again, once all others are done,
+    /// with backoff before each round. Separate from retries of single requests
+    pub repo_retries: RetryPolicy,
+    /// Report repos without contributions to consider, like new or empty ones, with
+    /// BusFactor::without_contributors, instead of skipping them. min_contributors does
+    /// not apply to them
+    pub include_empty: bool,
+    /// Fraction of contributions the truck factor has to cover, like 0.8, see share::truck_factor.
+    /// None does not compute it
+    pub coverage: Option<f64>,
+    /// Weight of commits from the last recent_window in shares, within 0..=1, see
+    /// share::blend_recent. 0 uses all-time commits only, otherwise costs a request
+    /// per page of recent commits, up to max_contributor_pages. Only for commits basis
+    pub recency_weight: f64,
+    /// How far back recent commits go
+    pub recent_window: Duration,
 }
-/// Entity used to communicate with api.github.com
-pub struct GithubApi {
-    token: String,
+
+impl Default for BusFactorQuery {
+    fn default() -> Self {
+        Self {
+            bus_threshold: 0.75,
+            users_to_consider: 25,
+            delay_sec: 0.0,
+            min_contributors: 0,
+            basis: ShareBasis::Commits,
+            verify_repos: false,
+            members: None,
+            normalize: false,
+            timeout_per_repo: None,
+            count_requests: false,
+            max_contributor_pages: 3,
+            include_forks: false,
+            scorer: None,
+            fail_fast: false,
+            repo_retries: RetryPolicy::default(),
+            include_empty: false,
+            coverage: None,
+            recency_weight: 0.0,
+            recent_window: Duration::from_secs(90 * 24 * 60 * 60),
+        }
+    }
 }
-#[derive(Debug, PartialEq)]
-// Percentage user share in repository
-pub struct UserShare {
-    pub bus_factor: f64,
-    pub user_name: String,
+
+impl BusFactorQuery {
+    /// Starts from the defaults, validates the values in build
+    pub fn builder() -> BusFactorQueryBuilder {
+        BusFactorQueryBuilder {
+            query: BusFactorQuery::default(),
+        }
+    }
+
+    /// Requests bus factor of a repo is expected to cost, retries and repos with more
+    /// pages of recent commits than one cost more. With ShareBasis::PullRequests most of them
+    /// are searches, those count to the search quota, not to the core one
+    pub fn requests_per_repo(&self) -> u64 {
+        let page_cap = self.max_contributor_pages.max(1);
+        let pages = match self.basis {
+            // Search of issues is requested until the last page, expected is the cap
+            ShareBasis::PullRequests => page_cap.min(SEARCH_RESULTS_CAP / PAGE_LIMIT),
+            _ => self
+                .users_to_consider
+                .div_ceil(PAGE_LIMIT)
+                .clamp(1, page_cap),
+        };
+
+        pages as u64
+            + u64::from(self.normalize)
+            + u64::from(self.recency_weight > 0.0)
+            + u64::from(self.verify_repos)
+    }
 }
 
-#[derive(Debug, PartialEq)]
-/// Contains repo information together with most active user
-pub struct BusFactor {
-    pub leader: UserShare,
-    pub repo_name: String,
-    pub stars: u64,
+/// Builds BusFactorQuery, making sure values make sense
+pub struct BusFactorQueryBuilder {
+    query: BusFactorQuery,
 }
 
-impl GithubApi {
-    pub fn new(token: &str) -> Self {
-        Self {
-            token: token.to_string(),
-        }
+impl BusFactorQueryBuilder {
+    pub fn bus_threshold(mut self, bus_threshold: f64) -> Self {
+        self.query.bus_threshold = bus_threshold;
+        self
     }
 
-    /// For given count elements returns number of full pages, and residual
-    fn get_pages(count: u32) -> (u32, u32) {
-        // Number of pages with PAGE_LIMIT elements
-        let full_pages = count / PAGE_LIMIT;
-        // Last page that has the rest
-        let last_page = count % PAGE_LIMIT;
+    pub fn users_to_consider(mut self, users_to_consider: u32) -> Self {
+        self.query.users_to_consider = users_to_consider;
+        self
+    }
 
-        (full_pages, last_page)
+    pub fn delay_sec(mut self, delay_sec: f64) -> Self {
+        self.query.delay_sec = delay_sec;
+        self
     }
 
-    /// Returns most popular projects (by stars) for given language in descending order
-    pub async fn get_repos(&self, repo_query: &RepoQuery<'_>) -> Result<Repos, Box<dyn Error>> {
-        let (full_pages, last_page) = GithubApi::get_pages(repo_query.count);
+    pub fn min_contributors(mut self, min_contributors: u32) -> Self {
+        self.query.min_contributors = min_contributors;
+        self
+    }
 
-        let query = format!(
-            "?q=language:{language}&sort=stars&order=desc",
-            language = repo_query.language
-        );
+    pub fn basis(mut self, basis: ShareBasis) -> Self {
+        self.query.basis = basis;
+        self
+    }
 
-        let mut futures = vec![];
-        // Accumulate repos from all full pages, page numbering starts from 1, not 0
-        for page in 1..=full_pages {
-            futures.push(self.get_repos_from_page(&query, page, PAGE_LIMIT));
+    pub fn verify_repos(mut self, verify_repos: bool) -> Self {
+        self.query.verify_repos = verify_repos;
+        self
+    }
+
+    pub fn members(mut self, members: Option<HashSet<String>>) -> Self {
+        self.query.members = members.map(|members| {
+            members
+                .iter()
+                .map(|login| github_data::login_key(login))
+                .collect()
+        });
+        self
+    }
+
+    pub fn normalize(mut self, normalize: bool) -> Self {
+        self.query.normalize = normalize;
+        self
+    }
+
+    pub fn timeout_per_repo(mut self, timeout_per_repo: Option<Duration>) -> Self {
+        self.query.timeout_per_repo = timeout_per_repo;
+        self
+    }
+
+    pub fn count_requests(mut self, count_requests: bool) -> Self {
+        self.query.count_requests = count_requests;
+        self
+    }
+
+    pub fn scorer(mut self, scorer: Option<Arc<dyn Scorer>>) -> Self {
+        self.query.scorer = scorer;
+        self
+    }
+
+    pub fn max_contributor_pages(mut self, max_contributor_pages: u32) -> Self {
+        self.query.max_contributor_pages = max_contributor_pages;
+        self
+    }
+
+    pub fn include_forks(mut self, include_forks: bool) -> Self {
+        self.query.include_forks = include_forks;
+        self
+    }
+
+    pub fn fail_fast(mut self, fail_fast: bool) -> Self {
+        self.query.fail_fast = fail_fast;
+        self
+    }
+
+    pub fn repo_retries(mut self, repo_retries: RetryPolicy) -> Self {
+        self.query.repo_retries = repo_retries;
+        self
+    }
+
+    pub fn include_empty(mut self, include_empty: bool) -> Self {
+        self.query.include_empty = include_empty;
+        self
+    }
+
+    pub fn coverage(mut self, coverage: Option<f64>) -> Self {
+        self.query.coverage = coverage;
+        self
+    }
+
+    pub fn recency_weight(mut self, recency_weight: f64) -> Self {
+        self.query.recency_weight = recency_weight;
+        self
+    }
+
+    pub fn recent_window(mut self, recent_window: Duration) -> Self {
+        self.query.recent_window = recent_window;
+        self
+    }
+
+    /// Fails if threshold is not within 0..=1, users_to_consider is not within
+    /// 1..=500, delay is negative, max_contributor_pages is 0, coverage is not within 0..=1,
+    /// or recency weight is not within 0..=1
+    pub fn build(self) -> Result<BusFactorQuery, InvalidQueryError> {
+        let query = self.query;
+
+        if !(0.0..=1.0).contains(&query.bus_threshold) {
+            return Err(InvalidQueryError::new(&format!(
+                "Bus threshold {} is not within 0..=1",
+                query.bus_threshold
+            )));
         }
 
-        if last_page > 0 {
-            // Get number of pages for last request
-            let last_page_elements = match full_pages {
-                // If there are no full pages, get exactly last_page elements
-                0 => last_page,
-                // If there are full pages, get full page, to have pagination right
-                _ => PAGE_LIMIT,
-            };
-            futures.push(self.get_repos_from_page(&query, full_pages + 1, last_page_elements));
+        if !(1..=MAX_USERS_TO_CONSIDER).contains(&query.users_to_consider) {
+            return Err(InvalidQueryError::new(&format!(
+                "Number of users to consider {} is not within 1..={}",
+                query.users_to_consider, MAX_USERS_TO_CONSIDER
+            )));
         }
 
-        let mut responses = Vec::new();
-        dbg!(repo_query);
-        if repo_query.delay_sec > 0.0 {
-            for element in futures {
-                responses.push(element.await);
-                println!("after push");
-                tokio::time::sleep(Duration::from_secs_f64(repo_query.delay_sec)).await;
-                println!("after sleep");
+        // Also catches NaN
+        if !(query.delay_sec >= 0.0 && query.delay_sec.is_finite()) {
+            return Err(InvalidQueryError::new(&format!(
+                "Delay {} must be a non-negative number of seconds",
+                query.delay_sec
+            )));
+        }
 
+        if let Some(coverage) = query.coverage {
+            // Nothing to cover with 0
+            if !(coverage > 0.0 && coverage <= 1.0) {
+                return Err(InvalidQueryError::new(&format!(
+                    "Coverage {} is not within 0..=1, above 0",
+                    coverage
+                )));
             }
+        }
 
-        } else {
-            // Execute all requests concurrently, responses are in the same order as futures
-            responses = futures::future::join_all(futures).await;
+        if query.max_contributor_pages == 0 {
+            return Err(InvalidQueryError::new(
+                "At least one contributor page has to be fetched",
+            ));
         }
 
-        // Last element is treated differently, exclude it from following iteration
-        let mut last_response = None;
-        if last_page > 0 {
-            last_response = responses.pop();
+        if !(0.0..=1.0).contains(&query.recency_weight) {
+            return Err(InvalidQueryError::new(&format!(
+                "Recency weight {} is not within 0..=1",
+                query.recency_weight
+            )));
         }
 
-        let mut result = Repos::default();
-        for res in responses {
-            let repos = res?;
-            result.items.extend_from_slice(&repos.items);
+        if query.recency_weight > 0.0 && query.basis != ShareBasis::Commits {
+            return Err(InvalidQueryError::new(
+                "Recent activity is counted in commits, recency weight is for commits basis",
+            ));
         }
 
-        if let Some(last_result) = last_response {
-            let last_repos = last_result?;
-            // From last result get exactly last_page elements
-            result
-                .items
-                .extend_from_slice(&last_repos.items[0..last_page as usize])
+        if query.normalize && query.basis == ShareBasis::PullRequests {
+            return Err(InvalidQueryError::new(
+                "Shares of pull requests are relative to all merged ones already, normalize is for commits",
+            ));
         }
 
-        Ok(result)
+        Ok(query)
     }
+}
 
-    /// Helper function that returns repositories on given page
-    async fn get_repos_from_page(
-        &self,
-        query: &str,
-        page: u32,
-        per_page: u32,
-    ) -> Result<Repos, Box<dyn Error>> {
-        let endpoint = format!(
-            "{endpoint}{query}&per_page={per_page}&page={page}",
-            endpoint = REPO_ENDPONT,
-            query = query,
-            per_page = per_page,
-            page = page
-        );
+#[derive(Debug, Clone, PartialEq)]
+/// Group of users bus factor can be restricted to
+pub enum MemberScope {
+    Org(String),
+    Team { org: String, team: String },
+}
 
-        debug!("Repos endpoint {}", endpoint);
+impl MemberScope {
+    fn endpoint(&self) -> String {
+        match self {
+            MemberScope::Org(org) => format!("https://api.github.com/orgs/{}/members", org),
+            MemberScope::Team { org, team } => {
+                format!("https://api.github.com/orgs/{}/teams/{}/members", org, team)
+            }
+        }
+    }
+}
 
-        // Create separate client for each call
-        let repos = GithubClient::new(&self.token)
-            .get_response_body::<Repos>(&endpoint)
-            .await?;
+impl FromStr for MemberScope {
+    type Err = InvalidQueryError;
 
-        Ok(repos)
+    /// Parses team given as org/team
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('/') {
+            Some((org, team)) if !org.is_empty() && !team.is_empty() => Ok(MemberScope::Team {
+                org: org.to_string(),
+                team: team.to_string(),
+            }),
+            _ => Err(InvalidQueryError::new(&format!(
+                "Team '{}' is not in the org/team format",
+                s
+            ))),
+        }
     }
+}
 
-    async fn worker(&self, jobs : &[RepoData], query: &BusFactorQuery) -> Vec<Result<UserShare, Box<dyn Error>>> {
-        let mut local_res = Vec::new();
+#[derive(Debug, Clone, PartialEq)]
+/// Repo given by name, like "owner/name"
+pub struct FullName {
+    pub owner: String,
+    pub name: String,
+}
 
-        for job in jobs {
-            info!("getting repo share...");
-            let res = self.calculate_repo_share(&job.contributors_url, query.users_to_consider).await;
+impl FullName {
+    /// True if it names the repo, github names are case insensitive
+    pub fn matches(&self, full_name: &str) -> bool {
+        full_name.eq_ignore_ascii_case(&self.to_string())
+    }
+}
 
-            local_res.push(res);
+impl fmt::Display for FullName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.owner, self.name)
+    }
+}
 
-            tokio::time::sleep(Duration::from_secs_f64(query.delay_sec)).await;
-        }
+impl FromStr for FullName {
+    type Err = InvalidQueryError;
 
-        local_res
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('/') {
+            Some((owner, name)) if !owner.is_empty() && !name.is_empty() && !name.contains('/') => {
+                Ok(FullName {
+                    owner: owner.to_string(),
+                    name: name.to_string(),
+                })
+            }
+            _ => Err(InvalidQueryError::new(&format!(
+                "Repo '{}' is not in the owner/name format",
+                s
+            ))),
+        }
     }
+}
 
-    /// Calculates bus factor for each repo. Returns collection of repos that has
-    /// factor significant.
-    pub async fn get_repos_bus_factor(
-        &self,
-        repos: &Repos,
-        query: &BusFactorQuery,
-    ) -> Result<Vec<BusFactor>, Box<dyn Error>> {
-        let mut futures = Vec::new();
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Share of the leader above which repo is considered at risk
+pub enum Threshold {
+    Fixed(f64),
+    /// Taken from the results, repos above AUTO_THRESHOLD_PERCENTILE of leader shares
+    Auto,
+}
 
-        // Generate futures
-        // for item in &repos.items {
-        //     futures
-        //         .push(self.calculate_repo_share(&item.contributors_url, query.users_to_consider));
-        // }
+impl Threshold {
+    /// Actual threshold for given results, for Auto it's the value only the top
+    /// scores, leader's shares by default, reach. With no results there is nothing to be above it
+    pub fn resolve(&self, results: &[BusFactor]) -> f64 {
+        match self {
+            Threshold::Fixed(threshold) => *threshold,
+            Threshold::Auto => {
+                let shares: Vec<f64> = results.iter().map(BusFactor::score).collect();
+                share::percentile(&shares, AUTO_THRESHOLD_PERCENTILE).unwrap_or(1.0)
+            }
+        }
+    }
+}
 
-        // Number of api calls that can be executed at once
-        let n_workers = 5;
+impl FromStr for Threshold {
+    type Err = InvalidQueryError;
 
-        let jobs_count = repos.items.len() / n_workers;
+    /// Parses "auto", or share within 0..=1
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "auto" {
+            return Ok(Threshold::Auto);
+        }
 
-        // Last chunk may contain less than jobs_count elements, the remainder
-        for chunk in repos.items.chunks(jobs_count) {
-            futures.push(self.worker(chunk, query));
+        match s.parse::<f64>() {
+            Ok(threshold) if (0.0..=1.0).contains(&threshold) => Ok(Threshold::Fixed(threshold)),
+            _ => Err(InvalidQueryError::new(&format!(
+                "Threshold '{}' is neither auto, nor a number within 0..=1",
+                s
+            ))),
         }
+    }
+}
 
+/// Share of a single repo, with number of requests it cost
+type CountedShare = (Result<Option<RepoShare>, SendError>, u32);
 
-        let responses = futures::future::join_all(futures).await;
+/// Spawned task, aborted when dropped, so cancelled call does not leave requests running
+struct Task<T>(JoinHandle<T>);
 
-        // flatten partial results to vector of all results
-        let responses : Vec<Result<UserShare, Box<dyn Error>>> = responses.into_iter().flatten().collect();
-        // if query.delay_sec > 0.0 {
-        //     for (idx, element) in futures.into_iter().enumerate() {
-        //         let repo = &repos.items[idx];
+impl<T> Future for Task<T> {
+    type Output = Result<T, JoinError>;
 
-        //         let share = element.await;
-        //         tokio::time::sleep(Duration::from_secs_f64(query.delay_sec)).await;
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.0).poll(cx)
+    }
+}
 
-        //         if let Ok(s) = &share {
+impl<T> Drop for Task<T> {
+    fn drop(&mut self) {
+        // No-op for finished task
+        self.0.abort();
+    }
+}
 
-        //             info!(
-        //                 "Project {}, stars {} has bus factor {} for user {}",
-        //                 repo.name,
-        //                 repo.stargazers_count,
-        //                 s.bus_factor,
-        //                 s.user_name
-        //             );
-        //         }
+/// Contributor pages by endpoint, cell is shared by all requests for the same endpoint
+type ContributionsCache = Mutex<HashMap<String, Arc<OnceCell<Contributions>>>>;
 
-        //         responses.push(share);
-        //     }
-        // } else {
-            // // Execute all requests concurrently, responses are in the same order as futures
-            // for chunk in futures.chunks(10) {
+#[derive(Clone)]
+/// Entity used to communicate with api.github.com.
+/// Cheap to clone, clones share the client with its connections and concurrency limit,
+/// cache of contributor pages, and recorded latencies, so they can be moved to other
+/// tasks and still behave as one api. Settings changed on a clone with with_* are its own
+pub struct GithubApi {
+    client: Arc<GithubClient>,
+    /// Replaces scheme and host of contributor and repo urls, like mock server or mirror
+    contributors_base: Option<String>,
+    /// Search is requested from there instead of the api
+    search_base: Option<String>,
+    /// Pages fetched in this run, the same endpoint is requested only once
+    contributions: Arc<ContributionsCache>,
+    /// How long each repo took, recorded only if timing is enabled
+    repo_latencies: Option<Arc<Latencies>>,
+    /// Repos skipped because of errors, like timeouts, see take_skipped_repos
+    skipped: Arc<AtomicU64>,
+    /// Warnings of this part of the run, see scoped, the client keeps them otherwise
+    warnings: Option<Arc<Warnings>>,
+    /// Contributor pages are written there as fetched, see with_raw_dump
+    raw_dir: Option<PathBuf>,
+}
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+// Percentage user share in repository
+pub struct UserShare {
+    /// NaN if there is no leader, written as null in JSON
+    #[serde(deserialize_with = "nan_if_null")]
+    pub bus_factor: f64,
+    pub user_name: String,
+    /// Github profile of the user, for presentation only. None if unknown,
+    /// like for anonymous contributors, local clones or older reports
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avatar_url: Option<String>,
+}
 
-            //     responses.append(futures::future::join_all(chunk).await);
-            // }
-        // }
+/// Reads null, what serde_json writes for NaN, back as NaN
+pub(crate) fn nan_if_null<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    Ok(Option::<f64>::deserialize(deserializer)?.unwrap_or(f64::NAN))
+}
 
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+/// How much repository depends on the single person, ordered from the safest
+pub enum RiskLevel {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
 
-        let mut res = Vec::<BusFactor>::new();
-        // Well, unstable
-        // for (response, repo) in zip(&responses, &repos.items)  {
-        for (idx, item) in responses.into_iter().enumerate() {
-            let share = item?;
-            // responses, and repo has the same amount of elements
-            let repo = &repos.items[idx];
+impl fmt::Display for RiskLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            RiskLevel::Low => "Low",
+            RiskLevel::Medium => "Medium",
+            RiskLevel::High => "High",
+            RiskLevel::Critical => "Critical",
+        };
+        // pad, so it's possible to align it in columns
+        f.pad(name)
+    }
+}
 
-            trace!(
-                "Project {}, stars {} has bus factor {} for user {}",
-                repo.name,
-                repo.stargazers_count,
-                share.bus_factor,
-                share.user_name
-            );
+/// Classifies leader's share:
+/// - Critical: 90% and more, project is practically a one man show
+/// - High: from 75%, matches the default bus threshold
+/// - Medium: from 50%, leader does the majority of work
+/// - Low: below 50%
+pub fn risk_level(bus_factor: f64) -> RiskLevel {
+    if bus_factor >= 0.9 {
+        RiskLevel::Critical
+    } else if bus_factor >= 0.75 {
+        RiskLevel::High
+    } else if bus_factor >= 0.5 {
+        RiskLevel::Medium
+    } else {
+        RiskLevel::Low
+    }
+}
 
-            if share.bus_factor >= query.bus_threshold {
-                res.push(BusFactor {
-                    repo_name: repo.name.to_owned(),
-                    stars: repo.stargazers_count,
-                    leader: share,
-                })
-            }
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+/// Contains repo information together with most active user
+pub struct BusFactor {
+    pub leader: UserShare,
+    /// Id of the repo, 0 if unknown, like in reports from older versions
+    #[serde(default)]
+    pub repo_id: u64,
+    pub repo_name: String,
+    pub stars: u64,
+    /// Derived from leader's share
+    pub risk_level: RiskLevel,
+    /// Requests sent for the repo, retries included. Set only if BusFactorQuery::count_requests
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests: Option<u32>,
+    /// Main language of the repo, as github sees it
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub language: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub topics: Vec<String>,
+    /// Leader is the owner of the repo, high share is expected then
+    #[serde(default)]
+    pub owner_led: bool,
+    /// Like "owner/name", empty in reports from older versions
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub full_name: String,
+    /// How much the repo is used, size of its fork network. Set only by GithubApi::add_usage
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<u64>,
+    /// Reported because it was asked for by name, regardless of the threshold and filters
+    #[serde(default)]
+    pub always_included: bool,
+    /// Given by BusFactorQuery::scorer, None means leader's share, see score
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<f64>,
+    /// Open issues and pull requests of the repo, None if unknown, like for a local clone
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub open_issues: Option<u64>,
+    /// Repo has no contributions to consider, leader is unknown and the share is NaN.
+    /// Reported only with BusFactorQuery::include_empty
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub no_contributors: bool,
+    /// When the repo was created, None if unknown
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+    /// Minimal number of top contributors covering BusFactorQuery::coverage of contributions,
+    /// set only if the coverage is given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub truck_factor: Option<usize>,
+    /// Contributions of the leader. Share is that over true_total if set,
+    /// over total_considered_contributions otherwise, unless blended with recent commits,
+    /// see BusFactorQuery::recency_weight. None if unknown, like in older reports
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub leader_contributions: Option<u64>,
+    /// Sum of contributions of the considered contributors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_considered_contributions: Option<u64>,
+    /// All contributions of the repo, set only if the share is normalized, see BusFactorQuery::normalize
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub true_total: Option<u64>,
+    /// Repo was found, but its bus factor was not calculated, see get_top_repos_bus_factor.
+    /// Leader is unknown and the share is NaN
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub not_calculated: bool,
+    /// There may be more contributors than were fetched, like when max_contributor_pages
+    /// cut them, share is relative to the fetched ones only, see RepoShare::approximate
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub approximate: bool,
+}
+
+impl BusFactor {
+    pub fn new(repo_name: &str, stars: u64, leader: UserShare) -> Self {
+        Self {
+            risk_level: risk_level(leader.bus_factor),
+            repo_id: 0,
+            repo_name: repo_name.to_string(),
+            stars,
+            leader,
+            requests: None,
+            language: String::new(),
+            topics: Vec::new(),
+            owner_led: false,
+            full_name: String::new(),
+            usage: None,
+            always_included: false,
+            score: None,
+            open_issues: None,
+            no_contributors: false,
+            created_at: None,
+            truck_factor: None,
+            leader_contributions: None,
+            total_considered_contributions: None,
+            true_total: None,
+            not_calculated: false,
+            approximate: false,
         }
+    }
 
-        Ok(res)
+    /// Result of the share, with the counts behind it
+    pub fn from_share(repo_name: &str, stars: u64, share: RepoShare) -> Self {
+        Self {
+            leader_contributions: Some(share.leader_contributions),
+            total_considered_contributions: Some(share.considered_contributions),
+            true_total: share.normalized.then_some(share.total_contributions),
+            approximate: share.approximate,
+            ..BusFactor::new(repo_name, stars, share.leader)
+        }
     }
 
-    /// Gets share of contribution for most active user among users_to_consider
-    async fn calculate_repo_share(
-        &self,
-        contributors_url: &str,
-        users_to_consider: u32,
-    ) -> Result<UserShare, Box<dyn Error>> {
-        if users_to_consider == 0 {
-            // Such request does not make any sense
-            return Err(Box::new(InvalidQueryError::new(
-                "Number of users to consider must be greater than 0.",
-            )));
+    /// Repo without contributions to consider, it's never significant
+    pub fn without_contributors(repo_name: &str, stars: u64) -> Self {
+        Self {
+            no_contributors: true,
+            ..BusFactor::new(
+                repo_name,
+                stars,
+                UserShare {
+                    bus_factor: f64::NAN,
+                    ..UserShare::default()
+                },
+            )
         }
+    }
 
-        let endpoint = format!(
-            "{contributors_url}?per_page={per_page}",
-            contributors_url = contributors_url,
-            per_page = users_to_consider
-        );
+    /// Repo listed without calculating anything for it, it's never significant
+    pub fn not_calculated(repo: &RepoData) -> Self {
+        Self {
+            not_calculated: true,
+            repo_id: repo.id,
+            language: repo.language.clone(),
+            topics: repo.topics.clone(),
+            full_name: repo.full_name.clone(),
+            open_issues: Some(repo.open_issues_count),
+            created_at: repo.created_at,
+            ..BusFactor::new(
+                &repo.name,
+                repo.stargazers_count,
+                UserShare {
+                    bus_factor: f64::NAN,
+                    ..UserShare::default()
+                },
+            )
+        }
+    }
 
-        trace!("Contributors endpoint {}", endpoint);
+    /// False if there is no leader and share to show, like for repo without contributions
+    pub fn has_share(&self) -> bool {
+        !self.no_contributors && !self.not_calculated
+    }
 
-        let contributions = GithubClient::new(&self.token)
-            .get_response_body::<Contributions>(&endpoint)
-            .await?;
+    /// True if both describe the same repo, by id if known, or by name
+    pub fn is_same_repo(&self, other: &BusFactor) -> bool {
+        if self.repo_id != 0 && other.repo_id != 0 {
+            self.repo_id == other.repo_id
+        } else {
+            self.repo_name == other.repo_name
+        }
+    }
 
-        let total_contributions = contributions
-            .iter()
-            .fold(0, |acc, contr| acc + contr.contributions);
+    /// Score the threshold is compared with, leader's share unless a scorer gave another one
+    pub fn score(&self) -> f64 {
+        self.score.unwrap_or(self.leader.bus_factor)
+    }
 
-        // Assuming there is always at least one contribution
-        // Contributions are sorted in descending order, so first element
-        // is contributor with highest activity.
-        let leader = &contributions[0];
-        let bus_factor = leader.contributions as f64 / total_contributions as f64;
+    /// True if score, leader's share by default, reaches the threshold
+    pub fn is_significant(&self, bus_threshold: f64) -> bool {
+        self.score() >= bus_threshold
+    }
 
-        Ok(UserShare {
-            user_name: leader.login.to_string(),
-            bus_factor,
-        })
+    /// Ascending order by leader's share, for sort_by. NaN share is the lowest one
+    pub fn by_leader_share(a: &BusFactor, b: &BusFactor) -> Ordering {
+        nan_lowest(a.leader.bus_factor, b.leader.bus_factor)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use std::{fs, path::PathBuf};
+    /// Ascending order by score, for sort_by. NaN score is the lowest one
+    pub fn by_score(a: &BusFactor, b: &BusFactor) -> Ordering {
+        nan_lowest(a.score(), b.score())
+    }
 
-    use reqwest::{header::USER_AGENT, StatusCode};
+    /// Ascending order by stars, for sort_by
+    pub fn by_stars(a: &BusFactor, b: &BusFactor) -> Ordering {
+        a.stars.cmp(&b.stars)
+    }
 
-    use super::*;
+    /// Ascending order by open issues, unknown ones are the lowest, for sort_by
+    pub fn by_open_issues(a: &BusFactor, b: &BusFactor) -> Ordering {
+        a.open_issues.cmp(&b.open_issues)
+    }
+}
 
-    #[test]
-    fn test_get_pages() {
-        // Result that fits on one page
-        let (full_pages, last_page) = GithubApi::get_pages(50);
-        assert_eq!(full_pages, 0);
-        assert_eq!(last_page, 50);
+fn nan_lowest(a: f64, b: f64) -> Ordering {
+    match (a.is_nan(), b.is_nan()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+        (false, false) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+    }
+}
 
-        // Result that comes to second page
-        let (full_pages, last_page) = GithubApi::get_pages(101);
-        assert_eq!(full_pages, 1);
-        assert_eq!(last_page, 1);
+impl GithubApi {
+    pub fn new(token: &str) -> Self {
+        Self::with_client(GithubClient::new(token))
+    }
 
-        // Huuuge result
-        let (full_pages, last_page) = GithubApi::get_pages(2531);
-        assert_eq!(full_pages, 25);
+    /// Creates api that sends requests as described by the config
+    pub fn with_config(token: &str, config: &TransportConfig) -> Result<Self, SendError> {
+        Ok(Self::with_client(GithubClient::with_config(token, config)?))
+    }
+
+    /// Creates api that answers every request from responses recorded in dir,
+    /// see GithubClient::offline
+    pub fn offline(dir: &Path, config: &TransportConfig) -> Result<Self, SendError> {
+        Ok(Self::with_client(GithubClient::offline(dir, config)?))
+    }
+
+    /// Creates api that uses already configured client
+    pub fn with_client(client: GithubClient) -> Self {
+        Self {
+            client: Arc::new(client),
+            contributors_base: None,
+            search_base: None,
+            contributions: Arc::default(),
+            repo_latencies: None,
+            skipped: Arc::default(),
+            warnings: None,
+            raw_dir: None,
+        }
+    }
+
+    /// Clone with its own skipped repos and warnings, for a part of the run done
+    /// at the same time as others, like a language of the batch. The rest is shared
+    pub fn scoped(&self) -> Self {
+        Self {
+            skipped: Arc::default(),
+            warnings: Some(Arc::default()),
+            repo_latencies: self.repo_latencies.as_ref().map(|_| Arc::default()),
+            ..self.clone()
+        }
+    }
+
+    /// Records how long bus factor of each repo took, see take_repo_latencies
+    pub fn with_timing(mut self) -> Self {
+        self.repo_latencies = Some(Arc::default());
+        self
+    }
+
+    /// Returns latencies of repos processed since the last call, empty if timing is not enabled
+    pub fn take_repo_latencies(&self) -> Vec<Duration> {
+        self.repo_latencies
+            .as_ref()
+            .map(|latencies| latencies.take())
+            .unwrap_or_default()
+    }
+
+    /// Returns number of repos skipped because of errors since the last call.
+    /// Such repos are not in the results, the rest of them is still calculated
+    pub fn take_skipped_repos(&self) -> u64 {
+        self.skipped.swap(0, AtomicOrdering::Relaxed)
+    }
+
+    /// Returns warnings raised since the last call, by this api and its clones.
+    /// They are logged as well. Those of the client, like rate limit, are shared by
+    /// scoped clones too, they go to the one that takes them first
+    pub fn take_warnings(&self) -> Vec<RunWarning> {
+        let mut warnings = self
+            .warnings
+            .as_ref()
+            .map(|warnings| warnings.take())
+            .unwrap_or_default();
+        warnings.extend(self.client.take_warnings());
+        warnings
+    }
+
+    fn warn(&self, category: WarningCategory, message: String) {
+        match &self.warnings {
+            Some(warnings) => warnings.raise(category, message),
+            None => self.client.warn(category, message),
+        }
+    }
+
+    /// Requests sent so far, shared by all clones
+    pub fn request_stats(&self) -> RequestStats {
+        self.client.request_stats()
+    }
+
+    /// Contributors, and other requests for a single repo, like its metadata, are requested
+    /// from given base, like "http://localhost:8080", instead of the host from contributors_url.
+    /// Search is not affected, see with_search_base
+    pub fn with_contributors_base(mut self, base: &str) -> Self {
+        self.contributors_base = Some(base.trim_end_matches('/').to_string());
+        self
+    }
+
+    /// Writes every contributor page, as fetched, to dir/owner/name/contributors-N.json,
+    /// N is the number of the page. Page is written once, when it's fetched, pages taken
+    /// from the cache of the run are not written again
+    pub fn with_raw_dump(mut self, dir: &Path) -> Self {
+        self.raw_dir = Some(dir.to_path_buf());
+        self
+    }
+
+    /// Search is requested from given base, like a mirror, instead of api.github.com.
+    /// Contributors are not affected, urls from search results are used as they are,
+    /// unless with_contributors_base is given too
+    pub fn with_search_base(mut self, base: &str) -> Self {
+        self.search_base = Some(base.trim_end_matches('/').to_string());
+        self
+    }
+
+    fn search_endpoint(&self) -> String {
+        match &self.search_base {
+            Some(base) => format!("{}/{}", base, SEARCH_PATH),
+            None => REPO_ENDPONT.to_string(),
+        }
+    }
+
+    /// Search of issues, goes to the same base as search of repos
+    fn issue_search_endpoint(&self) -> String {
+        format!(
+            "{}/{}",
+            self.search_base.as_deref().unwrap_or(API_BASE),
+            ISSUE_SEARCH_PATH
+        )
+    }
+
+    /// Url of the contributors endpoint, or of other one of the repo. Relative url is resolved
+    /// against the api, or against the override, which also replaces scheme and host of absolute one
+    fn resolve_contributors_url(&self, contributors_url: &str) -> String {
+        let path = match contributors_url.find("://") {
+            Some(scheme) => {
+                let host_start = scheme + "://".len();
+                match contributors_url[host_start..].find('/') {
+                    Some(path_start) => &contributors_url[host_start + path_start..],
+                    None => "",
+                }
+            }
+            None => contributors_url,
+        };
+        let absolute = path.len() != contributors_url.len();
+
+        match &self.contributors_base {
+            Some(base) => format!("{}/{}", base, path.trim_start_matches('/')),
+            None if absolute => contributors_url.to_string(),
+            None => format!("{}/{}", API_BASE, path.trim_start_matches('/')),
+        }
+    }
+
+    /// Url of the commits endpoint of the repo, next to its contributors one. Url not ending
+    /// with /contributors is an error, some other part of it could be replaced otherwise
+    fn commits_url(contributors_url: &str) -> Result<String, SendError> {
+        match contributors_url.strip_suffix("/contributors") {
+            Some(repo_url) => Ok(format!("{}/commits", repo_url)),
+            None => Err(Box::new(ResponseError::new(&format!(
+                "Contributors url {} does not end with /contributors",
+                contributors_url
+            )))),
+        }
+    }
+
+    /// For given count elements returns number of full pages, and residual
+    fn get_pages(count: u32) -> (u32, u32) {
+        // Number of pages with PAGE_LIMIT elements
+        let full_pages = count / PAGE_LIMIT;
+        // Last page that has the rest
+        let last_page = count % PAGE_LIMIT;
+
+        (full_pages, last_page)
+    }
+
+    /// Count of repos the search can actually give, warns if the requested one is above the cap
+    fn capped_count(count: u32) -> u32 {
+        if count > SEARCH_RESULTS_CAP {
+            warn!(
+                "Search gives at most {} repos, count {} is cut to that",
+                SEARCH_RESULTS_CAP, count
+            );
+        }
+
+        count.min(SEARCH_RESULTS_CAP)
+    }
+
+    /// Returns time after which searching for count repos again does not exceed
+    /// the search rate limit, used to pace consecutive searches
+    pub fn search_pacing(count: u32) -> Duration {
+        let (full_pages, last_page) = GithubApi::get_pages(count);
+        let requests = full_pages + u32::from(last_page > 0);
+
+        Duration::from_secs(60) * requests / SEARCH_REQUESTS_PER_MINUTE
+    }
+
+    /// Builds search part of the url for given query.
+    /// Returns InvalidQueryError for empty stars range.
+    fn build_query(repo_query: &RepoQuery) -> Result<String, InvalidQueryError> {
+        let mut qualifiers = Vec::new();
+
+        if let Some(language) = repo_query.language {
+            qualifiers.push(format!("language:{}", language));
+        }
+
+        match (repo_query.min_stars, repo_query.max_stars) {
+            (Some(min_stars), Some(max_stars)) if min_stars > max_stars => {
+                return Err(InvalidQueryError::new(&format!(
+                    "Max stars {} is lower than min stars {}",
+                    max_stars, min_stars
+                )));
+            }
+            (Some(min_stars), Some(max_stars)) => {
+                qualifiers.push(format!("stars:{}..{}", min_stars, max_stars))
+            }
+            (Some(min_stars), None) => qualifiers.push(format!("stars:>={}", min_stars)),
+            (None, Some(max_stars)) => qualifiers.push(format!("stars:<={}", max_stars)),
+            (None, None) => {}
+        }
+
+        if let Some(topic) = repo_query.topic {
+            qualifiers.push(format!("topic:{}", topic));
+        }
+
+        // Search needs at least one qualifier, this one matches every repo worth a look
+        if qualifiers.is_empty() {
+            qualifiers.push("stars:>0".to_string());
+        }
+
+        // Qualifiers are separated with space, encoded as +
+        let mut query = format!("?q={}", qualifiers.join("+"));
+
+        match repo_query.sort {
+            RepoSort::Stars => query.push_str("&sort=stars&order=desc"),
+            // Lack of sort means best match
+            RepoSort::BestMatch => {}
+            RepoSort::HelpWantedIssues => query.push_str("&sort=help-wanted-issues&order=desc"),
+        }
+
+        Ok(query)
+    }
+
+    /// Search query as it's sent, like "q=language:rust+stars:>=100&sort=stars&order=desc",
+    /// without the page, so it can be compared with the search of the web UI.
+    /// Token goes in a header, it's never part of the query
+    pub fn search_query(repo_query: &RepoQuery) -> Result<String, InvalidQueryError> {
+        Ok(GithubApi::build_query(repo_query)?
+            .trim_start_matches('?')
+            .to_string())
+    }
+
+    /// Query of a search about to be sent, logged at info level
+    fn sent_query(repo_query: &RepoQuery) -> Result<String, InvalidQueryError> {
+        let query = GithubApi::build_query(repo_query)?;
+        info!("Search query {}", query.trim_start_matches('?'));
+        Ok(query)
+    }
+
+    /// Removes repos that already appeared earlier in the list, keeping the order.
+    /// Pages are fetched at different moments, if ranking changes meanwhile,
+    /// the same repo may show up on two pages.
+    fn dedup(repos: &mut Repos) {
+        let mut seen = HashSet::new();
+        let before = repos.items.len();
+
+        // Repos without url can not be compared, they are kept
+        repos.items.retain(|repo| {
+            repo.contributors_url.is_empty() || seen.insert(repo.contributors_url.clone())
+        });
+
+        if repos.items.len() < before {
+            info!(
+                "Removed {} duplicated repos from search results",
+                before - repos.items.len()
+            );
+        }
+    }
+
+    /// Brings repos sorted by stars to the order that does not depend on the moment
+    /// pages were fetched: stars descending, ties by id. Search orders ties arbitrarily,
+    /// and may do it differently for each page.
+    fn sort_by_stars(repos: &mut Repos) {
+        // Page fetched later starting with more stars than previous one ended with,
+        // means stars changed in between, and some repo could fall between pages
+        let drifted = repos
+            .items
+            .windows(2)
+            .filter(|pair| pair[0].stargazers_count < pair[1].stargazers_count)
+            .count();
+        if drifted > 0 {
+            debug!(
+                "Stars changed while paging, {} repos out of order, some may be missed",
+                drifted
+            );
+        }
+
+        repos
+            .items
+            .sort_by_key(|repo| (std::cmp::Reverse(repo.stargazers_count), repo.id));
+    }
+
+    /// Returns projects for given language, by default most popular (by stars)
+    /// in descending order. Duplicates are removed, which means that for less stable
+    /// orderings result may contain fewer repos than requested.
+    ///
+    /// Search has no cursors, only page numbers, so if stars change between requests
+    /// repo can move to already fetched page, and be missed, or to the next one,
+    /// and be seen twice. It can't be avoided, only made less likely: without
+    /// delay_sec all pages are requested at once, so window for the change is as short
+    /// as possible, and results sorted by stars get deterministic order of ties.
+    pub async fn get_repos(&self, repo_query: &RepoQuery<'_>) -> Result<Repos, SendError> {
+        if repo_query.count == 0 {
+            return Ok(Repos::default());
+        }
+
+        let (full_pages, last_page) =
+            GithubApi::get_pages(GithubApi::capped_count(repo_query.count));
+
+        let query = GithubApi::sent_query(repo_query)?;
+
+        let mut futures = vec![];
+        // Accumulate repos from all full pages, page numbering starts from 1, not 0
+        for page in 1..=full_pages {
+            futures.push(self.get_repos_from_page(&query, page, PAGE_LIMIT));
+        }
+
+        if last_page > 0 {
+            // Get number of pages for last request
+            let last_page_elements = match full_pages {
+                // If there are no full pages, get exactly last_page elements
+                0 => last_page,
+                // If there are full pages, get full page, to have pagination right
+                _ => PAGE_LIMIT,
+            };
+            futures.push(self.get_repos_from_page(&query, full_pages + 1, last_page_elements));
+        }
+
+        let mut responses = Vec::new();
+        trace!("{:?}", repo_query);
+        if repo_query.delay_sec > 0.0 {
+            for element in futures {
+                responses.push(element.await);
+                tokio::time::sleep(Duration::from_secs_f64(repo_query.delay_sec)).await;
+            }
+        } else {
+            // Execute all requests concurrently, responses are in the same order as futures
+            responses = futures::future::join_all(futures).await;
+        }
+
+        // Last element is treated differently, exclude it from following iteration
+        let mut last_response = None;
+        if last_page > 0 {
+            last_response = responses.pop();
+        }
+
+        let mut result = Repos::default();
+        for res in responses {
+            let repos = res?;
+            result.incomplete_results |= repos.incomplete_results;
+            result.total_count = result.total_count.max(repos.total_count);
+            result.items.extend_from_slice(&repos.items);
+        }
+
+        if let Some(last_result) = last_response {
+            let last_repos = last_result?;
+            result.incomplete_results |= last_repos.incomplete_results;
+            result.total_count = result.total_count.max(last_repos.total_count);
+            // From last result get last_page elements. Page may have less than that,
+            // when search runs out of results, or hits the cap of 1000
+            let available = last_repos.items.len().min(last_page as usize);
+            if available < last_page as usize {
+                debug!(
+                    "Last page has only {} of {} requested repos",
+                    available, last_page
+                );
+            }
+
+            result
+                .items
+                .extend_from_slice(&last_repos.items[..available])
+        }
+
+        GithubApi::dedup(&mut result);
+        result.found = result.items.len();
+        GithubApi::retain_language(repo_query, &mut result.items);
+        if repo_query.sort == RepoSort::Stars {
+            GithubApi::sort_by_stars(&mut result);
+        }
+        GithubApi::check_count(repo_query, result.items.len())?;
+
+        Ok(result)
+    }
+
+    /// With strict_language drops repos in other language than the requested one, logs how many there were
+    fn retain_language(repo_query: &RepoQuery, repos: &mut Vec<RepoData>) {
+        let language = match repo_query.language {
+            Some(language) if repo_query.strict_language => language,
+            _ => return,
+        };
+
+        let found = repos.len();
+        let key = GithubApi::language_key(language);
+        repos.retain(|repo| GithubApi::language_key(&repo.language) == key);
+
+        let excluded = found - repos.len();
+        if excluded > 0 {
+            info!("Excluded {} repos not detected as {}", excluded, language);
+        }
+    }
+
+    /// Language as the search takes it, so it can be compared with the detected one:
+    /// "Jupyter Notebook" is jupyter-notebook, "C++" is cpp, case is ignored
+    fn language_key(language: &str) -> String {
+        let key = language.trim().to_ascii_lowercase().replace(' ', "-");
+        match key.as_str() {
+            "c++" => "cpp".to_string(),
+            "c#" => "csharp".to_string(),
+            "f#" => "fsharp".to_string(),
+            _ => key,
+        }
+    }
+
+    /// Fewer repos than requested is an error only for strict_count, otherwise it's logged
+    fn check_count(repo_query: &RepoQuery, found: usize) -> Result<(), SendError> {
+        if found >= repo_query.count as usize {
+            return Ok(());
+        }
+
+        let message = format!(
+            "Found only {} of {} requested repos",
+            found, repo_query.count
+        );
+        if repo_query.strict_count {
+            return Err(Box::new(ShortfallError::new(&message)));
+        }
+        info!("{}", message);
+
+        Ok(())
+    }
+
+    /// Helper function that returns repositories on given page.
+    /// If search reports incomplete results, page is requested once again,
+    /// partial data is accepted only if the retry is incomplete too.
+    async fn get_repos_from_page(
+        &self,
+        query: &str,
+        page: u32,
+        per_page: u32,
+    ) -> Result<Repos, SendError> {
+        let endpoint = format!(
+            "{endpoint}{query}&per_page={per_page}&page={page}",
+            endpoint = self.search_endpoint(),
+            query = query,
+            per_page = per_page,
+            page = page
+        );
+
+        debug!("Repos endpoint {}", endpoint);
+
+        let repos = self.get_search_page(&endpoint).await?;
+
+        if !repos.incomplete_results {
+            return Ok(repos);
+        }
+
+        debug!("Incomplete results for page {}, retrying", page);
+        tokio::time::sleep(INCOMPLETE_RETRY_DELAY).await;
+
+        let repos = self.get_search_page(&endpoint).await?;
+
+        if repos.incomplete_results {
+            self.warn(
+                WarningCategory::IncompleteResults,
+                format!(
+                    "Search results for page {} are still incomplete, got {} repos",
+                    page,
+                    repos.items.len()
+                ),
+            );
+        }
+
+        Ok(repos)
+    }
+
+    /// Requests a page of search results. Returns ResponseError if instead of items
+    /// there is only an error message, github sends such ones with 200 at times.
+    /// 403 that is not rate limiting is a PermissionError, token is not allowed to search
+    async fn get_search_page(&self, endpoint: &str) -> Result<Repos, SendError> {
+        let repos = match self.client.get_response_body::<Repos>(endpoint).await {
+            // Rate limits come with 403 too, but they are told apart by the client already
+            Err(e)
+                if e.downcast_ref::<ResponseError>()
+                    .and_then(ResponseError::status)
+                    == Some(StatusCode::FORBIDDEN) =>
+            {
+                return Err(Box::new(PermissionError::new(&format!(
+                    "Token is not allowed to search repositories, it likely lacks a scope \
+                     or permission needed for search, this is not rate limiting: {}",
+                    e
+                ))));
+            }
+            res => res?,
+        };
+
+        match &repos.message {
+            Some(message) if repos.items.is_empty() => Err(Box::new(ResponseError::new(&format!(
+                "Search failed: {}",
+                message
+            )))),
+            _ => Ok(repos),
+        }
+    }
+
+    /// How many repos the search is going to give for the requested count: no more than
+    /// total_count of the search says match it, and no more than search gives at all.
+    /// Unknown total_count is taken as large enough
+    pub fn expected_repos(total_count: Option<u64>, count: u32) -> u32 {
+        let available = total_count.map_or(SEARCH_RESULTS_CAP, |total| {
+            total.min(SEARCH_RESULTS_CAP as u64) as u32
+        });
+
+        count.min(available)
+    }
+
+    /// Returns stream that fetches search results lazily, page by page
+    pub fn stream_repos(
+        &self,
+        repo_query: &RepoQuery<'_>,
+    ) -> Result<RepoStream<'_>, InvalidQueryError> {
+        Ok(RepoStream {
+            api: self,
+            query: GithubApi::sent_query(repo_query)?,
+            remaining: GithubApi::capped_count(repo_query.count),
+            page: 1,
+            first_page: 1,
+            delay_sec: repo_query.delay_sec,
+            expected: None,
+        })
+    }
+
+    /// Like stream_repos, but fetches only full pages from start_page to end_page,
+    /// both inclusive, count of the query is ignored. Lets a partial re-run look
+    /// at a range of results, or split a query between runs. Pages are numbered
+    /// from 1 and have 100 repos, so with the cap of search results end_page is 10 at most.
+    pub fn stream_repo_pages(
+        &self,
+        repo_query: &RepoQuery<'_>,
+        start_page: u32,
+        end_page: u32,
+    ) -> Result<RepoStream<'_>, InvalidQueryError> {
+        let max_page = SEARCH_RESULTS_CAP / PAGE_LIMIT;
+        if start_page == 0 || start_page > end_page {
+            return Err(InvalidQueryError::new(&format!(
+                "Invalid page range {}..{}, pages are numbered from 1 and start page can't be after the end one",
+                start_page, end_page
+            )));
+        }
+        if end_page > max_page {
+            return Err(InvalidQueryError::new(&format!(
+                "End page {} is too far, search gives only {} results, that is {} pages of {}",
+                end_page, SEARCH_RESULTS_CAP, max_page, PAGE_LIMIT
+            )));
+        }
+
+        Ok(RepoStream {
+            api: self,
+            query: GithubApi::sent_query(repo_query)?,
+            remaining: (end_page - start_page + 1) * PAGE_LIMIT,
+            page: start_page,
+            first_page: start_page,
+            delay_sec: repo_query.delay_sec,
+            expected: None,
+        })
+    }
+
+    /// Streaming counterpart of get_repos followed by get_repos_bus_factor.
+    /// Repos are processed in batches of one search page, on_batch gets results
+    /// of each batch as soon as they are ready. Memory stays flat regardless
+    /// of the count, but next page is not requested before current batch is
+    /// done, so it's slower than the eager path for small counts.
+    /// Duplicates across pages are not removed, that would require remembering
+    /// all repos seen so far. For the same reason contributor pages are cached
+    /// only within the batch. Along with the batch on_batch gets number of repos
+    /// the search is going to give in total, see RepoStream::expected_repos.
+    pub async fn for_each_batch<F>(
+        &self,
+        repo_query: &RepoQuery<'_>,
+        query: &BusFactorQuery,
+        mut on_batch: F,
+    ) -> Result<(), SendError>
+    where
+        F: FnMut(Vec<BusFactor>, u32),
+    {
+        let mut stream = self.stream_repos(repo_query)?;
+        let mut found = 0;
+
+        while let Some(page) = stream.next_page().await {
+            let mut repos = Repos {
+                items: page?,
+                ..Repos::default()
+            };
+            GithubApi::retain_language(repo_query, &mut repos.items);
+            let expected = stream.expected_repos().unwrap_or(repo_query.count);
+            // Known after the first page, no point calculating anything
+            if repo_query.strict_count && found == 0 {
+                GithubApi::check_count(repo_query, expected as usize)?;
+            }
+            found += repos.items.len();
+            // Cache of its own, the shared one may be in use by other languages at the same time
+            let batch_api = Self {
+                contributions: Arc::default(),
+                ..self.clone()
+            };
+            on_batch(
+                batch_api.get_repos_bus_factor(&repos, query).await?,
+                expected,
+            );
+        }
+
+        // Total of the search may be more than the search actually gives
+        GithubApi::check_count(repo_query, found)
+    }
+
+    /// Core rate limit of the token, the request itself does not count to it
+    pub async fn get_rate_limit(&self) -> Result<RateLimitData, SendError> {
+        let limits: RateLimits = self.client.get_response_body(RATE_LIMIT_ENDPOINT).await?;
+
+        Ok(limits.resources.core)
+    }
+
+    /// Number of repos the search is going to give for the query, with a single request
+    /// for one repo, see expected_repos
+    pub async fn count_repos(&self, repo_query: &RepoQuery<'_>) -> Result<u32, SendError> {
+        let query = GithubApi::sent_query(repo_query)?;
+        let page = self.get_repos_from_page(&query, 1, 1).await?;
+
+        Ok(GithubApi::expected_repos(
+            page.total_count,
+            repo_query.count,
+        ))
+    }
+
+    /// Checks if api can be reached, returns how long the request took.
+    /// Fails the same way as any other request would, like with reqwest::Error for network problems
+    pub async fn check_connection(&self) -> Result<Duration, SendError> {
+        let start = std::time::Instant::now();
+
+        self.client.get_response(RATE_LIMIT_ENDPOINT).await?;
+
+        Ok(start.elapsed())
+    }
+
+    /// Returns metadata of the repo given by owner and name.
+    /// Returns NotFoundError if there is no such repo, or token has no access to it
+    pub async fn get_repo(&self, owner: &str, name: &str) -> Result<RepoData, SendError> {
+        let endpoint = self.resolve_contributors_url(&format!("/repos/{}/{}", owner, name));
+
+        debug!("Repo endpoint {}", endpoint);
+
+        match self.client.get_response_body::<RepoData>(&endpoint).await {
+            Err(e)
+                if e.downcast_ref::<ResponseError>()
+                    .and_then(ResponseError::status)
+                    == Some(StatusCode::NOT_FOUND) =>
+            {
+                Err(Box::new(NotFoundError::new(&format!(
+                    "Repo {}/{} not found: {}",
+                    owner, name, e
+                ))))
+            }
+            res => res,
+        }
+    }
+
+    /// Fetches repos given by owner and name, one request each. Fails if any of them is not there
+    pub async fn get_repos_by_name(&self, full_names: &[FullName]) -> Result<Repos, SendError> {
+        let mut repos = Repos::default();
+
+        for full_name in full_names {
+            repos
+                .items
+                .push(self.get_repo(&full_name.owner, &full_name.name).await?);
+        }
+
+        Ok(repos)
+    }
+
+    /// Returns logins of all members of organization or team, as github_data::login_key,
+    /// so they can be compared with logins of contributors.
+    /// Token needs read:org scope to see private members of organization, and teams.
+    pub async fn get_members(&self, scope: &MemberScope) -> Result<HashSet<String>, SendError> {
+        let mut members = HashSet::new();
+
+        for page in 1.. {
+            let endpoint = format!(
+                "{endpoint}?per_page={per_page}&page={page}",
+                endpoint = scope.endpoint(),
+                per_page = PAGE_LIMIT,
+                page = page
+            );
+
+            debug!("Members endpoint {}", endpoint);
+
+            let page = self.client.get_response_body::<Members>(&endpoint).await?;
+            let last = page.len() < PAGE_LIMIT as usize;
+
+            members.extend(
+                page.iter()
+                    .map(|member| github_data::login_key(&member.login)),
+            );
+
+            if last {
+                break;
+            }
+        }
+
+        info!("Found {} members of {:?}", members.len(), scope);
+
+        Ok(members)
+    }
+
+    /// Checks if repo still exists, using its metadata endpoint.
+    /// Repo that does not answer in time is assumed to be alive.
+    async fn is_alive(&self, repo: &RepoData) -> Result<bool, SendError> {
+        let endpoint = self.resolve_contributors_url(&repo.contributors_url);
+        let endpoint = endpoint.trim_end_matches("/contributors");
+
+        let res = tokio::time::timeout(
+            VERIFY_TIMEOUT,
+            self.client.get_response_body::<serde_json::Value>(endpoint),
+        )
+        .await;
+
+        match res {
+            Ok(Ok(_)) => Ok(true),
+            Ok(Err(e)) => match e
+                .downcast_ref::<ResponseError>()
+                .and_then(ResponseError::status)
+            {
+                Some(StatusCode::NOT_FOUND) | Some(StatusCode::GONE) => {
+                    info!("Dropping {}, repo is gone: {}", repo.name, e);
+                    Ok(false)
+                }
+                _ => Err(e),
+            },
+            Err(_) => {
+                self.warn(
+                    WarningCategory::MissingData,
+                    format!("Verification of {} timed out, keeping it", repo.name),
+                );
+                Ok(true)
+            }
+        }
+    }
+
+    /// Fills in missing contributors urls, drops repos there is no way to get them for
+    fn with_contributors_urls(&self, repos: &Repos) -> Repos {
+        let mut res = Repos {
+            incomplete_results: repos.incomplete_results,
+            ..Repos::default()
+        };
+
+        for repo in &repos.items {
+            match repo.contributors_endpoint() {
+                Some(contributors_url) => res.items.push(RepoData {
+                    contributors_url,
+                    ..repo.clone()
+                }),
+                None => self.warn(
+                    WarningCategory::SkippedRepo,
+                    format!("Project {} has no contributors url, skipping", repo.name),
+                ),
+            }
+        }
+
+        res
+    }
+
+    /// Drops forks, logs how many there were
+    fn without_forks(repos: &Repos) -> Repos {
+        let items: Vec<RepoData> = repos
+            .items
+            .iter()
+            .filter(|repo| !repo.fork)
+            .cloned()
+            .collect();
+
+        let excluded = repos.items.len() - items.len();
+        if excluded > 0 {
+            info!(
+                "Excluded {} forks, use --include-forks to keep them",
+                excluded
+            );
+        }
+
+        Repos {
+            incomplete_results: repos.incomplete_results,
+            items,
+            ..Repos::default()
+        }
+    }
+
+    /// Returns only repos that still exist
+    pub async fn verify_repos(&self, repos: &Repos) -> Result<Repos, SendError> {
+        let mut verified = Repos {
+            incomplete_results: repos.incomplete_results,
+            ..Repos::default()
+        };
+
+        // Same concurrency as for contributors
+        for chunk in repos.items.chunks(5) {
+            let alive =
+                futures::future::join_all(chunk.iter().map(|repo| self.is_alive(repo))).await;
+
+            for (repo, alive) in chunk.iter().zip(alive) {
+                if alive? {
+                    verified.items.push(repo.clone());
+                }
+            }
+        }
+
+        debug!(
+            "{} of {} repos verified",
+            verified.items.len(),
+            repos.items.len()
+        );
+
+        Ok(verified)
+    }
+
+    /// Sets usage of the repos, one request per repo. Dependents are not available in the
+    /// REST api, so size of the fork network is taken. Usage stays None for repos without
+    /// full name, or if the request failed, summary falls back to stars then
+    pub async fn add_usage(&self, results: &mut [BusFactor]) {
+        // Same concurrency as for contributors
+        for chunk in results.chunks_mut(5) {
+            let usage =
+                futures::future::join_all(chunk.iter().map(|repo| self.get_usage(repo))).await;
+
+            for (repo, usage) in chunk.iter_mut().zip(usage) {
+                repo.usage = usage;
+            }
+        }
+    }
+
+    async fn get_usage(&self, repo: &BusFactor) -> Option<u64> {
+        let (owner, name) = repo.full_name.split_once('/')?;
+
+        match self.get_repo(owner, name).await {
+            Ok(data) => data.network_count,
+            Err(e) => {
+                self.warn(
+                    WarningCategory::MissingData,
+                    format!("No usage of {}: {}", repo.full_name, e),
+                );
+                None
+            }
+        }
+    }
+
+    /// Returns share of each job, together with number of requests it cost
+    async fn worker(&self, jobs: &[RepoData], query: &BusFactorQuery) -> Vec<CountedShare> {
+        let mut local_res = Vec::new();
+
+        for job in jobs {
+            info!("getting repo share...");
+            let share = self.calculate_repo_share(&job.contributors_url, query);
+            let share = async {
+                match query.timeout_per_repo {
+                    Some(timeout) => match tokio::time::timeout(timeout, share).await {
+                        Ok(res) => res,
+                        Err(_) => Err(Box::new(RepoTimeoutError::new(&format!(
+                            "Project {} not done in {:?}",
+                            job.name, timeout
+                        ))) as SendError),
+                    },
+                    None => share.await,
+                }
+            };
+
+            local_res.push(github_client::count_requests(share).await);
+
+            // Even zero sleep waits for the timer tick
+            if query.delay_sec > 0.0 {
+                tokio::time::sleep(Duration::from_secs_f64(query.delay_sec)).await;
+            }
+        }
+
+        local_res
+    }
+
+    /// Like get_repos_bus_factor, but bus factor is calculated only for limit most starred
+    /// repos, the rest is listed after them without it, see BusFactor::not_calculated.
+    /// Forks are left out of both, unless BusFactorQuery::include_forks
+    pub async fn get_top_repos_bus_factor(
+        &self,
+        repos: &Repos,
+        query: &BusFactorQuery,
+        limit: usize,
+    ) -> Result<Vec<BusFactor>, SendError> {
+        let mut items: Vec<&RepoData> = repos
+            .items
+            .iter()
+            .filter(|repo| query.include_forks || !repo.fork)
+            .collect();
+        // Stable, so repos with the same stars keep the order of the search
+        items.sort_by_key(|repo| std::cmp::Reverse(repo.stargazers_count));
+        let rest = items.split_off(limit.min(items.len()));
+
+        let top = Repos {
+            items: items.into_iter().cloned().collect(),
+            incomplete_results: repos.incomplete_results,
+            ..Repos::default()
+        };
+        let mut res = self.get_repos_bus_factor(&top, query).await?;
+        if !rest.is_empty() {
+            info!(
+                "Listing {} repos past the first {} without bus factor",
+                rest.len(),
+                limit
+            );
+        }
+        res.extend(rest.into_iter().map(BusFactor::not_calculated));
+
+        Ok(res)
+    }
+
+    /// Calculates bus factor for each repo. Returns collection of all repos,
+    /// use BusFactor::is_significant to tell apart those above the threshold.
+    pub async fn get_repos_bus_factor(
+        &self,
+        repos: &Repos,
+        query: &BusFactorQuery,
+    ) -> Result<Vec<BusFactor>, SendError> {
+        if query.basis == ShareBasis::LinesChanged {
+            return Err(Box::new(InvalidQueryError::new(&format!(
+                "Share basis {:?} is not supported yet",
+                query.basis
+            ))));
+        }
+
+        let without_forks;
+        let repos = if query.include_forks {
+            repos
+        } else {
+            without_forks = GithubApi::without_forks(repos);
+            &without_forks
+        };
+
+        let repos = &self.with_contributors_urls(repos);
+
+        let verified;
+        let repos = if query.verify_repos {
+            verified = self.verify_repos(repos).await?;
+            &verified
+        } else {
+            repos
+        };
+
+        let mut futures = Vec::new();
+
+        // Number of api calls that can be executed at once, with adaptive
+        // concurrency client keeps the actual number within the current limit
+        let n_workers = self
+            .client
+            .concurrency()
+            .map_or(5, |concurrency| concurrency.max());
+
+        // At least one job per worker, chunks of 0 elements are not allowed
+        let jobs_count = repos.items.len().div_ceil(n_workers).max(1);
+
+        // Last chunk may contain less than jobs_count elements, the remainder.
+        // Each worker is a task of its own, so parsing of responses runs on many threads,
+        // there are no more tasks than workers
+        let query = Arc::new(query.clone());
+        for chunk in repos.items.chunks(jobs_count) {
+            let (api, query, jobs) = (self.clone(), query.clone(), chunk.to_vec());
+            futures.push(Task(tokio::spawn(async move {
+                api.worker(&jobs, &query).await
+            })));
+        }
+
+        let responses = futures::future::join_all(futures).await;
+
+        // flatten partial results to vector of all results, panic of any worker fails them all
+        let mut responses: Vec<CountedShare> = responses
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .flatten()
+            .collect();
+
+        // With fail_fast the first error ends it all, nothing to try again
+        let rounds = if query.fail_fast {
+            0
+        } else {
+            query.repo_retries.max_retries
+        };
+        for round in 0..rounds {
+            let failed: Vec<usize> = responses
+                .iter()
+                .enumerate()
+                .filter(|(_, (item, _))| {
+                    matches!(item, Err(e) if !GithubApi::fails_every_repo(e.as_ref()))
+                })
+                .map(|(idx, _)| idx)
+                .collect();
+            if failed.is_empty() {
+                break;
+            }
+
+            let delay = self
+                .client
+                .backoff(&query.repo_retries, "repo-retries", round);
+            info!(
+                "Trying {} failed repos again in {:?}, round {} of {}",
+                failed.len(),
+                delay,
+                round + 1,
+                rounds
+            );
+            tokio::time::sleep(delay).await;
+
+            // Few repos fail, one worker does
+            let jobs: Vec<RepoData> = failed.iter().map(|&idx| repos.items[idx].clone()).collect();
+            let retried = self.worker(&jobs, &query).await;
+            for (idx, counted) in failed.into_iter().zip(retried) {
+                responses[idx] = counted;
+            }
+        }
+
+        let mut res = Vec::<BusFactor>::new();
+        let mut dropped = 0;
+        let mut empty = 0;
+        let mut timed_out = 0;
+        let mut failed = vec![];
+        for (idx, (item, requests)) in responses.into_iter().enumerate() {
+            // responses, and repo has the same amount of elements
+            let repo = &repos.items[idx];
+
+            let share = match item {
+                Ok(Some(share)) => Some(share),
+                Err(e) if query.fail_fast || GithubApi::fails_every_repo(e.as_ref()) => {
+                    return Err(e as SendError)
+                }
+                Err(e) if e.is::<RepoTimeoutError>() => {
+                    self.warn(WarningCategory::SkippedRepo, format!("{}, skipping", e));
+                    timed_out += 1;
+                    self.skipped.fetch_add(1, AtomicOrdering::Relaxed);
+                    continue;
+                }
+                Err(e) => {
+                    self.warn(
+                        WarningCategory::SkippedRepo,
+                        format!("Project {} failed: {}, skipping", repo.name, e),
+                    );
+                    failed.push(repo.name.as_str());
+                    self.skipped.fetch_add(1, AtomicOrdering::Relaxed);
+                    continue;
+                }
+                Ok(None) if query.include_empty => None,
+                Ok(None) => {
+                    debug!(
+                        "Project {} has no contributions to consider, skipping",
+                        repo.name
+                    );
+                    empty += 1;
+                    continue;
+                }
+            };
+
+            let bus_factor = match share {
+                Some(share) => {
+                    if share.considered < query.min_contributors as usize {
+                        debug!(
+                            "Project {} has only {} contributors, skipping",
+                            repo.name, share.considered
+                        );
+                        dropped += 1;
+                        continue;
+                    }
+
+                    trace!(
+                        "Project {}, stars {} has bus factor {} for user {}",
+                        repo.name,
+                        repo.stargazers_count,
+                        share.leader.bus_factor,
+                        share.leader.user_name
+                    );
+
+                    BusFactor {
+                        score: query
+                            .scorer
+                            .as_ref()
+                            .map(|scorer| scorer.score(&share, repo)),
+                        truck_factor: query
+                            .coverage
+                            .map(|coverage| share::truck_factor(&share.contributors, coverage)),
+                        // Logins are case insensitive
+                        owner_led: !repo.owner.login.is_empty()
+                            && repo
+                                .owner
+                                .login
+                                .eq_ignore_ascii_case(&share.leader.user_name),
+                        ..BusFactor::from_share(&repo.name, repo.stargazers_count, share)
+                    }
+                }
+                None => {
+                    debug!(
+                        "Project {} has no contributions to consider, reported as such",
+                        repo.name
+                    );
+                    BusFactor::without_contributors(&repo.name, repo.stargazers_count)
+                }
+            };
+
+            let bus_factor = BusFactor {
+                repo_id: repo.id,
+                requests: if query.count_requests {
+                    Some(requests)
+                } else {
+                    None
+                },
+                language: repo.language.clone(),
+                topics: repo.topics.clone(),
+                full_name: repo.full_name.clone(),
+                open_issues: Some(repo.open_issues_count),
+                created_at: repo.created_at,
+                ..bus_factor
+            };
+            self.client.progress(ProgressEvent::RepoCompleted {
+                repo: bus_factor.repo_name.clone(),
+                full_name: bus_factor.full_name.clone(),
+                bus_factor: Some(bus_factor.leader.bus_factor).filter(|share| !share.is_nan()),
+                leader: Some(bus_factor.leader.user_name.clone())
+                    .filter(|_| bus_factor.has_share()),
+            });
+            res.push(bus_factor);
+        }
+
+        if dropped > 0 {
+            info!(
+                "Dropped {} repos with less than {} contributors",
+                dropped, query.min_contributors
+            );
+        }
+
+        if empty > 0 {
+            info!("Skipped {} repos without contributions to consider", empty);
+        }
+
+        if timed_out > 0 {
+            warn!("Skipped {} repos that timed out", timed_out);
+        }
+
+        if !failed.is_empty() {
+            warn!(
+                "Skipped {} repos that failed, {} tries each, use --fail-fast to stop on them: {}",
+                failed.len(),
+                rounds + 1,
+                failed.join(", ")
+            );
+        }
+
+        Ok(res)
+    }
+
+    /// Errors not specific to the repo, skipping it would not help the next one
+    fn fails_every_repo(err: &(dyn Error + Send + Sync + 'static)) -> bool {
+        err.is::<InvalidQueryError>()
+            || err.is::<RateLimitError>()
+            || err.is::<AbuseError>()
+            || err.is::<PermissionError>()
+            || err.is::<AuthError>()
+    }
+
+    /// Returns users_to_consider most active contributors, in descending order, as the API
+    /// reports them, without calculating anything. Above PAGE_LIMIT contributors are fetched
+    /// page by page, pages concurrently. Url can be relative to the api, like the one
+    /// made by RepoData::contributors_endpoint, contributors base override applies to it
+    pub async fn get_contributions(
+        &self,
+        contributors_url: &str,
+        users_to_consider: u32,
+    ) -> Result<Contributions, SendError> {
+        if users_to_consider == 0 {
+            return Err(Box::new(InvalidQueryError::new(
+                "Number of users to consider must be greater than 0.",
+            )));
+        }
+
+        let contributors_url = &self.resolve_contributors_url(contributors_url);
+
+        if users_to_consider <= PAGE_LIMIT {
+            let endpoint = format!(
+                "{contributors_url}?per_page={per_page}",
+                contributors_url = contributors_url,
+                per_page = users_to_consider
+            );
+
+            trace!("Contributors endpoint {}", endpoint);
+
+            return self.get_contributions_page(&endpoint).await;
+        }
+
+        let pages = users_to_consider.div_ceil(PAGE_LIMIT);
+        let endpoints: Vec<String> = (1..=pages)
+            .map(|page| {
+                format!(
+                    "{contributors_url}?per_page={per_page}&page={page}",
+                    contributors_url = contributors_url,
+                    per_page = PAGE_LIMIT,
+                    page = page
+                )
+            })
+            .collect();
+
+        trace!("Contributors endpoints {:?}", endpoints);
+
+        // Stops at the first failed page, errors are not collected, so the future stays Send.
+        // Pages are fetched at once, but come in their order
+        let pages: Vec<Contributions> = futures::stream::iter(endpoints)
+            .map(|endpoint| async move { self.get_contributions_page(&endpoint).await })
+            .buffered(CONTRIBUTOR_PAGES_AT_ONCE)
+            .try_collect()
+            .await?;
+
+        let mut contributions: Contributions = pages.into_iter().flatten().collect();
+
+        // Leader has to be the first one, whatever the pages hold. Sort is stable, so ties
+        // keep the order of pages
+        contributions.sort_by_key(|contr| std::cmp::Reverse(contr.contributions));
+        contributions.truncate(users_to_consider as usize);
+
+        Ok(contributions)
+    }
+
+    /// Shares of merged pull requests authored by each user, found by the search of issues.
+    /// Pages are requested one by one, and only until the last one, search has a low rate
+    /// limit. Search gives at most 1000 results, no more than max_contributor_pages of them
+    /// are fetched, share is approximate if there are more. Authors of deleted accounts are left out
+    async fn pull_request_share(
+        &self,
+        contributors_url: &str,
+        query: &BusFactorQuery,
+    ) -> Result<Option<RepoShare>, SendError> {
+        let full_name = contributors_url
+            .split_once("/repos/")
+            .and_then(|(_, path)| path.strip_suffix("/contributors"))
+            .ok_or_else(|| {
+                InvalidQueryError::new(&format!("Can't tell the repo of {}", contributors_url))
+            })?;
+
+        let pages = query
+            .max_contributor_pages
+            .min(SEARCH_RESULTS_CAP / PAGE_LIMIT);
+        let mut authors = vec![];
+        let mut fetched = 0;
+        let mut total = 0;
+        for page in 1..=pages {
+            let endpoint = format!(
+                "{search}?q=repo:{repo}+is:pr+is:merged&per_page={per_page}&page={page}",
+                search = self.issue_search_endpoint(),
+                repo = full_name,
+                per_page = PAGE_LIMIT,
+                page = page
+            );
+            trace!("Pull requests endpoint {}", endpoint);
+
+            let issues: Issues = self.client.get_response_body(&endpoint).await?;
+            total = issues.total_count;
+            fetched += issues.items.len() as u64;
+            let last = issues.items.len() < PAGE_LIMIT as usize;
+            authors.extend(issues.items.into_iter().filter_map(|issue| issue.user));
+
+            if last {
+                break;
+            }
+        }
+
+        if fetched < total {
+            self.warn(
+                WarningCategory::ApproximateShare,
+                format!(
+                    "Pull requests of {} truncated at {} of {}, share is approximate",
+                    full_name, fetched, total
+                ),
+            );
+        }
+
+        let contributions = share::count_authors(authors.iter().map(|user| user.login.as_str()));
+        // Every author of fetched pull requests is considered
+        let share = share::compute_repo_share(
+            &contributions,
+            usize::MAX,
+            query.members.as_ref(),
+            ShareSource::Api,
+        );
+
+        Ok(share.map(|share| RepoShare {
+            approximate: fetched < total,
+            ..share
+        }))
+    }
+
+    /// Gets single page of contributors, each endpoint is requested once per run.
+    /// Concurrent requests for the same endpoint wait for the first one, failed request
+    /// is not cached, so the next one tries again
+    async fn get_contributions_page(&self, endpoint: &str) -> Result<Contributions, SendError> {
+        let cell = self
+            .contributions
+            .lock()
+            .unwrap()
+            .entry(endpoint.to_string())
+            .or_default()
+            .clone();
+
+        let contributions = cell
+            .get_or_try_init(|| self.fetch_contributions_page(endpoint))
+            .await?;
+        Ok(contributions.clone())
+    }
+
+    /// Requests single page of contributors. Empty repo is answered with 204 and no body,
+    /// it's the same as no contributors at all
+    async fn fetch_contributions_page(&self, endpoint: &str) -> Result<Contributions, SendError> {
+        let res = self.client.get_response(endpoint).await?;
+
+        let empty = res.status == StatusCode::NO_CONTENT || res.body.trim().is_empty();
+        if let Some(dir) = &self.raw_dir {
+            // Written as an empty list, so every dumped page reads back as contributions
+            let body = if empty { "[]" } else { &res.body };
+            GithubApi::dump_raw(dir, endpoint, body)?;
+        }
+
+        if empty {
+            trace!("No contributors at {}", endpoint);
+            return Ok(Contributions::new());
+        }
+
+        let contributions: Contributions = serde_json::from_str(&res.body)?;
+        Ok(contributions)
+    }
+
+    /// Where the contributor page of the endpoint is dumped, see with_raw_dump
+    fn raw_dump_path(dir: &Path, endpoint: &str) -> PathBuf {
+        let (path, params) = endpoint.split_once('?').unwrap_or((endpoint, ""));
+        let full_name = path
+            .split_once("/repos/")
+            .map_or(path, |(_, full_name)| full_name)
+            .trim_end_matches("/contributors");
+        let page = params
+            .split('&')
+            .find_map(|param| param.strip_prefix("page="))
+            .unwrap_or("1");
+
+        // Only names from the url, it must not point out of the dir
+        let mut repo_dir = dir.to_path_buf();
+        repo_dir.extend(
+            full_name
+                .split('/')
+                .filter(|part| !part.is_empty() && *part != ".."),
+        );
+        repo_dir.join(format!("contributors-{}.json", page))
+    }
+
+    fn dump_raw(dir: &Path, endpoint: &str, body: &str) -> Result<(), SendError> {
+        let path = GithubApi::raw_dump_path(dir, endpoint);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, body)
+            .map_err(|e| format!("Can't dump contributors to {}: {}", path.display(), e))?;
+
+        debug!("Dumped {} to {}", endpoint, path.display());
+        Ok(())
+    }
+
+    /// True if request failed, because token has no permissions for it
+    fn lacks_permissions(err: &(dyn Error + 'static)) -> bool {
+        err.downcast_ref::<ResponseError>().is_some_and(|err| {
+            err.status() == Some(StatusCode::FORBIDDEN)
+                && err.to_string().contains(NOT_ACCESSIBLE_MESSAGE)
+        })
+    }
+
+    /// Gets shares of contribution for users_to_consider most active users,
+    /// None if there is nobody to consider. Records how long it took, if timing is enabled
+    async fn calculate_repo_share(
+        &self,
+        contributors_url: &str,
+        query: &BusFactorQuery,
+    ) -> Result<Option<RepoShare>, SendError> {
+        let start = std::time::Instant::now();
+        let share = self.compute_repo_share(contributors_url, query).await;
+
+        if let Some(latencies) = &self.repo_latencies {
+            latencies.record(start.elapsed());
+        }
+
+        share
+    }
+
+    async fn compute_repo_share(
+        &self,
+        contributors_url: &str,
+        query: &BusFactorQuery,
+    ) -> Result<Option<RepoShare>, SendError> {
+        let users_to_consider = query.users_to_consider;
+        if users_to_consider == 0 {
+            // Such request does not make any sense
+            return Err(Box::new(InvalidQueryError::new(
+                "Number of users to consider must be greater than 0.",
+            )));
+        }
+
+        let contributors_url = &self.resolve_contributors_url(contributors_url);
+
+        if query.basis == ShareBasis::PullRequests {
+            return self.pull_request_share(contributors_url, query).await;
+        }
+
+        // Fewer users are requested, so full pages make the share approximate
+        let page_cap = query
+            .max_contributor_pages
+            .max(1)
+            .saturating_mul(PAGE_LIMIT);
+        let users_to_consider = users_to_consider.min(page_cap);
+
+        let contributions = match self
+            .get_contributions(contributors_url, users_to_consider)
+            .await
+        {
+            // 403 is also returned for other reasons, like too large repos
+            Err(e) if GithubApi::lacks_permissions(e.as_ref()) => {
+                return Err(Box::new(PermissionError::new(&format!(
+                    "Token has no access to contributors of {}, fine-grained token needs \
+                     read access to Metadata and Contents of the repo: {}",
+                    contributors_url, e
+                ))));
+            }
+            res => res?,
+        };
+
+        // The only place where fewer contributors are fetched than asked for, it must not be silent
+        if query.users_to_consider > page_cap && contributions.len() >= page_cap as usize {
+            self.warn(
+                WarningCategory::ApproximateShare,
+                format!(
+                    "Contributors of {} truncated at {} pages, share is approximate",
+                    contributors_url, query.max_contributor_pages
+                ),
+            );
+        }
+
+        let share = share::compute_repo_share(
+            &contributions,
+            users_to_consider as usize,
+            query.members.as_ref(),
+            ShareSource::Api,
+        );
+
+        let share = match share {
+            Some(share) if query.normalize => {
+                // Commits endpoint counts the same commits as contributors one
+                let commits_url = GithubApi::commits_url(contributors_url)?;
+                let total = self.client.get_total_count(&commits_url).await?;
+                trace!("Repo {} has {} commits in total", contributors_url, total);
+
+                Some(share::normalize(share, total))
+            }
+            share => share,
+        };
+
+        match share {
+            Some(share) if query.recency_weight > 0.0 => {
+                let since = Utc::now() - chrono::Duration::from_std(query.recent_window)?;
+                let recent = self
+                    .recent_contributions(contributors_url, since, query)
+                    .await?;
+                let recent = share::compute_repo_share(
+                    &recent,
+                    usize::MAX,
+                    query.members.as_ref(),
+                    ShareSource::Api,
+                );
+
+                Ok(Some(share::blend_recent(
+                    share,
+                    recent.as_ref(),
+                    query.recency_weight,
+                )))
+            }
+            share => Ok(share),
+        }
+    }
+
+    /// Counts commits of each author since the time, from up to max_contributor_pages pages
+    /// of the commits endpoint. Commits not linked to an account are not counted
+    async fn recent_contributions(
+        &self,
+        contributors_url: &str,
+        since: DateTime<Utc>,
+        query: &BusFactorQuery,
+    ) -> Result<Contributions, SendError> {
+        let commits_url = GithubApi::commits_url(contributors_url)?;
+        let mut authors = vec![];
+
+        for page in 1..=query.max_contributor_pages.max(1) {
+            let endpoint = format!(
+                "{}?since={}&per_page={}&page={}",
+                commits_url,
+                since.format("%Y-%m-%dT%H:%M:%SZ"),
+                PAGE_LIMIT,
+                page
+            );
+            trace!("Recent commits endpoint {}", endpoint);
+
+            let commits: Commits = self.client.get_response_body(&endpoint).await?;
+            let last = commits.len() < PAGE_LIMIT as usize;
+            authors.extend(commits.into_iter().filter_map(|commit| commit.author));
+
+            if last {
+                return Ok(share::count_authors(
+                    authors.iter().map(|user| user.login.as_str()),
+                ));
+            }
+        }
+
+        self.warn(
+            WarningCategory::ApproximateShare,
+            format!(
+                "Recent commits of {} truncated at {} pages, share is approximate",
+                commits_url, query.max_contributor_pages
+            ),
+        );
+        Ok(share::count_authors(
+            authors.iter().map(|user| user.login.as_str()),
+        ))
+    }
+}
+
+/// Lazily fetched search results, see GithubApi::stream_repos
+pub struct RepoStream<'a> {
+    api: &'a GithubApi,
+    query: String,
+    /// How many repos are still to be fetched
+    remaining: u32,
+    /// Next page to fetch, numbering starts from 1
+    page: u32,
+    /// Page the stream started from, there is no delay before it
+    first_page: u32,
+    delay_sec: f64,
+    /// Repos the stream is going to give, known once the first page is there
+    expected: Option<u32>,
+}
+
+impl RepoStream<'_> {
+    /// Number of repos the stream gives in total, taken from total_count of the first
+    /// page, so progress can be shown against it before bus factor of the page is calculated.
+    /// That is the requested count, unless fewer repos match the search. None before
+    /// the first page is fetched
+    pub fn expected_repos(&self) -> Option<u32> {
+        self.expected
+    }
+
+    /// Returns repos from the next page, None when requested count is reached
+    pub async fn next_page(&mut self) -> Option<Result<Vec<RepoData>, SendError>> {
+        // Search answers pages past the cap with an error
+        if self.remaining == 0 || self.page > SEARCH_RESULTS_CAP / PAGE_LIMIT {
+            return None;
+        }
+
+        if self.page > self.first_page && self.delay_sec > 0.0 {
+            tokio::time::sleep(Duration::from_secs_f64(self.delay_sec)).await;
+        }
+
+        // Keep per_page constant, otherwise pages would not line up
+        let per_page = if self.page == 1 {
+            self.remaining.min(PAGE_LIMIT)
+        } else {
+            PAGE_LIMIT
+        };
+
+        let res = self
+            .api
+            .get_repos_from_page(&self.query, self.page, per_page)
+            .await;
+
+        Some(res.map(|repos| {
+            if self.expected.is_none() {
+                // Pages before the first one are not fetched, but count to the cap
+                let skipped = (self.first_page - 1) * PAGE_LIMIT;
+                let total = repos
+                    .total_count
+                    .map(|total| total.saturating_sub(skipped as u64));
+                self.expected = Some(GithubApi::expected_repos(
+                    total,
+                    self.remaining.min(SEARCH_RESULTS_CAP - skipped),
+                ));
+            }
+
+            let mut items = repos.items;
+            // Short page means search has no more results
+            let exhausted = items.len() < per_page as usize;
+            items.truncate(self.remaining as usize);
+
+            self.remaining = if exhausted {
+                0
+            } else {
+                self.remaining - items.len() as u32
+            };
+            self.page += 1;
+
+            items
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::VecDeque,
+        fs,
+        path::PathBuf,
+        sync::{Arc, Mutex},
+    };
+
+    use assert_approx_eq::assert_approx_eq;
+    use futures::future::BoxFuture;
+    use reqwest::{
+        header::{HeaderMap, USER_AGENT},
+        StatusCode,
+    };
+
+    use crate::{
+        api_errors::MissingFixtureError,
+        github_data::ContributorData,
+        replay::{Fixture, ReplayClient},
+        transport::{RawResponse, Transport},
+    };
+
+    use super::*;
+
+    /// Answers with given bodies one after another, regardless of the endpoint
+    struct SequenceTransport {
+        bodies: Mutex<VecDeque<String>>,
+    }
+
+    impl SequenceTransport {
+        fn api(bodies: &[&str]) -> GithubApi {
+            GithubApi::with_client(GithubClient::with_transport(
+                "",
+                &TransportConfig::default(),
+                Arc::new(SequenceTransport {
+                    bodies: Mutex::new(bodies.iter().map(|body| body.to_string()).collect()),
+                }),
+            ))
+        }
+    }
+
+    impl Transport for SequenceTransport {
+        fn get<'a>(
+            &'a self,
+            _endpoint: &'a str,
+            _headers: HeaderMap,
+        ) -> BoxFuture<'a, Result<RawResponse, SendError>> {
+            let body = self
+                .bodies
+                .lock()
+                .unwrap()
+                .pop_front()
+                .expect("No more responses");
+
+            Box::pin(async move {
+                Ok(RawResponse {
+                    status: StatusCode::OK,
+                    headers: HeaderMap::new(),
+                    body,
+                })
+            })
+        }
+    }
+
+    const INCOMPLETE_PAGE: &str = r#"{"incomplete_results": true, "items": [
+        {"name": "a", "stargazers_count": 2, "contributors_url": "a/contributors"}]}"#;
+    const COMPLETE_PAGE: &str = r#"{"incomplete_results": false, "items": [
+        {"name": "a", "stargazers_count": 2, "contributors_url": "a/contributors"},
+        {"name": "b", "stargazers_count": 1, "contributors_url": "b/contributors"}]}"#;
+
+    #[tokio::test]
+    async fn incomplete_page_is_retried() {
+        let api = SequenceTransport::api(&[INCOMPLETE_PAGE, COMPLETE_PAGE]);
+        let repo_query = RepoQuery {
+            language: Some("rust"),
+            count: 2,
+            ..RepoQuery::default()
+        };
+
+        let repos = api.get_repos(&repo_query).await.unwrap();
+
+        assert!(!repos.incomplete_results);
+        assert_eq!(repos.items.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn shortfall_fails_only_strict_count() {
+        let repo_query = RepoQuery {
+            language: Some("rust"),
+            count: 3,
+            ..RepoQuery::default()
+        };
+
+        let repos = SequenceTransport::api(&[COMPLETE_PAGE])
+            .get_repos(&repo_query)
+            .await
+            .unwrap();
+        assert_eq!(repos.items.len(), 2);
+
+        let err = SequenceTransport::api(&[COMPLETE_PAGE])
+            .get_repos(&RepoQuery {
+                strict_count: true,
+                ..repo_query
+            })
+            .await
+            .unwrap_err();
+        assert!(err.is::<ShortfallError>(), "{}", err);
+        assert_eq!(err.to_string(), "Found only 2 of 3 requested repos");
+
+        // Streaming fails as soon as total of the search is known, before any contributors are fetched
+        let page = r#"{"incomplete_results": false, "total_count": 1, "items": [
+            {"name": "a", "stargazers_count": 2, "contributors_url": "a/contributors"}]}"#;
+        let err = SequenceTransport::api(&[page])
+            .for_each_batch(
+                &RepoQuery {
+                    strict_count: true,
+                    ..repo_query
+                },
+                &BusFactorQuery::default(),
+                |_, _| panic!("Nothing to calculate"),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Found only 1 of 3 requested repos");
+    }
+
+    #[tokio::test]
+    async fn batches_keep_contributors_cached_by_others() {
+        let contributors = r#"[{"login": "a", "contributions": 3}]"#;
+        let page = r#"{"incomplete_results": false, "total_count": 1, "items": [
+            {"name": "a", "stargazers_count": 2, "contributors_url": "a/contributors"}]}"#;
+        // Nothing is left for a second request of the cached contributors
+        let api = SequenceTransport::api(&[contributors, page, contributors]);
+        let other = api.scoped();
+        other.get_contributions("o/contributors", 25).await.unwrap();
+
+        let mut results = 0;
+        api.for_each_batch(
+            &RepoQuery {
+                count: 1,
+                ..RepoQuery::default()
+            },
+            &BusFactorQuery::default(),
+            |batch, _| results += batch.len(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(results, 1);
+
+        let cached = other.get_contributions("o/contributors", 25).await.unwrap();
+        assert_eq!(cached[0].login, "a");
+    }
+
+    #[tokio::test]
+    async fn strict_language_drops_other_languages() {
+        let page = r#"{"incomplete_results": false, "items": [
+            {"name": "a", "stargazers_count": 3, "contributors_url": "a/contributors", "language": "Rust"},
+            {"name": "b", "stargazers_count": 2, "contributors_url": "b/contributors", "language": "C++"},
+            {"name": "c", "stargazers_count": 1, "contributors_url": "c/contributors"}]}"#;
+        let repo_query = RepoQuery {
+            language: Some("rust"),
+            count: 3,
+            ..RepoQuery::default()
+        };
+
+        let repos = SequenceTransport::api(&[page])
+            .get_repos(&repo_query)
+            .await
+            .unwrap();
+        assert_eq!(repos.items.len(), 3);
+
+        let repos = SequenceTransport::api(&[page])
+            .get_repos(&RepoQuery {
+                strict_language: true,
+                ..repo_query
+            })
+            .await
+            .unwrap();
+        let names: Vec<&str> = repos.items.iter().map(|repo| repo.name.as_str()).collect();
+        assert_eq!(names, ["a"]);
+        // Search still found all of them
+        assert_eq!(repos.found, 3);
+
+        // Language is asked for the way the search takes it
+        let repos = SequenceTransport::api(&[page])
+            .get_repos(&RepoQuery {
+                language: Some("cpp"),
+                strict_language: true,
+                ..repo_query
+            })
+            .await
+            .unwrap();
+        assert_eq!(repos.items[0].name, "b");
+    }
+
+    #[tokio::test]
+    async fn zero_count_sends_no_requests() {
+        // Transport with no responses panics on any request
+        let api = SequenceTransport::api(&[]);
+
+        let repos = api
+            .get_repos(&RepoQuery {
+                language: Some("rust"),
+                count: 0,
+                ..RepoQuery::default()
+            })
+            .await
+            .unwrap();
+
+        assert!(repos.items.is_empty());
+    }
+
+    #[tokio::test]
+    async fn incomplete_page_is_accepted_after_retry() {
+        let api = SequenceTransport::api(&[INCOMPLETE_PAGE, INCOMPLETE_PAGE]);
+
+        let repos = api
+            .get_repos_from_page("?q=language:rust", 1, 1)
+            .await
+            .unwrap();
+
+        assert!(repos.incomplete_results);
+        assert_eq!(repos.items.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn message_without_items_is_response_error() {
+        let api = SequenceTransport::api(&[r#"{"message": "Validation Failed"}"#]);
+
+        let err = api
+            .get_repos_from_page("?q=language:rust", 1, 1)
+            .await
+            .unwrap_err();
+
+        assert!(err.downcast_ref::<ResponseError>().is_some(), "{}", err);
+        assert!(err.to_string().contains("Validation Failed"), "{}", err);
+    }
+
+    #[test]
+    fn test_get_pages() {
+        // Result that fits on one page
+        let (full_pages, last_page) = GithubApi::get_pages(50);
+        assert_eq!(full_pages, 0);
+        assert_eq!(last_page, 50);
+
+        // Result that comes to second page
+        let (full_pages, last_page) = GithubApi::get_pages(101);
+        assert_eq!(full_pages, 1);
+        assert_eq!(last_page, 1);
+
+        // Huuuge result
+        let (full_pages, last_page) = GithubApi::get_pages(2531);
+        assert_eq!(full_pages, 25);
         assert_eq!(last_page, 31);
 
-        // Weird, but correct
-        let (full_pages, last_page) = GithubApi::get_pages(0);
-        assert_eq!(full_pages, 0);
-        assert_eq!(last_page, 0);
+        // Weird, but correct
+        let (full_pages, last_page) = GithubApi::get_pages(0);
+        assert_eq!(full_pages, 0);
+        assert_eq!(last_page, 0);
+    }
+
+    #[test]
+    fn search_pacing_depends_on_number_of_pages() {
+        assert_eq!(GithubApi::search_pacing(0), Duration::ZERO);
+        assert_eq!(GithubApi::search_pacing(50), Duration::from_secs(2));
+        assert_eq!(GithubApi::search_pacing(100), Duration::from_secs(2));
+        assert_eq!(GithubApi::search_pacing(250), Duration::from_secs(6));
+    }
+
+    fn repo_with(repo_name: &str, stars: u64, bus_factor: f64) -> BusFactor {
+        BusFactor::new(
+            repo_name,
+            stars,
+            UserShare {
+                bus_factor,
+                user_name: "user".to_string(),
+                ..UserShare::default()
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn custom_scorer_decides_significance() {
+        let repo = |name: &str, stars: u64| RepoData {
+            name: name.to_string(),
+            stargazers_count: stars,
+            contributors_url: format!("https://api.github.com/repos/o/{}/contributors", name),
+            ..RepoData::default()
+        };
+        let repos = Repos {
+            items: vec![repo("popular", 10_000), repo("obscure", 10)],
+            ..Repos::default()
+        };
+        let contributors =
+            r#"[{"login": "leader", "contributions": 8}, {"login": "other", "contributions": 2}]"#;
+
+        // Same share in both, without a scorer both are significant
+        let api = SequenceTransport::api(&[contributors, contributors]);
+        let res = api
+            .get_repos_bus_factor(&repos, &BusFactorQuery::default())
+            .await
+            .unwrap();
+        assert!(res
+            .iter()
+            .all(|repo| repo.score.is_none() && repo.is_significant(0.75)));
+
+        // Popular repos matter more
+        let scorer = |share: &RepoShare, repo: &RepoData| {
+            share.leader.bus_factor * (repo.stargazers_count as f64).log10() / 4.0
+        };
+        let query = BusFactorQuery::builder()
+            .scorer(Some(Arc::new(scorer)))
+            .build()
+            .unwrap();
+        let api = SequenceTransport::api(&[contributors, contributors]);
+        let mut res = api.get_repos_bus_factor(&repos, &query).await.unwrap();
+
+        assert_approx_eq!(res[0].score(), 0.8);
+        assert_approx_eq!(res[1].score(), 0.2);
+        assert!(res[0].is_significant(0.75));
+        assert!(!res[1].is_significant(0.75));
+
+        res.sort_by(BusFactor::by_score);
+        assert_eq!(res[0].repo_name, "obscure");
+        assert_approx_eq!(res[0].leader.bus_factor, 0.8);
+    }
+
+    #[test]
+    fn sorted_by_leader_share_nan_lowest() {
+        let mut repos = [
+            repo_with("b", 1, 0.5),
+            repo_with("nan", 2, f64::NAN),
+            repo_with("a", 3, 0.9),
+            repo_with("c", 4, 0.1),
+        ];
+
+        repos.sort_by(BusFactor::by_leader_share);
+        let names: Vec<&str> = repos.iter().map(|repo| repo.repo_name.as_str()).collect();
+        assert_eq!(names, vec!["nan", "c", "b", "a"]);
+
+        // Highest first
+        repos.sort_by(|a, b| BusFactor::by_leader_share(b, a));
+        assert_eq!(repos[0].repo_name, "a");
+        assert_eq!(repos[3].repo_name, "nan");
+    }
+
+    #[test]
+    fn contributors_url_is_resolved() {
+        let api = GithubApi::new("");
+        assert_eq!(
+            api.resolve_contributors_url("https://api.github.com/repos/o/r/contributors"),
+            "https://api.github.com/repos/o/r/contributors"
+        );
+        assert_eq!(
+            api.resolve_contributors_url("/repos/o/r/contributors"),
+            "https://api.github.com/repos/o/r/contributors"
+        );
+
+        let api = GithubApi::new("").with_contributors_base("http://localhost:8080/");
+        assert_eq!(
+            api.resolve_contributors_url("https://api.github.com/repos/o/r/contributors"),
+            "http://localhost:8080/repos/o/r/contributors"
+        );
+        assert_eq!(
+            api.resolve_contributors_url("repos/o/r/contributors"),
+            "http://localhost:8080/repos/o/r/contributors"
+        );
+    }
+
+    /// Api with search and repo fixtures on given bases, contributors_url points to the api
+    fn split_api(search_base: &str, repos_base: &str) -> GithubApi {
+        GithubApi::with_client(GithubClient::with_transport(
+            "",
+            &TransportConfig::default(),
+            Arc::new(ReplayClient::new(vec![
+                Fixture {
+                    endpoint: format!(
+                        "{}/search/repositories?q=language:rust&sort=stars&order=desc&per_page=1&page=1",
+                        search_base
+                    ),
+                    status: 200,
+                    body: serde_json::json!({"items": [{
+                        "name": "r",
+                        "stargazers_count": 1,
+                        "contributors_url": "https://api.github.com/repos/o/r/contributors"
+                    }]}),
+                    headers: Default::default(),
+                },
+                Fixture {
+                    endpoint: format!("{}/repos/o/r/contributors?per_page=25", repos_base),
+                    status: 200,
+                    body: serde_json::json!([{"login": "leader", "contributions": 1}]),
+                    headers: Default::default(),
+                },
+            ])),
+        ))
+    }
+
+    async fn bus_factor_of_one(api: &GithubApi) -> Vec<BusFactor> {
+        let repos = api
+            .get_repos(&RepoQuery {
+                language: Some("rust"),
+                count: 1,
+                ..RepoQuery::default()
+            })
+            .await
+            .unwrap();
+
+        api.get_repos_bus_factor(&repos, &BusFactorQuery::default())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn search_and_repos_have_separate_bases() {
+        // Urls from search results are used as they are
+        let api = split_api("http://search.mirror", "https://api.github.com")
+            .with_search_base("http://search.mirror/");
+        assert_eq!(bus_factor_of_one(&api).await.len(), 1);
+
+        let api = split_api("http://search.mirror", "http://repos.mirror")
+            .with_search_base("http://search.mirror")
+            .with_contributors_base("http://repos.mirror");
+        assert_eq!(bus_factor_of_one(&api).await.len(), 1);
+        assert_eq!(
+            api.resolve_contributors_url("/repos/o/r"),
+            "http://repos.mirror/repos/o/r"
+        );
+
+        // Both through the same proxy
+        let api = split_api("http://proxy", "http://proxy")
+            .with_search_base("http://proxy")
+            .with_contributors_base("http://proxy");
+        assert_eq!(bus_factor_of_one(&api).await.len(), 1);
+    }
+
+    #[test]
+    fn commits_url_is_next_to_contributors() {
+        assert_eq!(
+            GithubApi::commits_url("https://api.github.com/repos/o/contributors/contributors")
+                .unwrap(),
+            "https://api.github.com/repos/o/contributors/commits"
+        );
+        assert!(GithubApi::commits_url("https://api.github.com/repos/o/r/stats").is_err());
+    }
+
+    #[test]
+    fn threshold_from_str() {
+        assert_eq!("auto".parse::<Threshold>().unwrap(), Threshold::Auto);
+        assert_eq!("0.5".parse::<Threshold>().unwrap(), Threshold::Fixed(0.5));
+        assert!("1.5".parse::<Threshold>().is_err());
+        assert!("high".parse::<Threshold>().is_err());
+    }
+
+    #[test]
+    fn auto_threshold_flags_top_shares() {
+        let results: Vec<BusFactor> = (1..=10)
+            .map(|i| repo_with(&i.to_string(), 0, i as f64 / 10.0))
+            .collect();
+
+        let threshold = Threshold::Auto.resolve(&results);
+        let flagged: Vec<&str> = results
+            .iter()
+            .filter(|repo| repo.is_significant(threshold))
+            .map(|repo| repo.repo_name.as_str())
+            .collect();
+
+        assert_eq!(flagged, vec!["10"]);
+        assert_eq!(Threshold::Auto.resolve(&[]), 1.0);
+        assert_eq!(Threshold::Fixed(0.3).resolve(&results), 0.3);
+    }
+
+    #[test]
+    fn sorted_by_stars() {
+        let mut repos = [repo_with("b", 10, 0.5), repo_with("a", 1, 0.9)];
+
+        repos.sort_by(BusFactor::by_stars);
+        assert_eq!(repos[0].repo_name, "a");
+    }
+
+    #[test]
+    fn query_builder_validates() {
+        let query = BusFactorQuery::builder()
+            .bus_threshold(0.5)
+            .users_to_consider(100)
+            .delay_sec(0.1)
+            .build()
+            .unwrap();
+        assert_eq!(query.bus_threshold, 0.5);
+        assert_eq!(query.users_to_consider, 100);
+
+        assert!(BusFactorQuery::builder()
+            .bus_threshold(1.5)
+            .build()
+            .is_err());
+        assert!(BusFactorQuery::builder()
+            .bus_threshold(f64::NAN)
+            .build()
+            .is_err());
+        assert!(BusFactorQuery::builder()
+            .users_to_consider(0)
+            .build()
+            .is_err());
+        assert!(BusFactorQuery::builder()
+            .users_to_consider(501)
+            .build()
+            .is_err());
+        assert!(BusFactorQuery::builder().delay_sec(-1.0).build().is_err());
+        assert!(BusFactorQuery::builder()
+            .coverage(Some(0.8))
+            .build()
+            .is_ok());
+        assert!(BusFactorQuery::builder()
+            .coverage(Some(0.0))
+            .build()
+            .is_err());
+        assert!(BusFactorQuery::builder()
+            .coverage(Some(1.5))
+            .build()
+            .is_err());
+        assert!(BusFactorQuery::builder()
+            .coverage(Some(f64::NAN))
+            .build()
+            .is_err());
+        assert!(BusFactorQuery::builder()
+            .basis(ShareBasis::PullRequests)
+            .normalize(true)
+            .build()
+            .is_err());
+        assert!(BusFactorQuery::builder()
+            .recency_weight(1.0)
+            .build()
+            .is_ok());
+        assert!(BusFactorQuery::builder()
+            .recency_weight(1.5)
+            .build()
+            .is_err());
+        assert!(BusFactorQuery::builder()
+            .recency_weight(f64::NAN)
+            .build()
+            .is_err());
+        assert!(BusFactorQuery::builder()
+            .basis(ShareBasis::PullRequests)
+            .recency_weight(0.5)
+            .build()
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn inactive_leader_is_discounted() {
+        let api = SequenceTransport::api(&[
+            r#"[{"login": "old", "contributions": 80}, {"login": "new", "contributions": 20}]"#,
+            // Recent commits, one not linked to any account
+            r#"[{"author": {"login": "new"}}, {"author": {"login": "new"}}, {"author": null}]"#,
+        ]);
+        let repos = Repos {
+            items: vec![RepoData {
+                name: "a".to_string(),
+                contributors_url: "a/contributors".to_string(),
+                ..RepoData::default()
+            }],
+            ..Repos::default()
+        };
+        let query = BusFactorQuery::builder()
+            .recency_weight(0.5)
+            .build()
+            .unwrap();
+
+        let res = api.get_repos_bus_factor(&repos, &query).await.unwrap();
+        assert_eq!(res[0].leader.user_name, "new");
+        assert_approx_eq!(res[0].leader.bus_factor, 0.6);
+        assert_eq!(res[0].leader_contributions, Some(20));
+    }
+
+    #[test]
+    fn risk_level_cut_points() {
+        assert_eq!(risk_level(1.0), RiskLevel::Critical);
+        assert_eq!(risk_level(0.9), RiskLevel::Critical);
+        assert_eq!(risk_level(0.89), RiskLevel::High);
+        assert_eq!(risk_level(0.75), RiskLevel::High);
+        assert_eq!(risk_level(0.74), RiskLevel::Medium);
+        assert_eq!(risk_level(0.5), RiskLevel::Medium);
+        assert_eq!(risk_level(0.49), RiskLevel::Low);
+        assert_eq!(risk_level(0.0), RiskLevel::Low);
+    }
+
+    #[test]
+    fn cost_of_repo_depends_on_query() {
+        assert_eq!(BusFactorQuery::default().requests_per_repo(), 1);
+
+        let query = BusFactorQuery {
+            users_to_consider: 250,
+            normalize: true,
+            verify_repos: true,
+            ..BusFactorQuery::default()
+        };
+        assert_eq!(query.requests_per_repo(), 5);
+
+        // Capped by the contributor pages
+        let query = BusFactorQuery {
+            users_to_consider: 1000,
+            recency_weight: 0.5,
+            ..BusFactorQuery::default()
+        };
+        assert_eq!(query.requests_per_repo(), 4);
+
+        let query = BusFactorQuery {
+            basis: ShareBasis::PullRequests,
+            ..BusFactorQuery::default()
+        };
+        assert_eq!(query.requests_per_repo(), 3);
+    }
+
+    #[tokio::test]
+    async fn rate_limit_and_count_for_profile() {
+        let api = SequenceTransport::api(&[
+            r#"{"total_count": 42, "incomplete_results": false, "items": [
+                {"name": "a", "stargazers_count": 2, "contributors_url": "a/contributors"}]}"#,
+            r#"{"resources": {"core": {"limit": 5000, "remaining": 4321, "reset": 1600000000, "used": 679},
+                "search": {"limit": 30, "remaining": 30, "reset": 1600000000}}}"#,
+        ]);
+        let repo_query = RepoQuery {
+            language: Some("rust"),
+            count: 100,
+            ..RepoQuery::default()
+        };
+
+        assert_eq!(api.count_repos(&repo_query).await.unwrap(), 42);
+        let core = api.get_rate_limit().await.unwrap();
+        assert_eq!(
+            (core.limit, core.remaining, core.reset),
+            (5000, 4321, 1600000000)
+        );
+    }
+
+    #[test]
+    fn query_without_language() {
+        let query = GithubApi::build_query(&RepoQuery::default()).unwrap();
+        assert_eq!(query, "?q=stars:>0&sort=stars&order=desc");
+
+        let query = GithubApi::build_query(&RepoQuery {
+            min_stars: Some(1000),
+            ..RepoQuery::default()
+        })
+        .unwrap();
+        assert_eq!(query, "?q=stars:>=1000&sort=stars&order=desc");
+    }
+
+    #[test]
+    fn search_query_is_reported_without_token() {
+        let query = GithubApi::search_query(&RepoQuery {
+            language: Some("rust"),
+            min_stars: Some(100),
+            ..RepoQuery::default()
+        })
+        .unwrap();
+        assert_eq!(query, "q=language:rust+stars:>=100&sort=stars&order=desc");
+        assert!(!query.contains("token"));
+    }
+
+    #[test]
+    fn query_depends_on_sort() {
+        let query = GithubApi::build_query(&RepoQuery {
+            language: Some("rust"),
+            ..RepoQuery::default()
+        })
+        .unwrap();
+        assert_eq!(query, "?q=language:rust&sort=stars&order=desc");
+
+        let query = GithubApi::build_query(&RepoQuery {
+            language: Some("rust"),
+            sort: RepoSort::BestMatch,
+            ..RepoQuery::default()
+        })
+        .unwrap();
+        assert_eq!(query, "?q=language:rust");
+    }
+
+    /// Api answering search for rust with pages of given sizes, for given sort
+    fn search_api(sort: RepoSort, per_page: u32, page_sizes: &[usize]) -> GithubApi {
+        let query = GithubApi::build_query(&RepoQuery {
+            language: Some("rust"),
+            sort,
+            ..RepoQuery::default()
+        })
+        .unwrap();
+
+        let mut first = 0;
+        let fixtures = page_sizes
+            .iter()
+            .enumerate()
+            .map(|(idx, &size)| {
+                let items: Vec<serde_json::Value> = (first..first + size)
+                    .map(|i| {
+                        serde_json::json!({
+                            "name": format!("repo-{}", i),
+                            "stargazers_count": 0,
+                            "contributors_url": format!("https://api.github.com/repos/o/repo-{}/contributors", i),
+                        })
+                    })
+                    .collect();
+                first += size;
+
+                Fixture {
+                    endpoint: format!(
+                        "{}{}&per_page={}&page={}",
+                        REPO_ENDPONT,
+                        query,
+                        per_page,
+                        idx + 1
+                    ),
+                    status: 200,
+                    body: serde_json::json!({ "items": items }),
+                    headers: Default::default(),
+                }
+            })
+            .collect();
+
+        GithubApi::with_client(GithubClient::with_transport(
+            "",
+            &TransportConfig::default(),
+            Arc::new(ReplayClient::new(fixtures)),
+        ))
+    }
+
+    #[tokio::test]
+    async fn short_last_page_is_taken_as_is() {
+        for sort in [RepoSort::Stars, RepoSort::BestMatch] {
+            // Asked for 150, second page has only 20 instead of 50
+            let api = search_api(sort, PAGE_LIMIT, &[100, 20]);
+            let repos = api
+                .get_repos(&RepoQuery {
+                    language: Some("rust"),
+                    count: 150,
+                    sort,
+                    ..RepoQuery::default()
+                })
+                .await
+                .unwrap();
+
+            assert_eq!(repos.items.len(), 120);
+            assert_eq!(repos.items[119].name, "repo-119");
+        }
+    }
+
+    #[tokio::test]
+    async fn short_single_page_is_taken_as_is() {
+        for sort in [RepoSort::Stars, RepoSort::BestMatch] {
+            let api = search_api(sort, 50, &[7]);
+            let repos = api
+                .get_repos(&RepoQuery {
+                    language: Some("rust"),
+                    count: 50,
+                    sort,
+                    ..RepoQuery::default()
+                })
+                .await
+                .unwrap();
+
+            assert_eq!(repos.items.len(), 7);
+        }
+    }
+
+    #[tokio::test]
+    async fn stream_stops_on_short_page() {
+        let api = search_api(RepoSort::Stars, PAGE_LIMIT, &[100, 20]);
+        let mut stream = api
+            .stream_repos(&RepoQuery {
+                language: Some("rust"),
+                count: 300,
+                ..RepoQuery::default()
+            })
+            .unwrap();
+
+        let mut sizes = vec![];
+        while let Some(page) = stream.next_page().await {
+            sizes.push(page.unwrap().len());
+        }
+
+        assert_eq!(sizes, vec![100, 20]);
+    }
+
+    #[tokio::test]
+    async fn stream_fetches_page_range() {
+        let api = search_api(RepoSort::Stars, PAGE_LIMIT, &[100, 100, 100, 20]);
+        let query = RepoQuery {
+            language: Some("rust"),
+            ..RepoQuery::default()
+        };
+        let mut stream = api.stream_repo_pages(&query, 2, 3).unwrap();
+
+        let mut pages = vec![];
+        while let Some(page) = stream.next_page().await {
+            pages.push(page.unwrap());
+        }
+
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[0][0].name, "repo-100");
+        assert_eq!(pages[1][99].name, "repo-299");
+
+        // Range runs past the results
+        let mut stream = api.stream_repo_pages(&query, 4, 10).unwrap();
+        assert_eq!(stream.next_page().await.unwrap().unwrap().len(), 20);
+        assert!(stream.next_page().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn stream_expects_fewer_repos_if_search_has_fewer() {
+        let page = |total_count: u64| {
+            format!(
+                r#"{{"total_count": {}, "items": [{{"name": "a", "stargazers_count": 1}}]}}"#,
+                total_count
+            )
+        };
+        let query = |count: u32| RepoQuery {
+            language: Some("rust"),
+            count,
+            ..RepoQuery::default()
+        };
+
+        let api = SequenceTransport::api(&[&page(1)]);
+        let mut stream = api.stream_repos(&query(50)).unwrap();
+        assert_eq!(stream.expected_repos(), None);
+        stream.next_page().await.unwrap().unwrap();
+        assert_eq!(stream.expected_repos(), Some(1));
+
+        // Requested count, when more repos match
+        let api = SequenceTransport::api(&[&page(5000)]);
+        let mut stream = api.stream_repos(&query(50)).unwrap();
+        stream.next_page().await.unwrap().unwrap();
+        assert_eq!(stream.expected_repos(), Some(50));
+
+        // Pages before the range count to the cap
+        let api = SequenceTransport::api(&[&page(5000)]);
+        let mut stream = api.stream_repo_pages(&query(0), 9, 10).unwrap();
+        stream.next_page().await.unwrap().unwrap();
+        assert_eq!(stream.expected_repos(), Some(200));
+    }
+
+    #[test]
+    fn expected_repos_are_capped() {
+        assert_eq!(GithubApi::expected_repos(Some(30), 50), 30);
+        assert_eq!(GithubApi::expected_repos(Some(5000), 50), 50);
+        assert_eq!(GithubApi::expected_repos(Some(5000), 1500), 1000);
+        assert_eq!(GithubApi::expected_repos(None, 1500), 1000);
+    }
+
+    #[test]
+    fn page_range_is_validated() {
+        let api = search_api(RepoSort::Stars, PAGE_LIMIT, &[]);
+        let query = RepoQuery::default();
+
+        assert!(api.stream_repo_pages(&query, 0, 1).is_err());
+        assert!(api.stream_repo_pages(&query, 3, 2).is_err());
+        assert!(api.stream_repo_pages(&query, 1, 11).is_err());
+        assert!(api.stream_repo_pages(&query, 10, 10).is_ok());
+    }
+
+    #[tokio::test]
+    async fn repo_is_fetched_by_full_name() {
+        let api = GithubApi::with_client(GithubClient::with_transport(
+            "",
+            &TransportConfig::default(),
+            Arc::new(ReplayClient::new(vec![
+                Fixture {
+                    endpoint: "https://api.github.com/repos/torvalds/linux".to_string(),
+                    status: 200,
+                    body: serde_json::json!({
+                        "name": "linux",
+                        "full_name": "torvalds/linux",
+                        "html_url": "https://github.com/torvalds/linux",
+                        "contributors_url": "https://api.github.com/repos/torvalds/linux/contributors",
+                        "stargazers_count": 150000
+                    }),
+                    headers: Default::default(),
+                },
+                Fixture {
+                    endpoint: "https://api.github.com/repos/torvalds/gone".to_string(),
+                    status: 404,
+                    body: serde_json::json!({"message": "Not Found"}),
+                    headers: Default::default(),
+                },
+            ])),
+        ));
+
+        let repo = api.get_repo("torvalds", "linux").await.unwrap();
+        assert_eq!(repo.name, "linux");
+        assert_eq!(repo.stargazers_count, 150000);
+        assert_eq!(
+            repo.contributors_url,
+            "https://api.github.com/repos/torvalds/linux/contributors"
+        );
+
+        let err = api.get_repo("torvalds", "gone").await.unwrap_err();
+        assert!(err.is::<NotFoundError>());
+    }
+
+    /// Answers contributors of repos with "slow" in the name only after a long delay
+    struct SlowTransport;
+
+    impl Transport for SlowTransport {
+        fn get<'a>(
+            &'a self,
+            endpoint: &'a str,
+            _headers: HeaderMap,
+        ) -> BoxFuture<'a, Result<RawResponse, SendError>> {
+            Box::pin(async move {
+                if endpoint.contains("slow") {
+                    tokio::time::sleep(Duration::from_secs(30)).await;
+                }
+
+                Ok(RawResponse {
+                    status: StatusCode::OK,
+                    headers: HeaderMap::new(),
+                    body: r#"[{"login": "a", "contributions": 1}]"#.to_string(),
+                })
+            })
+        }
+    }
+
+    /// Fails the first request for each endpoint with 502, answers the next ones
+    #[derive(Default)]
+    struct FlakyTransport {
+        seen: Mutex<HashSet<String>>,
+    }
+
+    impl Transport for FlakyTransport {
+        fn get<'a>(
+            &'a self,
+            endpoint: &'a str,
+            _headers: HeaderMap,
+        ) -> BoxFuture<'a, Result<RawResponse, SendError>> {
+            Box::pin(async move {
+                let first = self.seen.lock().unwrap().insert(endpoint.to_string());
+
+                Ok(RawResponse {
+                    status: if first && endpoint.contains("flaky") {
+                        StatusCode::BAD_GATEWAY
+                    } else {
+                        StatusCode::OK
+                    },
+                    headers: HeaderMap::new(),
+                    body: r#"[{"login": "a", "contributions": 1}]"#.to_string(),
+                })
+            })
+        }
+    }
+
+    /// Answers with two pages of contributors, first one comes later than second one.
+    /// Last of the first page is tied with one of the second page
+    struct OutOfOrderTransport;
+
+    impl Transport for OutOfOrderTransport {
+        fn get<'a>(
+            &'a self,
+            endpoint: &'a str,
+            _headers: HeaderMap,
+        ) -> BoxFuture<'a, Result<RawResponse, SendError>> {
+            Box::pin(async move {
+                let body = if endpoint.ends_with("&page=1") {
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    r#"[{"login": "second", "contributions": 50}, {"login": "third", "contributions": 40}]"#
+                } else {
+                    r#"[{"login": "first", "contributions": 60}, {"login": "tied", "contributions": 40},
+                        {"login": "fifth", "contributions": 1}]"#
+                };
+
+                Ok(RawResponse {
+                    status: StatusCode::OK,
+                    headers: HeaderMap::new(),
+                    body: body.to_string(),
+                })
+            })
+        }
+    }
+
+    /// Answers every contributors page with a full page of distinct contributors
+    struct FullPageTransport;
+
+    impl Transport for FullPageTransport {
+        fn get<'a>(
+            &'a self,
+            endpoint: &'a str,
+            _headers: HeaderMap,
+        ) -> BoxFuture<'a, Result<RawResponse, SendError>> {
+            Box::pin(async move {
+                let contributors: Vec<String> = (0..PAGE_LIMIT)
+                    .map(|idx| {
+                        format!(r#"{{"login": "{}-{}", "contributions": 1}}"#, endpoint, idx)
+                    })
+                    .collect();
+
+                Ok(RawResponse {
+                    status: StatusCode::OK,
+                    headers: HeaderMap::new(),
+                    body: format!("[{}]", contributors.join(",")),
+                })
+            })
+        }
+    }
+
+    /// Answers every request with given status
+    struct StatusTransport(StatusCode);
+
+    impl Transport for StatusTransport {
+        fn get<'a>(
+            &'a self,
+            _endpoint: &'a str,
+            _headers: HeaderMap,
+        ) -> BoxFuture<'a, Result<RawResponse, SendError>> {
+            Box::pin(async move {
+                Ok(RawResponse {
+                    status: self.0,
+                    headers: HeaderMap::new(),
+                    body: String::new(),
+                })
+            })
+        }
+    }
+
+    /// Answers every request as github does for an empty repo
+    struct NoContentTransport;
+
+    impl Transport for NoContentTransport {
+        fn get<'a>(
+            &'a self,
+            _endpoint: &'a str,
+            _headers: HeaderMap,
+        ) -> BoxFuture<'a, Result<RawResponse, SendError>> {
+            Box::pin(async move {
+                Ok(RawResponse {
+                    status: StatusCode::NO_CONTENT,
+                    headers: HeaderMap::new(),
+                    body: String::new(),
+                })
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn no_content_means_no_contributors() {
+        let api = GithubApi::with_client(GithubClient::with_transport(
+            "",
+            &TransportConfig::default(),
+            Arc::new(NoContentTransport),
+        ));
+
+        let share = api
+            .calculate_repo_share(
+                "https://api.github.com/repos/o/empty/contributors",
+                &BusFactorQuery::default(),
+            )
+            .await
+            .unwrap();
+
+        assert!(share.is_none());
+    }
+
+    #[tokio::test]
+    async fn empty_repo_is_reported_only_if_asked_for() {
+        let repos = Repos {
+            items: vec![
+                RepoData {
+                    name: "empty".to_string(),
+                    contributors_url: "a/contributors".to_string(),
+                    stargazers_count: 2,
+                    ..RepoData::default()
+                },
+                RepoData {
+                    name: "full".to_string(),
+                    contributors_url: "b/contributors".to_string(),
+                    ..RepoData::default()
+                },
+            ],
+            ..Repos::default()
+        };
+        let contributors = r#"[{"login": "a", "contributions": 1}]"#;
+        let query = BusFactorQuery {
+            min_contributors: 1,
+            ..BusFactorQuery::default()
+        };
+
+        let res = SequenceTransport::api(&["[]", contributors])
+            .get_repos_bus_factor(&repos, &query)
+            .await
+            .unwrap();
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].repo_name, "full");
+
+        let query = BusFactorQuery {
+            include_empty: true,
+            ..query
+        };
+        let res = SequenceTransport::api(&["[]", contributors])
+            .get_repos_bus_factor(&repos, &query)
+            .await
+            .unwrap();
+        assert_eq!(res.len(), 2);
+        assert!(res[0].no_contributors);
+        assert_eq!(res[0].stars, 2);
+        assert!(res[0].leader.bus_factor.is_nan());
+        assert!(!res[0].is_significant(0.0));
+        assert!(!res[1].no_contributors);
+    }
+
+    #[test]
+    fn repo_timestamps_are_parsed() {
+        let page = r#"{"items": [
+            {"name": "a", "stargazers_count": 3, "created_at": "2015-10-21T07:28:00Z",
+             "updated_at": "2021-12-24T10:15:30+01:00"},
+            {"name": "b", "stargazers_count": 2, "created_at": "yesterday", "updated_at": null},
+            {"name": "c", "stargazers_count": 1}]}"#;
+
+        let repos: Repos = serde_json::from_str(page).unwrap();
+
+        let a = &repos.items[0];
+        assert_eq!(
+            a.created_at.unwrap().to_rfc3339(),
+            "2015-10-21T07:28:00+00:00"
+        );
+        assert_eq!(
+            a.updated_at.unwrap().to_rfc3339(),
+            "2021-12-24T09:15:30+00:00"
+        );
+        // Bad timestamp does not fail the page
+        assert_eq!(repos.items[1].created_at, None);
+        assert_eq!(repos.items[1].updated_at, None);
+        assert_eq!(repos.items[2].created_at, None);
+
+        let json = serde_json::to_string(a).unwrap();
+        assert!(
+            json.contains(r#""created_at":"2015-10-21T07:28:00Z""#),
+            "{}",
+            json
+        );
+        assert_eq!(&serde_json::from_str::<RepoData>(&json).unwrap(), a);
+    }
+
+    #[tokio::test]
+    async fn repo_without_contributors_url_does_not_break_page() {
+        let page = r#"{"items": [
+            {"name": "a", "stargazers_count": 3, "contributors_url": "a/contributors"},
+            {"name": "b", "stargazers_count": 2, "full_name": "o/b"},
+            {"name": "c", "stargazers_count": 1, "contributors_url": null}]}"#;
+        let repos: Repos = serde_json::from_str(page).unwrap();
+        assert_eq!(repos.items.len(), 3);
+
+        let contributors = r#"[{"login": "a", "contributions": 1}]"#;
+        let api = SequenceTransport::api(&[contributors, contributors]);
+
+        let res = api
+            .get_repos_bus_factor(&repos, &BusFactorQuery::default())
+            .await
+            .unwrap();
+
+        // Url of b is made from its full name, c has nothing to make it from
+        let names: Vec<&str> = res.iter().map(|repo| repo.repo_name.as_str()).collect();
+        assert_eq!(names, vec!["a", "b"]);
+        assert_eq!(
+            repos.items[1].contributors_endpoint().unwrap(),
+            "/repos/o/b/contributors"
+        );
+    }
+
+    #[tokio::test]
+    async fn connection_check_fails_on_error_status() {
+        let api = GithubApi::with_client(GithubClient::with_transport(
+            "",
+            &TransportConfig::default(),
+            Arc::new(StatusTransport(StatusCode::SERVICE_UNAVAILABLE)),
+        ));
+        assert!(api.check_connection().await.is_err());
+
+        let api = SequenceTransport::api(&["{}"]);
+        assert!(api.check_connection().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn owner_led_repo_is_detected() {
+        let page = r#"{"items": [
+            {"name": "solo", "stargazers_count": 2, "contributors_url": "solo/contributors", "owner": {"login": "Alice"}},
+            {"name": "team", "stargazers_count": 1, "contributors_url": "team/contributors", "owner": {"login": "org"}}]}"#;
+        let repos: Repos = serde_json::from_str(page).unwrap();
+        let contributors =
+            r#"[{"login": "alice", "contributions": 9}, {"login": "bob", "contributions": 1}]"#;
+        let api = SequenceTransport::api(&[contributors, contributors]);
+
+        let res = api
+            .get_repos_bus_factor(&repos, &BusFactorQuery::default())
+            .await
+            .unwrap();
+
+        assert!(res[0].owner_led);
+        assert!(!res[1].owner_led);
+    }
+
+    #[tokio::test]
+    async fn requests_are_counted_per_repo() {
+        let api = SequenceTransport::api(&[r#"[{"login": "a", "contributions": 1}]"#]);
+        let repos = Repos {
+            items: vec![RepoData {
+                name: "a".to_string(),
+                contributors_url: "a/contributors".to_string(),
+                ..RepoData::default()
+            }],
+            ..Repos::default()
+        };
+        let query = BusFactorQuery {
+            count_requests: true,
+            ..BusFactorQuery::default()
+        };
+
+        let res = api.get_repos_bus_factor(&repos, &query).await.unwrap();
+
+        assert_eq!(res[0].requests, Some(1));
+    }
+
+    #[tokio::test]
+    async fn repeated_contributors_url_is_requested_once() {
+        // Second request would run out of responses
+        let api = SequenceTransport::api(&[r#"[{"login": "a", "contributions": 1}]"#]);
+        let url = "https://api.github.com/repos/o/a/contributors";
+
+        let (first, second) = futures::join!(
+            api.get_contributions(url, 25),
+            api.get_contributions(url, 25)
+        );
+        let again = api.get_contributions(url, 25).await.unwrap();
+
+        assert_eq!(first.unwrap(), second.unwrap());
+        assert_eq!(again.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn repo_latencies_are_recorded_with_timing() {
+        let api =
+            SequenceTransport::api(&[r#"[{"login": "a", "contributions": 1}]"#]).with_timing();
+        let repos = Repos {
+            items: vec![RepoData {
+                name: "a".to_string(),
+                contributors_url: "a/contributors".to_string(),
+                ..RepoData::default()
+            }],
+            ..Repos::default()
+        };
+
+        api.get_repos_bus_factor(&repos, &BusFactorQuery::default())
+            .await
+            .unwrap();
+
+        assert_eq!(api.take_repo_latencies().len(), 1);
+        assert!(api.take_repo_latencies().is_empty());
+
+        // Part of the run has latencies of its own
+        let scoped = api.scoped();
+        scoped
+            .get_repos_bus_factor(&repos, &BusFactorQuery::default())
+            .await
+            .unwrap();
+        assert!(api.take_repo_latencies().is_empty());
+        assert_eq!(scoped.take_repo_latencies().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn clones_share_cache_across_tasks() {
+        // Second request would run out of responses
+        let api = SequenceTransport::api(&[r#"[{"login": "a", "contributions": 1}]"#]);
+        let url = "https://api.github.com/repos/o/a/contributors";
+
+        // Errors are not Send yet, so the task is a local one
+        let clone = api.clone();
+        let tasks = tokio::task::LocalSet::new();
+        let spawned = tasks
+            .run_until(async move {
+                tokio::task::spawn_local(async move { clone.get_contributions(url, 25).await })
+                    .await
+                    .unwrap()
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(api.get_contributions(url, 25).await.unwrap(), spawned);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn workers_run_as_tasks_on_many_threads() {
+        let body = r#"[{"login": "a", "contributions": 1}]"#;
+        let api = SequenceTransport::api(&[body; 20]);
+        let repos = Repos {
+            items: (0..20)
+                .map(|idx| RepoData {
+                    name: format!("repo{}", idx),
+                    contributors_url: format!("repo{}/contributors", idx),
+                    ..RepoData::default()
+                })
+                .collect(),
+            ..Repos::default()
+        };
+
+        let res = api
+            .get_repos_bus_factor(&repos, &BusFactorQuery::default())
+            .await
+            .unwrap();
+
+        // Order of repos is kept, no matter which task finished first
+        let names: Vec<String> = res.iter().map(|repo| repo.repo_name.clone()).collect();
+        let expected: Vec<String> = (0..20).map(|idx| format!("repo{}", idx)).collect();
+        assert_eq!(names, expected);
+    }
+
+    #[tokio::test]
+    async fn raw_contributions_are_returned() {
+        let api = SequenceTransport::api(&[
+            r#"[{"login": "leader", "contributions": 9}, {"login": "other", "contributions": 1}]"#,
+        ]);
+
+        let contributions = api
+            .get_contributions("/repos/o/repo/contributors", 2)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            contributions,
+            vec![
+                ContributorData {
+                    login: "leader".to_string(),
+                    contributions: 9,
+                    ..ContributorData::default()
+                },
+                ContributorData {
+                    login: "other".to_string(),
+                    contributions: 1,
+                    ..ContributorData::default()
+                },
+            ]
+        );
+        assert!(api
+            .get_contributions("/repos/o/repo/contributors", 0)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn empty_body_means_no_contributors() {
+        let api = SequenceTransport::api(&[""]);
+
+        let contributions = api
+            .get_contributions("https://api.github.com/repos/o/empty/contributors", 25)
+            .await
+            .unwrap();
+
+        assert!(contributions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn page_size_stays_within_api_cap() {
+        let api = GithubApi::with_client(GithubClient::with_transport(
+            "",
+            &TransportConfig::default(),
+            Arc::new(FullPageTransport),
+        ));
+
+        let contributions = api
+            .get_contributions("https://api.github.com/repos/o/huge/contributors", 250)
+            .await
+            .unwrap();
+
+        // Github caps per_page at 100, larger one would silently give only 100
+        assert_eq!(contributions.len(), 250);
+        assert!(contributions
+            .iter()
+            .all(|contr| contr.login.contains("per_page=100&page=")));
+    }
+
+    #[tokio::test]
+    async fn contributor_pages_are_capped() {
+        let api = GithubApi::with_client(GithubClient::with_transport(
+            "",
+            &TransportConfig::default(),
+            Arc::new(FullPageTransport),
+        ));
+        let query = BusFactorQuery::builder()
+            .users_to_consider(500)
+            .max_contributor_pages(2)
+            .build()
+            .unwrap();
+
+        let (share, requests) = github_client::count_requests(
+            api.calculate_repo_share("https://api.github.com/repos/o/huge/contributors", &query),
+        )
+        .await;
+        let share = share.unwrap().unwrap();
+
+        assert_eq!(requests, 2);
+        assert_eq!(share.considered, 200);
+        assert!(share.approximate);
+
+        assert!(BusFactorQuery::builder()
+            .max_contributor_pages(0)
+            .build()
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn contributor_pages_are_merged_in_order() {
+        let api = GithubApi::with_client(GithubClient::with_transport(
+            "",
+            &TransportConfig::default(),
+            Arc::new(OutOfOrderTransport),
+        ));
+
+        let contributions = api
+            .get_contributions("https://api.github.com/repos/o/huge/contributors", 150)
+            .await
+            .unwrap();
+
+        let logins: Vec<&str> = contributions.iter().map(|c| c.login.as_str()).collect();
+        // Ties keep the order of pages, like in a single long page
+        assert_eq!(logins, vec!["first", "second", "third", "tied", "fifth"]);
+    }
+
+    #[tokio::test]
+    async fn slow_repo_is_skipped_others_proceed() {
+        let api = GithubApi::with_client(GithubClient::with_transport(
+            "",
+            &TransportConfig::default(),
+            Arc::new(SlowTransport),
+        ));
+        let repo = |name: &str| RepoData {
+            contributors_url: format!("https://api.github.com/repos/o/{}/contributors", name),
+            name: name.to_string(),
+            ..RepoData::default()
+        };
+        // More repos than workers, so slow one shares the worker with others
+        let repos = Repos {
+            items: (0..12)
+                .map(|i| {
+                    repo(&if i == 2 {
+                        "slow".to_string()
+                    } else {
+                        format!("repo-{}", i)
+                    })
+                })
+                .collect(),
+            ..Repos::default()
+        };
+        let query = BusFactorQuery {
+            delay_sec: 0.05,
+            timeout_per_repo: Some(Duration::from_millis(200)),
+            ..BusFactorQuery::default()
+        };
+
+        let start = std::time::Instant::now();
+        let res = api.get_repos_bus_factor(&repos, &query).await.unwrap();
+
+        assert_eq!(res.len(), 11);
+        assert!(res.iter().all(|bus_factor| bus_factor.repo_name != "slow"));
+        assert_eq!(api.take_skipped_repos(), 1);
+        assert_eq!(api.take_skipped_repos(), 0);
+        // Timeout and pacing of 3 jobs per worker, nowhere near waiting for the slow repo
+        assert!(
+            start.elapsed() < Duration::from_secs(2),
+            "took {:?}",
+            start.elapsed()
+        );
+
+        let query = BusFactorQuery {
+            fail_fast: true,
+            ..query
+        };
+        let err = api.get_repos_bus_factor(&repos, &query).await.unwrap_err();
+        assert!(err.is::<RepoTimeoutError>());
+    }
+
+    #[tokio::test]
+    async fn failed_repos_are_tried_again_after_others() {
+        let api = || {
+            GithubApi::with_client(GithubClient::with_transport(
+                "",
+                &TransportConfig::default(),
+                Arc::new(FlakyTransport::default()),
+            ))
+        };
+        let repo = |name: &str| RepoData {
+            contributors_url: format!("https://api.github.com/repos/o/{}/contributors", name),
+            name: name.to_string(),
+            ..RepoData::default()
+        };
+        let repos = Repos {
+            items: vec![repo("fine"), repo("flaky")],
+            ..Repos::default()
+        };
+
+        let once = api();
+        let res = once
+            .get_repos_bus_factor(&repos, &BusFactorQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(res.len(), 1);
+        assert_eq!(once.take_skipped_repos(), 1);
+
+        let query = BusFactorQuery {
+            repo_retries: RetryPolicy {
+                max_retries: 2,
+                backoff: Duration::from_millis(10),
+                ..RetryPolicy::default()
+            },
+            ..BusFactorQuery::default()
+        };
+        let again = api();
+        let res = again.get_repos_bus_factor(&repos, &query).await.unwrap();
+        // Order of the repos is kept
+        assert_eq!(res.len(), 2);
+        assert_eq!(res[1].repo_name, "flaky");
+        assert_eq!(again.take_skipped_repos(), 0);
+    }
+
+    #[tokio::test]
+    async fn dumped_contributors_read_back() {
+        let dir = std::env::temp_dir().join(format!("bus_factor_raw_{}", std::process::id()));
+        let body = r#"[{"login": "a", "contributions": 3, "html_url": "https://github.com/a"},
+            {"login": "b", "contributions": 1}]"#;
+        // Only one response, the same page requested again comes from the cache
+        let api = SequenceTransport::api(&[body]).with_raw_dump(&dir);
+        let url = "https://api.github.com/repos/o/r/contributors";
+
+        let fetched = api.get_contributions(url, 25).await.unwrap();
+        assert_eq!(api.get_contributions(url, 25).await.unwrap(), fetched);
+
+        let dumped =
+            fs::read_to_string(dir.join("o").join("r").join("contributors-1.json")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(dumped, body);
+        assert_eq!(
+            serde_json::from_str::<Contributions>(&dumped).unwrap(),
+            fetched
+        );
+
+        let path = GithubApi::raw_dump_path(
+            &dir,
+            "https://mirror/repos/o/r/contributors?per_page=100&page=3",
+        );
+        assert_eq!(path, dir.join("o").join("r").join("contributors-3.json"));
+        let path = GithubApi::raw_dump_path(&dir, "/repos/../../etc/contributors?per_page=1");
+        assert_eq!(path, dir.join("etc").join("contributors-1.json"));
+    }
+
+    #[tokio::test]
+    async fn offline_answers_only_recorded_requests() {
+        let dir = std::env::temp_dir().join(format!("bus_factor_offline_{}", std::process::id()));
+        let repo = |name: &str| RepoData {
+            contributors_url: format!("https://api.github.com/repos/o/{}/contributors", name),
+            name: name.to_string(),
+            ..RepoData::default()
+        };
+        let repos = Repos {
+            items: vec![repo("recorded")],
+            ..Repos::default()
+        };
+        let query = BusFactorQuery {
+            fail_fast: true,
+            ..BusFactorQuery::default()
+        };
+
+        let recording = TransportConfig {
+            record_dir: Some(dir.clone()),
+            ..TransportConfig::default()
+        };
+        let online = GithubApi::with_client(GithubClient::with_transport(
+            "",
+            &recording,
+            Arc::new(SequenceTransport {
+                bodies: Mutex::new(
+                    vec![r#"[{"login": "a", "contributions": 1}]"#.to_string()].into(),
+                ),
+            }),
+        ));
+        let recorded = online.get_repos_bus_factor(&repos, &query).await.unwrap();
+
+        // Sequence is used up, nothing but the recording can answer
+        let offline = GithubApi::offline(&dir, &recording).unwrap();
+        let replayed = offline.get_repos_bus_factor(&repos, &query).await.unwrap();
+        assert_eq!(replayed, recorded);
+
+        let missed = Repos {
+            items: vec![repo("recorded"), repo("cold")],
+            ..Repos::default()
+        };
+        let err = offline
+            .get_repos_bus_factor(&missed, &query)
+            .await
+            .unwrap_err();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(err.is::<MissingFixtureError>());
+        let message = err.to_string();
+        assert!(
+            message.contains("https://api.github.com/repos/o/cold/contributors?per_page=25"),
+            "{}",
+            message
+        );
+        assert!(message.contains(&dir.display().to_string()), "{}", message);
+
+        // Nothing was ever recorded there
+        let err = GithubApi::offline(&dir, &recording).err().unwrap();
+        assert!(
+            err.to_string().contains(&dir.display().to_string()),
+            "{}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn bus_factor_runs_in_spawned_task() {
+        let api = GithubApi::with_client(GithubClient::with_transport(
+            "",
+            &TransportConfig::default(),
+            Arc::new(ReplayClient::new(vec![Fixture {
+                endpoint: "https://api.github.com/repos/o/fine/contributors?per_page=25"
+                    .to_string(),
+                status: 200,
+                body: serde_json::json!([{"login": "a", "contributions": 1}]),
+                headers: Default::default(),
+            }])),
+        ));
+        let repos = Repos {
+            items: vec![RepoData {
+                contributors_url: "https://api.github.com/repos/o/fine/contributors".to_string(),
+                name: "fine".to_string(),
+                ..RepoData::default()
+            }],
+            ..Repos::default()
+        };
+
+        // Does not compile unless the future and its error are Send + 'static
+        let res = tokio::spawn(async move {
+            api.get_repos_bus_factor(&repos, &BusFactorQuery::default())
+                .await
+        })
+        .await
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(res[0].repo_name, "fine");
+    }
+
+    /// Throttles the first request, then answers as the inner transport
+    struct ThrottleFirst {
+        inner: ReplayClient,
+        throttled: Mutex<bool>,
+    }
+
+    impl Transport for ThrottleFirst {
+        fn get<'a>(
+            &'a self,
+            endpoint: &'a str,
+            headers: HeaderMap,
+        ) -> BoxFuture<'a, Result<RawResponse, SendError>> {
+            if std::mem::replace(&mut *self.throttled.lock().unwrap(), true) {
+                return self.inner.get(endpoint, headers);
+            }
+
+            let mut headers = HeaderMap::new();
+            headers.insert(reqwest::header::RETRY_AFTER, "0".parse().unwrap());
+            Box::pin(async move {
+                Ok(RawResponse {
+                    status: StatusCode::TOO_MANY_REQUESTS,
+                    headers,
+                    body: r#"{"message": "You have exceeded a secondary rate limit"}"#.to_string(),
+                })
+            })
+        }
+    }
+
+    /// Buffer that stays readable after the writer is handed over
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn every_progress_event_is_sent_once() {
+        use crate::progress::{ProgressEvent, ProgressWriter};
+
+        let contributors = |repo: &str, body: serde_json::Value| Fixture {
+            endpoint: format!(
+                "https://api.github.com/repos/o/{}/contributors?per_page=25",
+                repo
+            ),
+            status: 200,
+            body,
+            headers: Default::default(),
+        };
+        let config = TransportConfig {
+            retry: RetryPolicy {
+                max_retries: 1,
+                backoff: Duration::from_millis(1),
+                ..RetryPolicy::default()
+            },
+            ..TransportConfig::default()
+        };
+        let buffer = SharedBuffer::default();
+        let progress = Arc::new(ProgressWriter::new(buffer.clone()));
+        let client = GithubClient::with_transport(
+            "",
+            &config,
+            Arc::new(ThrottleFirst {
+                inner: ReplayClient::new(vec![
+                    contributors("a", serde_json::json!([{"login": "x", "contributions": 3}, {"login": "y", "contributions": 1}])),
+                    contributors("b", serde_json::json!([{"login": "y", "contributions": 1}])),
+                    contributors("empty", serde_json::json!([])),
+                ]),
+                throttled: Mutex::new(false),
+            }),
+        )
+        .with_progress(progress.clone());
+        let api = GithubApi::with_client(client);
+        let repo = |name: &str| RepoData {
+            contributors_url: format!("https://api.github.com/repos/o/{}/contributors", name),
+            full_name: format!("o/{}", name),
+            name: name.to_string(),
+            ..RepoData::default()
+        };
+        let repos = Repos {
+            items: vec![repo("a"), repo("b"), repo("empty")],
+            ..Repos::default()
+        };
+        let query = BusFactorQuery {
+            include_empty: true,
+            ..BusFactorQuery::default()
+        };
+
+        progress.start(3);
+        api.get_repos_bus_factor(&repos, &query).await.unwrap();
+        progress.finish(true, Duration::from_millis(10));
+
+        let out = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let events: Vec<ProgressEvent> = out
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(events.len(), 6, "{}", out);
+        assert_eq!(
+            events[0],
+            ProgressEvent::Started {
+                schema: 1,
+                project_count: 3
+            }
+        );
+        assert_eq!(
+            events[5],
+            ProgressEvent::Finished {
+                success: true,
+                repos: 3,
+                elapsed_ms: 10
+            }
+        );
+        let rate_limited: Vec<&ProgressEvent> = events
+            .iter()
+            .filter(|event| matches!(event, ProgressEvent::RateLimited { .. }))
+            .collect();
+        assert_eq!(rate_limited.len(), 1);
+        assert!(matches!(
+            rate_limited[0],
+            ProgressEvent::RateLimited {
+                secondary: true,
+                wait_ms: 0,
+                ..
+            }
+        ));
+
+        // Workers finish in any order, each repo is there once
+        let mut completed: Vec<(String, Option<f64>, Option<String>)> = events
+            .iter()
+            .filter_map(|event| match event {
+                ProgressEvent::RepoCompleted {
+                    full_name,
+                    bus_factor,
+                    leader,
+                    ..
+                } => Some((full_name.clone(), *bus_factor, leader.clone())),
+                _ => None,
+            })
+            .collect();
+        completed.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            completed,
+            [
+                ("o/a".to_string(), Some(0.75), Some("x".to_string())),
+                ("o/b".to_string(), Some(1.0), Some("y".to_string())),
+                ("o/empty".to_string(), None, None),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn counts_behind_the_share_are_reported() {
+        let fixture = |endpoint: &str, body: serde_json::Value, headers: &[(&str, &str)]| Fixture {
+            endpoint: endpoint.to_string(),
+            status: 200,
+            body,
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        };
+        let api = GithubApi::with_client(GithubClient::with_transport(
+            "",
+            &TransportConfig::default(),
+            Arc::new(ReplayClient::new(vec![
+                fixture(
+                    "https://api.github.com/repos/o/r/contributors?per_page=25",
+                    serde_json::json!([{"login": "a", "contributions": 61}, {"login": "b", "contributions": 36}]),
+                    &[],
+                ),
+                fixture(
+                    "https://api.github.com/repos/o/r/commits?per_page=1",
+                    serde_json::json!([{}]),
+                    &[(
+                        "link",
+                        r#"<https://api.github.com/repos/o/r/commits?per_page=1&page=1013>; rel="last""#,
+                    )],
+                ),
+            ])),
+        ));
+        let repos = Repos {
+            items: vec![RepoData {
+                contributors_url: "https://api.github.com/repos/o/r/contributors".to_string(),
+                name: "r".to_string(),
+                ..RepoData::default()
+            }],
+            ..Repos::default()
+        };
+
+        let res = api
+            .get_repos_bus_factor(&repos, &BusFactorQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(res[0].leader_contributions, Some(61));
+        assert_eq!(res[0].total_considered_contributions, Some(97));
+        assert_eq!(res[0].true_total, None);
+        assert_eq!(res[0].leader.bus_factor, 61.0 / 97.0);
+        assert_eq!(res[0].truck_factor, None);
+
+        let query = BusFactorQuery {
+            coverage: Some(0.6),
+            ..BusFactorQuery::default()
+        };
+        let res = api.get_repos_bus_factor(&repos, &query).await.unwrap();
+        assert_eq!(res[0].truck_factor, Some(1));
+
+        let query = BusFactorQuery {
+            normalize: true,
+            ..BusFactorQuery::default()
+        };
+        let res = api.get_repos_bus_factor(&repos, &query).await.unwrap();
+        assert_eq!(res[0].leader_contributions, Some(61));
+        assert_eq!(res[0].total_considered_contributions, Some(97));
+        assert_eq!(res[0].true_total, Some(1013));
+        assert_eq!(res[0].leader.bus_factor, 61.0 / 1013.0);
+
+        let json = serde_json::to_value(&res[0]).unwrap();
+        assert_eq!(json["leader_contributions"], 61);
+        assert_eq!(json["true_total"], 1013);
+    }
+
+    #[tokio::test]
+    async fn only_most_starred_get_bus_factor() {
+        let contributors = |repo: &str| Fixture {
+            endpoint: format!(
+                "https://api.github.com/repos/o/{}/contributors?per_page=25",
+                repo
+            ),
+            status: 200,
+            body: serde_json::json!([{"login": "a", "contributions": 9}]),
+            headers: Default::default(),
+        };
+        // Contributors of the least starred one are not there, requesting them would fail
+        let api = GithubApi::with_client(GithubClient::with_transport(
+            "",
+            &TransportConfig::default(),
+            Arc::new(ReplayClient::new(vec![
+                contributors("many"),
+                contributors("some"),
+            ])),
+        ));
+        let repo = |name: &str, stars: u64| RepoData {
+            contributors_url: format!("https://api.github.com/repos/o/{}/contributors", name),
+            name: name.to_string(),
+            full_name: format!("o/{}", name),
+            stargazers_count: stars,
+            ..RepoData::default()
+        };
+        let repos = Repos {
+            items: vec![repo("few", 5), repo("many", 50), repo("some", 20)],
+            ..Repos::default()
+        };
+
+        let res = api
+            .get_top_repos_bus_factor(&repos, &BusFactorQuery::default(), 2)
+            .await
+            .unwrap();
+
+        let names: Vec<&str> = res.iter().map(|repo| repo.repo_name.as_str()).collect();
+        assert_eq!(names, ["many", "some", "few"]);
+        assert!(res[..2]
+            .iter()
+            .all(|repo| repo.has_share() && !repo.not_calculated));
+        assert!(res[2].not_calculated && !res[2].has_share());
+        assert!(res[2].leader.bus_factor.is_nan());
+        assert!(!res[2].is_significant(0.0));
+        assert_eq!((res[2].full_name.as_str(), res[2].stars), ("o/few", 5));
+
+        let json = serde_json::to_value(&res).unwrap();
+        assert_eq!(json[2]["not_calculated"], true);
+        assert!(json[0].get("not_calculated").is_none());
+
+        assert!(api.take_warnings().is_empty());
+
+        // Limit above the count calculates everything, missing contributors skip the repo
+        let all = api
+            .get_top_repos_bus_factor(&repos, &BusFactorQuery::default(), 10)
+            .await
+            .unwrap();
+        assert_eq!(all.len(), 2);
+        assert!(all.iter().all(|repo| !repo.not_calculated));
+        assert_eq!(
+            api.take_warnings()[0].category,
+            WarningCategory::SkippedRepo
+        );
+    }
+
+    #[tokio::test]
+    async fn leader_profile_is_reported_if_known() {
+        let contributors = |repo: &str, body: serde_json::Value| Fixture {
+            endpoint: format!(
+                "https://api.github.com/repos/o/{}/contributors?per_page=25",
+                repo
+            ),
+            status: 200,
+            body,
+            headers: Default::default(),
+        };
+        let api = GithubApi::with_client(GithubClient::with_transport(
+            "",
+            &TransportConfig::default(),
+            Arc::new(ReplayClient::new(vec![
+                contributors(
+                    "known",
+                    serde_json::json!([{
+                        "login": "a",
+                        "contributions": 9,
+                        "html_url": "https://github.com/a",
+                        "avatar_url": "https://avatars.githubusercontent.com/u/1?v=4"
+                    }]),
+                ),
+                // Like anonymous contributors, there are no urls
+                contributors(
+                    "unknown",
+                    serde_json::json!([{"login": "b", "contributions": 9}]),
+                ),
+            ])),
+        ));
+        let repo = |name: &str| RepoData {
+            contributors_url: format!("https://api.github.com/repos/o/{}/contributors", name),
+            name: name.to_string(),
+            ..RepoData::default()
+        };
+        let repos = Repos {
+            items: vec![repo("known"), repo("unknown")],
+            ..Repos::default()
+        };
+
+        let res = api
+            .get_repos_bus_factor(&repos, &BusFactorQuery::default())
+            .await
+            .unwrap();
+        let json = serde_json::to_value(&res).unwrap();
+
+        assert_eq!(json[0]["leader"]["profile_url"], "https://github.com/a");
+        assert_eq!(
+            json[0]["leader"]["avatar_url"],
+            "https://avatars.githubusercontent.com/u/1?v=4"
+        );
+        assert!(json[1]["leader"].get("profile_url").is_none());
+        assert!(json[1]["leader"].get("avatar_url").is_none());
+        assert_eq!(serde_json::from_value::<Vec<BusFactor>>(json).unwrap(), res);
+    }
+
+    #[tokio::test]
+    async fn failed_repo_is_skipped_unless_fail_fast() {
+        let contributors = |repo: &str, status: u16, body: serde_json::Value| Fixture {
+            endpoint: format!(
+                "https://api.github.com/repos/o/{}/contributors?per_page=25",
+                repo
+            ),
+            status,
+            body,
+            headers: Default::default(),
+        };
+        let api = |fixtures: Vec<Fixture>| {
+            GithubApi::with_client(GithubClient::with_transport(
+                "",
+                &TransportConfig::default(),
+                Arc::new(ReplayClient::new(fixtures)),
+            ))
+        };
+        let fixtures = || {
+            vec![
+                contributors(
+                    "huge",
+                    403,
+                    serde_json::json!({ "message": "The history or contributor list is too large to list contributors for this repository via the API." }),
+                ),
+                contributors(
+                    "fine",
+                    200,
+                    serde_json::json!([{"login": "a", "contributions": 1}]),
+                ),
+            ]
+        };
+        let repo = |name: &str| RepoData {
+            contributors_url: format!("https://api.github.com/repos/o/{}/contributors", name),
+            name: name.to_string(),
+            ..RepoData::default()
+        };
+        let repos = Repos {
+            items: vec![repo("huge"), repo("fine")],
+            ..Repos::default()
+        };
+
+        let resilient = api(fixtures());
+        let res = resilient
+            .get_repos_bus_factor(&repos, &BusFactorQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].repo_name, "fine");
+        assert_eq!(resilient.take_skipped_repos(), 1);
+        let warnings = resilient.take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].category, WarningCategory::SkippedRepo);
+        assert!(
+            warnings[0].message.contains("Project huge failed"),
+            "{}",
+            warnings[0].message
+        );
+        assert!(resilient.take_warnings().is_empty());
+
+        let query = BusFactorQuery {
+            fail_fast: true,
+            ..BusFactorQuery::default()
+        };
+        let err = api(fixtures())
+            .get_repos_bus_factor(&repos, &query)
+            .await
+            .unwrap_err();
+        assert!(err.is::<ResponseError>());
+
+        // Without permissions no other repo would do better
+        let forbidden = contributors(
+            "huge",
+            403,
+            serde_json::json!({ "message": "Resource not accessible by personal access token" }),
+        );
+        let err = api(vec![forbidden, fixtures().pop().unwrap()])
+            .get_repos_bus_factor(&repos, &BusFactorQuery::default())
+            .await
+            .unwrap_err();
+        assert!(err.is::<PermissionError>());
+    }
+
+    #[tokio::test]
+    async fn missing_permissions_are_hinted() {
+        let forbidden = |repo: &str, message: &str| Fixture {
+            endpoint: format!(
+                "https://api.github.com/repos/o/{}/contributors?per_page=25",
+                repo
+            ),
+            status: 403,
+            body: serde_json::json!({ "message": message }),
+            headers: Default::default(),
+        };
+        let api = GithubApi::with_client(GithubClient::with_transport(
+            "",
+            &TransportConfig::default(),
+            Arc::new(ReplayClient::new(vec![
+                forbidden("private", "Resource not accessible by personal access token"),
+                forbidden("huge", "The history or contributor list is too large to list contributors for this repository via the API."),
+            ])),
+        ));
+        let query = BusFactorQuery::default();
+
+        let err = api
+            .calculate_repo_share(
+                "https://api.github.com/repos/o/private/contributors",
+                &query,
+            )
+            .await
+            .unwrap_err();
+        assert!(err.is::<PermissionError>());
+        assert!(err.to_string().contains("Metadata and Contents"));
+
+        // Not every 403 is about permissions
+        let err = api
+            .calculate_repo_share("https://api.github.com/repos/o/huge/contributors", &query)
+            .await
+            .unwrap_err();
+        assert!(err.is::<ResponseError>());
+    }
+
+    #[tokio::test]
+    async fn search_forbidden_is_permission_error() {
+        let search = |status: u16, body: serde_json::Value, headers: &[(&str, &str)]| Fixture {
+            endpoint: format!("{}?q=language:rust&per_page=1&page=1", REPO_ENDPONT),
+            status,
+            body,
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        };
+        let api = |fixture: Fixture| {
+            GithubApi::with_client(GithubClient::with_transport(
+                "",
+                &TransportConfig::default(),
+                Arc::new(ReplayClient::new(vec![fixture])),
+            ))
+        };
+        let forbidden = serde_json::json!({
+            "message": "Resource not accessible by personal access token",
+            "documentation_url": "https://docs.github.com/rest/search/search#search-repositories"
+        });
+
+        let err = api(search(403, forbidden.clone(), &[]))
+            .get_repos_from_page("?q=language:rust", 1, 1)
+            .await
+            .unwrap_err();
+        assert!(err.is::<PermissionError>(), "{}", err);
+        assert!(err.to_string().contains("not allowed to search"), "{}", err);
+
+        // Exhausted rate limit is still reported as such
+        let err = api(search(
+            403,
+            forbidden,
+            &[("x-ratelimit-remaining", "0"), ("x-ratelimit-reset", "0")],
+        ))
+        .get_repos_from_page("?q=language:rust", 1, 1)
+        .await
+        .unwrap_err();
+        assert!(err.is::<crate::api_errors::RateLimitError>(), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn gone_repos_are_dropped() {
+        let fixture = |endpoint: &str, status: u16, body: serde_json::Value| Fixture {
+            endpoint: endpoint.to_string(),
+            status,
+            body,
+            headers: Default::default(),
+        };
+        let repo = |name: &str| RepoData {
+            contributors_url: format!("https://api.github.com/repos/owner/{}/contributors", name),
+            name: name.to_string(),
+            ..RepoData::default()
+        };
+
+        let api = GithubApi::with_client(GithubClient::with_transport(
+            "",
+            &TransportConfig::default(),
+            Arc::new(ReplayClient::new(vec![
+                fixture(
+                    "https://api.github.com/repos/owner/alive",
+                    200,
+                    serde_json::json!({"name": "alive"}),
+                ),
+                fixture(
+                    "https://api.github.com/repos/owner/gone",
+                    404,
+                    serde_json::json!({"message": "Not Found"}),
+                ),
+            ])),
+        ));
+        let repos = Repos {
+            items: vec![repo("alive"), repo("gone")],
+            ..Repos::default()
+        };
+
+        let verified = api.verify_repos(&repos).await.unwrap();
+
+        assert_eq!(verified.items, vec![repo("alive")]);
+    }
+
+    #[tokio::test]
+    async fn forks_are_excluded_by_default() {
+        let repos = Repos {
+            items: vec![
+                RepoData {
+                    name: "parent".to_string(),
+                    contributors_url: "https://api.github.com/repos/owner/parent/contributors"
+                        .to_string(),
+                    ..RepoData::default()
+                },
+                RepoData {
+                    name: "fork".to_string(),
+                    contributors_url: "https://api.github.com/repos/other/fork/contributors"
+                        .to_string(),
+                    fork: true,
+                    ..RepoData::default()
+                },
+            ],
+            ..Repos::default()
+        };
+        let contributors = r#"[{"login": "leader", "contributions": 9}]"#;
+
+        // Only one response, request for the fork would run out of them
+        let api = SequenceTransport::api(&[contributors]);
+        let res = api
+            .get_repos_bus_factor(&repos, &BusFactorQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].repo_name, "parent");
+
+        let api = SequenceTransport::api(&[contributors, contributors]);
+        let query = BusFactorQuery::builder()
+            .include_forks(true)
+            .build()
+            .unwrap();
+        let res = api.get_repos_bus_factor(&repos, &query).await.unwrap();
+        assert_eq!(res.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn members_are_collected_from_all_pages() {
+        let page = |from: usize, to: usize| {
+            let members: Vec<String> = (from..to)
+                .map(|i| format!(r#"{{"login": "User{}"}}"#, i))
+                .collect();
+            format!("[{}]", members.join(","))
+        };
+        let api = SequenceTransport::api(&[&page(0, 100), &page(100, 130)]);
+
+        let members = api
+            .get_members(&MemberScope::Org("org".to_string()))
+            .await
+            .unwrap();
+
+        assert_eq!(members.len(), 130);
+        // Compared with logins of contributors by lowercase
+        assert!(members.contains("user129"));
+    }
+
+    #[test]
+    fn full_name_from_str() {
+        let full_name: FullName = "Owner/Repo".parse().unwrap();
+        assert_eq!(full_name.to_string(), "Owner/Repo");
+        assert!(full_name.matches("owner/repo"));
+        assert!(!full_name.matches("owner/other"));
+
+        assert!("repo".parse::<FullName>().is_err());
+        assert!("owner/".parse::<FullName>().is_err());
+        assert!("owner/repo/tree".parse::<FullName>().is_err());
+    }
+
+    #[test]
+    fn team_from_str() {
+        assert_eq!(
+            "org/team".parse::<MemberScope>().unwrap(),
+            MemberScope::Team {
+                org: "org".to_string(),
+                team: "team".to_string()
+            }
+        );
+        assert!("org".parse::<MemberScope>().is_err());
+        assert!("/team".parse::<MemberScope>().is_err());
+    }
+
+    #[tokio::test]
+    async fn lines_changed_basis_is_not_supported() {
+        // No responses, query has to be rejected before anything is requested
+        let api = SequenceTransport::api(&[]);
+        let repos = Repos {
+            items: vec![RepoData::default()],
+            ..Repos::default()
+        };
+        let query = BusFactorQuery {
+            basis: ShareBasis::LinesChanged,
+            ..BusFactorQuery::default()
+        };
+
+        let err = api.get_repos_bus_factor(&repos, &query).await.unwrap_err();
+        assert!(err.is::<InvalidQueryError>());
+    }
+
+    #[tokio::test]
+    async fn shares_are_taken_from_merged_pull_requests() {
+        let page = |page: u32, authors: Vec<Option<&str>>| {
+            Fixture {
+            endpoint: format!(
+                "https://api.github.com/search/issues?q=repo:o/n+is:pr+is:merged&per_page=100&page={}",
+                page
+            ),
+            status: 200,
+            body: serde_json::json!({
+                "total_count": 105,
+                "items": authors
+                    .into_iter()
+                    .map(|author| serde_json::json!({ "user": author.map(|login| serde_json::json!({ "login": login })) }))
+                    .collect::<Vec<_>>(),
+            }),
+            headers: Default::default(),
+        }
+        };
+        let mut first = vec![Some("a"); 70];
+        first.extend(vec![Some("b"); 29]);
+        // Deleted account
+        first.push(None);
+        let api = |fixtures: Vec<Fixture>| {
+            GithubApi::with_client(GithubClient::with_transport(
+                "",
+                &TransportConfig::default(),
+                Arc::new(ReplayClient::new(fixtures)),
+            ))
+        };
+        let query = BusFactorQuery {
+            basis: ShareBasis::PullRequests,
+            ..BusFactorQuery::default()
+        };
+
+        let share = api(vec![page(1, first.clone()), page(2, vec![Some("b"); 5])])
+            .calculate_repo_share("https://api.github.com/repos/o/n/contributors", &query)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(share.leader.user_name, "a");
+        assert_approx_eq!(share.leader.bus_factor, 70.0 / 104.0);
+        assert_eq!(share.considered, 2);
+        assert!(!share.approximate);
+
+        // Second page is not there, it must not be asked for
+        let query = BusFactorQuery {
+            max_contributor_pages: 1,
+            ..query
+        };
+        let share = api(vec![page(1, first)])
+            .calculate_repo_share("https://api.github.com/repos/o/n/contributors", &query)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_approx_eq!(share.leader.bus_factor, 70.0 / 99.0);
+        assert!(share.approximate);
+    }
+
+    #[test]
+    fn query_contains_filters() {
+        let query = GithubApi::build_query(&RepoQuery {
+            language: Some("rust"),
+            min_stars: Some(1000),
+            topic: Some("cli"),
+            ..RepoQuery::default()
+        })
+        .unwrap();
+        assert_eq!(
+            query,
+            "?q=language:rust+stars:>=1000+topic:cli&sort=stars&order=desc"
+        );
+
+        let query = GithubApi::build_query(&RepoQuery {
+            language: Some("rust"),
+            max_stars: Some(500),
+            sort: RepoSort::BestMatch,
+            ..RepoQuery::default()
+        })
+        .unwrap();
+        assert_eq!(query, "?q=language:rust+stars:<=500");
+
+        let query = GithubApi::build_query(&RepoQuery {
+            language: Some("rust"),
+            min_stars: Some(100),
+            max_stars: Some(500),
+            sort: RepoSort::BestMatch,
+            ..RepoQuery::default()
+        })
+        .unwrap();
+        assert_eq!(query, "?q=language:rust+stars:100..500");
+
+        let query = GithubApi::build_query(&RepoQuery {
+            language: Some("rust"),
+            sort: RepoSort::HelpWantedIssues,
+            ..RepoQuery::default()
+        })
+        .unwrap();
+        assert_eq!(query, "?q=language:rust&sort=help-wanted-issues&order=desc");
+    }
+
+    #[test]
+    fn empty_stars_range_is_rejected() {
+        let res = GithubApi::build_query(&RepoQuery {
+            language: Some("rust"),
+            min_stars: Some(500),
+            max_stars: Some(100),
+            ..RepoQuery::default()
+        });
+
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn ties_in_stars_are_ordered_by_id() {
+        let repo = |name: &str, stars: u64, id: u64| RepoData {
+            id,
+            name: name.to_string(),
+            stargazers_count: stars,
+            ..RepoData::default()
+        };
+
+        let mut repos = Repos {
+            items: vec![repo("a", 10, 3), repo("b", 20, 2), repo("c", 10, 1)],
+            ..Repos::default()
+        };
+        GithubApi::sort_by_stars(&mut repos);
+
+        let names: Vec<&str> = repos.items.iter().map(|repo| repo.name.as_str()).collect();
+        assert_eq!(names, vec!["b", "c", "a"]);
+    }
+
+    #[test]
+    fn dedup_keeps_first_occurrence() {
+        let repo = |name: &str| RepoData {
+            contributors_url: format!("https://api.github.com/repos/{}/contributors", name),
+            name: name.to_string(),
+            ..RepoData::default()
+        };
+
+        let mut repos = Repos {
+            items: vec![repo("a"), repo("b"), repo("a"), repo("c"), repo("b")],
+            ..Repos::default()
+        };
+        GithubApi::dedup(&mut repos);
+
+        assert_eq!(repos.items, vec![repo("a"), repo("b"), repo("c")]);
+    }
+
+    #[test]
+    fn repo_sort_from_str() {
+        assert_eq!("stars".parse::<RepoSort>().unwrap(), RepoSort::Stars);
+        assert_eq!(
+            "best-match".parse::<RepoSort>().unwrap(),
+            RepoSort::BestMatch
+        );
+        assert_eq!(
+            "help-wanted-issues".parse::<RepoSort>().unwrap(),
+            RepoSort::HelpWantedIssues
+        );
+        assert!("forks".parse::<RepoSort>().is_err());
     }
 
     #[tokio::test]
+    #[ignore]
     /// Checks if usage and value of the token are valid
-    /// Test requires token to be in root/.token
+    /// Test requires token to be in root/.token, and network access
     async fn can_use_token() {
         let mut filepath = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         filepath.push(".token");
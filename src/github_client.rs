@@ -1,47 +1,1178 @@
-use std::error::Error;
+use std::{
+    cell::Cell,
+    future::Future,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
-use reqwest::header::USER_AGENT;
+use chrono::Utc;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, LINK, RETRY_AFTER, USER_AGENT},
+    StatusCode,
+};
 use serde::de::DeserializeOwned;
+use tokio::time::Instant;
+
+use crate::{
+    api_errors::{
+        AbuseError, AuthError, InvalidQueryError, RateLimitError, ResponseError, SendError,
+    },
+    concurrency::{AdaptiveConcurrency, AdaptiveConfig, RateLimiter},
+    progress::{ProgressEvent, ProgressListener},
+    replay::{Fixture, ReplayClient},
+    retry::{self, RetryPolicy, Throttle},
+    rng::Rng,
+    transport::{HttpTransport, RawResponse, Transport},
+    warnings::{RunWarning, WarningCategory, Warnings},
+};
+
+#[derive(Debug, Clone)]
+/// Everything that characterizes how requests are sent over the wire
+pub struct TransportConfig {
+    /// Proxy url for all requests, if None proxy is taken from the
+    /// environment (HTTP_PROXY, HTTPS_PROXY, ALL_PROXY)
+    pub proxy: Option<String>,
+    /// Time limit for the whole request, None means no limit
+    pub timeout: Option<Duration>,
+    pub user_agent: String,
+    /// Value of X-GitHub-Api-Version header, not sent if None
+    pub api_version: Option<String>,
+    pub retry: RetryPolicy,
+    /// Accept any certificate, meant only for testing behind intercepting proxies
+    pub accept_invalid_certs: bool,
+    /// PEM file with a root certificate trusted besides the system ones, like an internal CA
+    /// of GitHub Enterprise or of a proxy. None leaves only the system roots
+    pub ca_cert: Option<PathBuf>,
+    /// Extra headers added to every request, they take precedence over
+    /// default ones like User-Agent or Authorization
+    pub headers: HeaderMap,
+    /// Directory where every response is saved as a fixture for the ReplayClient
+    pub record_dir: Option<PathBuf>,
+    /// Limits requests in flight, backing off on throttling. None means no limit
+    pub adaptive: Option<AdaptiveConfig>,
+    /// Requests per second, of all tasks using the client together, retries and search
+    /// included. None means no limit
+    pub rate: Option<f64>,
+    /// Seeds jitter of retry delays, so the run can be repeated exactly. None seeds it
+    /// differently each time
+    pub seed: Option<u64>,
+}
+
+impl Default for TransportConfig {
+    fn default() -> Self {
+        Self {
+            proxy: None,
+            timeout: None,
+            user_agent: "bus_factor".to_string(),
+            api_version: None,
+            retry: RetryPolicy::default(),
+            accept_invalid_certs: false,
+            ca_cert: None,
+            headers: HeaderMap::new(),
+            record_dir: None,
+            adaptive: None,
+            rate: None,
+            seed: None,
+        }
+    }
+}
+
+tokio::task_local! {
+    /// Requests sent within count_requests
+    static REQUESTS: Cell<u32>;
+}
+
+/// Runs the future and returns number of requests it sent, retries included
+pub async fn count_requests<F: Future>(future: F) -> (F::Output, u32) {
+    REQUESTS
+        .scope(Cell::new(0), async move {
+            let output = future.await;
+            (output, REQUESTS.with(Cell::get))
+        })
+        .await
+}
+
+/// Parses header given in "name:value" form
+pub fn parse_header(header: &str) -> Result<(HeaderName, HeaderValue), InvalidQueryError> {
+    let (name, value) = header.split_once(':').ok_or_else(|| {
+        InvalidQueryError::new(&format!(
+            "Header '{}' is not in the name:value format",
+            header
+        ))
+    })?;
+
+    let name = HeaderName::from_bytes(name.trim().as_bytes()).map_err(|_| {
+        InvalidQueryError::new(&format!("Header name '{}' is invalid", name.trim()))
+    })?;
+
+    let value = HeaderValue::from_str(value.trim()).map_err(|_| {
+        InvalidQueryError::new(&format!(
+            "Value '{}' of header '{}' is invalid",
+            value.trim(),
+            name
+        ))
+    })?;
+
+    Ok((name, value))
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+/// Totals of all requests sent by the client so far
+pub struct RequestStats {
+    /// Retries included
+    pub requests: u64,
+    pub retries: u64,
+    /// As reported by the last response, None if no response had it
+    pub rate_limit_remaining: Option<u64>,
+}
+
+/// Source of the token, asked before every request, so tokens that expire,
+/// like ones of a GitHub App installation, can be replaced during a long run
+pub trait TokenProvider: Send + Sync {
+    /// Token for the next request, empty one makes requests anonymous
+    fn token(&self) -> String;
+
+    /// Called when the api rejected the token with 401. Returns true if token() can give
+    /// a fresh one, request is then sent once more with it, otherwise it fails right away
+    fn rejected(&self, _token: &str) -> bool {
+        false
+    }
+
+    /// Called when the token used up its rate limit, quota resets after reset_in.
+    /// Returns true if token() has another one to use meanwhile, request is then sent
+    /// again right away, otherwise the client waits for the reset
+    fn exhausted(&self, _token: &str, _reset_in: Duration) -> bool {
+        false
+    }
+}
+
+/// The same token for the whole run
+pub struct StaticToken(pub String);
+
+impl TokenProvider for StaticToken {
+    fn token(&self) -> String {
+        self.0.clone()
+    }
+}
+
+/// Token is skipped at least that long, even if its quota seems to reset already,
+/// like when the local clock is ahead of github's
+const MIN_EXHAUSTED: Duration = Duration::from_secs(60);
+
+/// Several tokens taken in turns, so the rate limit of all of them adds up.
+/// Token that used up its quota is skipped until it resets, if all did,
+/// the one that resets first is given
+pub struct TokenPool {
+    tokens: Vec<String>,
+    state: Mutex<PoolState>,
+}
+
+struct PoolState {
+    /// Index of the token to try first next time
+    next: usize,
+    /// When the quota of each token resets, None if it's not used up
+    exhausted_until: Vec<Option<Instant>>,
+}
+
+impl TokenPool {
+    /// Fails if there are no tokens
+    pub fn new(tokens: Vec<String>) -> Result<Self, InvalidQueryError> {
+        if tokens.is_empty() {
+            return Err(InvalidQueryError::new("Pool needs at least one token"));
+        }
+
+        Ok(Self {
+            state: Mutex::new(PoolState {
+                next: 0,
+                exhausted_until: vec![None; tokens.len()],
+            }),
+            tokens,
+        })
+    }
+}
+
+impl PoolState {
+    fn is_available(&self, index: usize, now: Instant) -> bool {
+        self.exhausted_until[index].is_none_or(|until| until <= now)
+    }
+}
+
+impl TokenProvider for TokenPool {
+    fn token(&self) -> String {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let count = self.tokens.len();
+
+        let index = (0..count)
+            .map(|offset| (state.next + offset) % count)
+            .find(|&index| state.is_available(index, now))
+            .unwrap_or_else(|| {
+                (0..count)
+                    .min_by_key(|&index| state.exhausted_until[index])
+                    .unwrap_or(0)
+            });
+        state.next = (index + 1) % count;
+
+        self.tokens[index].clone()
+    }
+
+    /// Next token is another one, if there is more than one
+    fn rejected(&self, _token: &str) -> bool {
+        self.tokens.len() > 1
+    }
+
+    fn exhausted(&self, token: &str, reset_in: Duration) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+
+        let until = now + reset_in.max(MIN_EXHAUSTED);
+        for (exhausted_until, _) in state
+            .exhausted_until
+            .iter_mut()
+            .zip(&self.tokens)
+            .filter(|(_, t)| *t == token)
+        {
+            *exhausted_until = Some(until);
+        }
+
+        (0..self.tokens.len())
+            .any(|index| self.tokens[index] != token && state.is_available(index, now))
+    }
+}
+
+impl<F> TokenProvider for F
+where
+    F: Fn() -> String + Send + Sync,
+{
+    fn token(&self) -> String {
+        self()
+    }
+
+    /// Function is asked again, it may give a fresh token
+    fn rejected(&self, _token: &str) -> bool {
+        true
+    }
+}
 
-use crate::api_errors::ResponseError;
 /// Entity that takes care on transport layer
 pub struct GithubClient {
-    inner: reqwest::Client,
-    token: String,
+    transport: Arc<dyn Transport>,
+    token: Arc<dyn TokenProvider>,
+    user_agent: String,
+    api_version: Option<String>,
+    retry: RetryPolicy,
+    headers: HeaderMap,
+    record_dir: Option<PathBuf>,
+    concurrency: Option<AdaptiveConcurrency>,
+    rate: Option<RateLimiter>,
+    stats: Mutex<RequestStats>,
+    warnings: Warnings,
+    progress: Option<Arc<dyn ProgressListener>>,
+    /// Jitter of retries is derived from it, see backoff
+    seed: u64,
 }
 
 impl GithubClient {
     pub fn new(token: &str) -> Self {
+        Self::with_config(token, &TransportConfig::default())
+            .expect("Default transport configuration is always valid")
+    }
+
+    /// Creates client that behaves as described by the config.
+    /// Fails if config contains invalid values, like malformed proxy url
+    pub fn with_config(token: &str, config: &TransportConfig) -> Result<Self, SendError> {
+        Self::check(config)?;
+        let transport = HttpTransport::new(config)?;
+
+        Ok(Self::with_transport(token, config, Arc::new(transport)))
+    }
+
+    /// Creates client that never goes to the network, every response is taken from those
+    /// recorded to dir before, see TransportConfig::record_dir. Request that was not
+    /// recorded fails with MissingFixtureError, naming the endpoint
+    pub fn offline(dir: &Path, config: &TransportConfig) -> Result<Self, SendError> {
+        Self::check(config)?;
+        let replay = ReplayClient::from_dir(dir)
+            .map_err(|e| format!("Can't read responses recorded in {}: {}", dir.display(), e))?;
+        // Responses are recorded already, token is not sent anywhere
+        let config = TransportConfig {
+            record_dir: None,
+            ..config.clone()
+        };
+
+        Ok(Self::with_transport("", &config, Arc::new(replay)))
+    }
+
+    /// Creates client that replays responses recorded to dir before, and requests only
+    /// those that were not recorded, like with_config does. Responses are recorded
+    /// to record_dir as usual, replayed ones are saved again unchanged
+    pub fn replaying(dir: &Path, token: &str, config: &TransportConfig) -> Result<Self, SendError> {
+        let mut client = Self::with_config(token, config)?;
+        // Nothing recorded yet, everything is requested
+        let replay = match dir.exists() {
+            true => ReplayClient::from_dir(dir).map_err(|e| {
+                format!("Can't read responses recorded in {}: {}", dir.display(), e)
+            })?,
+            false => ReplayClient::new(vec![]),
+        };
+        client.transport = Arc::new(replay.with_fallback(client.transport));
+
+        Ok(client)
+    }
+
+    /// Fails if values of the config that are not up to the transport are invalid
+    fn check(config: &TransportConfig) -> Result<(), SendError> {
+        config.rate.map(RateLimiter::new).transpose()?;
+        Ok(())
+    }
+
+    /// Creates client that gets responses from given transport, parts of the config
+    /// related to the connection itself (proxy, timeout, certs) are up to the transport.
+    /// Panics if the rate is not positive, with_config and offline check it
+    pub fn with_transport(
+        token: &str,
+        config: &TransportConfig,
+        transport: Arc<dyn Transport>,
+    ) -> Self {
         Self {
-            inner: reqwest::Client::new(),
-            token: token.to_string(),
+            transport,
+            token: Arc::new(StaticToken(token.to_string())),
+            user_agent: config.user_agent.clone(),
+            api_version: config.api_version.clone(),
+            retry: config.retry.clone(),
+            headers: config.headers.clone(),
+            record_dir: config.record_dir.clone(),
+            concurrency: config.adaptive.as_ref().map(AdaptiveConcurrency::new),
+            rate: config
+                .rate
+                .map(|rate| RateLimiter::new(rate).expect("Rate is checked by the caller")),
+            stats: Mutex::default(),
+            warnings: Warnings::default(),
+            progress: None,
+            seed: Rng::new(config.seed).next_u64(),
         }
     }
+
+    /// Replaces the token given on creation with the provider
+    pub fn with_token_provider(mut self, provider: Arc<dyn TokenProvider>) -> Self {
+        self.token = provider;
+        self
+    }
+
+    /// Sends progress events to the listener, of this client and of the api using it
+    pub fn with_progress(mut self, listener: Arc<dyn ProgressListener>) -> Self {
+        self.progress = Some(listener);
+        self
+    }
+
+    /// Passes the event to the listener, if there is one
+    pub fn progress(&self, event: ProgressEvent) {
+        if let Some(listener) = &self.progress {
+            listener.on_event(&event);
+        }
+    }
+
+    /// Controller of requests in flight, if adaptive concurrency is enabled
+    pub fn concurrency(&self) -> Option<&AdaptiveConcurrency> {
+        self.concurrency.as_ref()
+    }
+
+    /// Requests sent so far, by all tasks using the client
+    pub fn request_stats(&self) -> RequestStats {
+        *self.stats.lock().unwrap()
+    }
+
+    /// Delay before the retry of the policy, jittered by the seed of the client, of what
+    /// is retried, like the endpoint, and of the attempt. Not by the order of retries,
+    /// so with the same seed it's the same, however tasks are scheduled
+    pub fn backoff(&self, policy: &RetryPolicy, key: &str, attempt: u32) -> Duration {
+        policy.jittered_delay(attempt, &mut Rng::derived(self.seed, key, attempt as u64))
+    }
+
+    /// Logs the warning, and keeps it for take_warnings
+    pub fn warn(&self, category: WarningCategory, message: String) {
+        self.warnings.raise(category, message);
+    }
+
+    /// Warnings raised since the last call, by all tasks using the client
+    pub fn take_warnings(&self) -> Vec<RunWarning> {
+        self.warnings.take()
+    }
+
+    fn count_retry(&self) {
+        self.stats.lock().unwrap().retries += 1;
+    }
+
+    /// Headers sent with every request
+    fn request_headers(&self, token: &str) -> Result<HeaderMap, SendError> {
+        let mut headers = HeaderMap::new();
+        headers.insert(USER_AGENT, HeaderValue::from_str(&self.user_agent)?);
+
+        // Token file usually ends with a new line, without token requests are anonymous
+        if !token.trim().is_empty() {
+            let mut auth = HeaderValue::from_str(&format!("Bearer {}", token.trim()))?;
+            auth.set_sensitive(true);
+            headers.insert(AUTHORIZATION, auth);
+        }
+
+        if let Some(version) = &self.api_version {
+            headers.insert("X-GitHub-Api-Version", HeaderValue::from_str(version)?);
+        }
+
+        // Replaces defaults set above, only if user did set them explicitly
+        headers.extend(self.headers.clone());
+
+        Ok(headers)
+    }
+
     /// Sends a requests to given endpoint and returns a response body.
     /// Returns ResponseError if query was invalid
-    pub async fn get_response_body<T>(&self, endpoint: &str) -> Result<T, Box<dyn Error>>
+    pub async fn get_response_body<T>(&self, endpoint: &str) -> Result<T, SendError>
     where
         T: DeserializeOwned,
     {
-        const USER_AGENT_NAME: &str = "bus_factor";
+        let res = self.get_response(endpoint).await?;
+
+        let body: T = serde_json::from_str(&res.body)?;
+        Ok(body)
+    }
+
+    /// Returns number of items behind the list endpoint, using one request:
+    /// with one item per page, number of the last page is the number of items
+    pub async fn get_total_count(&self, endpoint: &str) -> Result<u64, SendError> {
+        let separator = if endpoint.contains('?') { '&' } else { '?' };
+        let endpoint = format!("{}{}per_page=1", endpoint, separator);
 
-        let res = self
-            .inner
-            .get(endpoint)
-            .header(USER_AGENT, USER_AGENT_NAME)
-            .bearer_auth(&self.token)
-            .send()
-            .await?;
+        let res = self.get_response(&endpoint).await?;
 
-        // If status code is 4xx, 5xx
-        if res.error_for_status_ref().is_err() {
-            // Api response contains useful information about the problem
-            let body = res.text().await?;
-            Err(Box::new(ResponseError::new(&body)))
-        } else {
-            let body: T = res.json().await?;
-            Ok(body)
+        match res.headers.get(LINK).and_then(|link| link.to_str().ok()) {
+            Some(link) => parse_last_page(link)
+                .ok_or_else(|| format!("No last page in Link header '{}'", link).into()),
+            // Everything fits on the single page
+            None => {
+                let items: Vec<serde_json::Value> = serde_json::from_str(&res.body)?;
+                Ok(items.len() as u64)
+            }
+        }
+    }
+
+    /// Sends a requests to given endpoint, retrying as configured, and returns
+    /// successful response. Returns ResponseError if query was invalid
+    pub async fn get_response(&self, endpoint: &str) -> Result<RawResponse, SendError> {
+        let mut attempt = 0;
+        // Token is refreshed once per request, rejected fresh one is an error
+        let mut refreshed = false;
+        // Tokens given up on for the rate limit, the request goes to each at most once
+        let mut switched_from: Vec<String> = vec![];
+
+        loop {
+            // Not counted outside of count_requests
+            let _ = REQUESTS.try_with(|count| count.set(count.get() + 1));
+            self.stats.lock().unwrap().requests += 1;
+
+            // Taken before the slot, waiting for the turn does not hold one
+            if let Some(rate) = &self.rate {
+                rate.acquire().await;
+            }
+            let permit = match &self.concurrency {
+                Some(concurrency) => Some(concurrency.acquire().await),
+                None => None,
+            };
+            let token = self.token.token();
+            let headers = self.request_headers(&token)?;
+            // Error is not Send, it must not live across the sleep below, so it's dropped within the block
+            let res = {
+                let res = self.transport.get(endpoint, headers).await;
+                // Slot is not held while waiting for retry
+                drop(permit);
+
+                match res {
+                    Ok(res) => Ok(res),
+                    Err(e)
+                        if retry::is_transient_error(e.as_ref())
+                            && attempt < self.retry.max_retries =>
+                    {
+                        let delay = self.backoff(&self.retry, endpoint, attempt);
+                        self.warn(
+                            WarningCategory::Retry,
+                            format!(
+                                "Request to {} failed with {}, retrying in {:?}",
+                                endpoint, e, delay
+                            ),
+                        );
+                        Err(delay)
+                    }
+                    Err(e) => return Err(e),
+                }
+            };
+
+            let res = match res {
+                Ok(res) => res,
+                Err(delay) => {
+                    attempt += 1;
+                    self.count_retry();
+                    tokio::time::sleep(delay).await;
+                    continue;
+                }
+            };
+
+            if let Some(remaining) = res
+                .headers
+                .get("x-ratelimit-remaining")
+                .and_then(|value| value.to_str().ok()?.trim().parse().ok())
+            {
+                self.stats.lock().unwrap().rate_limit_remaining = Some(remaining);
+                // Next requests go to another token, if there is one, before this one is throttled
+                if remaining == 0 && res.status.is_success() {
+                    if let Some(reset_in) = retry::rate_limit_reset(&res.headers, Utc::now()) {
+                        self.token.exhausted(&token, reset_in);
+                    }
+                }
+            }
+
+            if let Some(dir) = &self.record_dir {
+                Fixture::from_response(endpoint, &res, &token).save(dir)?;
+            }
+
+            if res.status == StatusCode::UNAUTHORIZED && !refreshed && self.token.rejected(&token) {
+                self.warn(
+                    WarningCategory::Retry,
+                    format!(
+                        "Token was rejected for {}, retrying with a fresh one",
+                        endpoint
+                    ),
+                );
+                refreshed = true;
+                self.count_retry();
+                continue;
+            }
+            if res.status == StatusCode::UNAUTHORIZED {
+                return Err(Box::new(AuthError::new(&format!(
+                    "Token was rejected for {}, check that it's valid and not expired: {}",
+                    endpoint,
+                    res.body.trim()
+                ))));
+            }
+
+            if let Some(throttle) = retry::classify_throttle(res.status, &res.headers, Utc::now()) {
+                let wait = throttle.wait();
+                if let Some(concurrency) = &self.concurrency {
+                    concurrency.on_throttle();
+                }
+
+                let message = match throttle {
+                    Throttle::RateLimit { .. } => format!(
+                        "Rate limit exceeded for {}, quota resets in {:?}",
+                        endpoint, wait
+                    ),
+                    Throttle::Abuse { .. } => format!(
+                        "Secondary rate limit hit for {}, backing off for {:?}",
+                        endpoint, wait
+                    ),
+                };
+                // Another token has its own quota, it's used without waiting
+                if matches!(throttle, Throttle::RateLimit { .. })
+                    && self.token.exhausted(&token, wait)
+                    && !switched_from.contains(&token)
+                {
+                    info!("{}, switching to another token", message);
+                    switched_from.push(token);
+                    self.count_retry();
+                    continue;
+                }
+
+                self.warn(WarningCategory::RateLimit, message);
+                self.progress(ProgressEvent::RateLimited {
+                    endpoint: endpoint.to_string(),
+                    secondary: matches!(throttle, Throttle::Abuse { .. }),
+                    wait_ms: wait.as_millis() as u64,
+                });
+
+                if attempt < self.retry.max_retries {
+                    attempt += 1;
+                    self.count_retry();
+                    tokio::time::sleep(wait).await;
+                    continue;
+                }
+
+                return Err(match throttle {
+                    Throttle::RateLimit { .. } => Box::new(RateLimitError::new(&res.body, wait)),
+                    Throttle::Abuse { .. } => Box::new(AbuseError::new(&res.body, wait)),
+                });
+            }
+
+            // If status code is 4xx, 5xx
+            if res.status.is_client_error() || res.status.is_server_error() {
+                if RetryPolicy::is_retryable(res.status) && attempt < self.retry.max_retries {
+                    // Server knows best how long to wait
+                    let delay = match res.headers.get(RETRY_AFTER) {
+                        Some(value) => {
+                            retry::parse_retry_after(value.to_str().unwrap_or_default(), Utc::now())
+                        }
+                        None => self.backoff(&self.retry, endpoint, attempt),
+                    };
+                    self.warn(
+                        WarningCategory::Retry,
+                        format!(
+                            "Request to {} failed with {}, retrying in {:?}",
+                            endpoint, res.status, delay
+                        ),
+                    );
+
+                    attempt += 1;
+                    self.count_retry();
+                    tokio::time::sleep(delay).await;
+                    continue;
+                }
+
+                // Api response contains useful information about the problem
+                return Err(Box::new(ResponseError::with_status(res.status, &res.body)));
+            }
+
+            if let Some(concurrency) = &self.concurrency {
+                concurrency.on_success();
+            }
+
+            return Ok(res);
         }
     }
 }
 
+/// Extracts number of the last page from the Link header, like:
+/// <https://api.github.com/repositories/1/commits?per_page=1&page=2>; rel="next",
+/// <https://api.github.com/repositories/1/commits?per_page=1&page=1234>; rel="last"
+pub fn parse_last_page(link: &str) -> Option<u64> {
+    let last = link
+        .split(',')
+        .find(|part| part.contains(r#"rel="last""#))?;
+    let url = last
+        .split(';')
+        .next()?
+        .trim()
+        .trim_start_matches('<')
+        .trim_end_matches('>');
+
+    url.split(['?', '&'])
+        .find_map(|param| param.strip_prefix("page="))
+        .and_then(|page| page.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use futures::future::BoxFuture;
+
+    use super::*;
+
+    /// Throttles requests when more than threshold of them are in flight
+    struct ThresholdTransport {
+        in_flight: AtomicUsize,
+        threshold: usize,
+    }
+
+    impl Transport for ThresholdTransport {
+        fn get<'a>(
+            &'a self,
+            _endpoint: &'a str,
+            _headers: HeaderMap,
+        ) -> BoxFuture<'a, Result<RawResponse, SendError>> {
+            Box::pin(async move {
+                let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                self.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+                let mut headers = HeaderMap::new();
+                let status = if in_flight > self.threshold {
+                    headers.insert(RETRY_AFTER, HeaderValue::from_static("0"));
+                    StatusCode::FORBIDDEN
+                } else {
+                    StatusCode::OK
+                };
+
+                Ok(RawResponse {
+                    status,
+                    headers,
+                    body: "[]".to_string(),
+                })
+            })
+        }
+    }
+
+    /// Fails with given error until it runs out of failures
+    struct FlakyTransport {
+        failures: AtomicUsize,
+        kind: std::io::ErrorKind,
+        message: &'static str,
+    }
+
+    impl Transport for FlakyTransport {
+        fn get<'a>(
+            &'a self,
+            _endpoint: &'a str,
+            _headers: HeaderMap,
+        ) -> BoxFuture<'a, Result<RawResponse, SendError>> {
+            Box::pin(async move {
+                let failed = self
+                    .failures
+                    .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| {
+                        left.checked_sub(1)
+                    })
+                    .is_ok();
+
+                if failed {
+                    return Err(Box::new(std::io::Error::new(self.kind, self.message)) as SendError);
+                }
+
+                Ok(RawResponse {
+                    status: StatusCode::OK,
+                    headers: HeaderMap::new(),
+                    body: "[]".to_string(),
+                })
+            })
+        }
+    }
+
+    /// Accepts only the given token, answers 401 to any other
+    struct AuthTransport {
+        accepted: &'static str,
+    }
+
+    impl Transport for AuthTransport {
+        fn get<'a>(
+            &'a self,
+            _endpoint: &'a str,
+            headers: HeaderMap,
+        ) -> BoxFuture<'a, Result<RawResponse, SendError>> {
+            Box::pin(async move {
+                let accepted = headers
+                    .get(AUTHORIZATION)
+                    .and_then(|auth| auth.to_str().ok())
+                    == Some(&format!("Bearer {}", self.accepted));
+
+                Ok(RawResponse {
+                    status: if accepted {
+                        StatusCode::OK
+                    } else {
+                        StatusCode::UNAUTHORIZED
+                    },
+                    headers: HeaderMap::new(),
+                    body: r#"{"message": "Bad credentials"}"#.to_string(),
+                })
+            })
+        }
+    }
+
+    /// Gives the old token until it's rejected, then the new one
+    struct RotatingToken {
+        rotated: Mutex<bool>,
+    }
+
+    impl TokenProvider for RotatingToken {
+        fn token(&self) -> String {
+            let token = if *self.rotated.lock().unwrap() {
+                "new"
+            } else {
+                "old"
+            };
+            token.to_string()
+        }
+
+        fn rejected(&self, token: &str) -> bool {
+            assert_eq!(token, "old");
+            *self.rotated.lock().unwrap() = true;
+            true
+        }
+    }
+
+    #[tokio::test]
+    async fn token_is_rotated_after_401() {
+        let client = GithubClient::with_transport(
+            "",
+            &TransportConfig::default(),
+            Arc::new(AuthTransport { accepted: "new" }),
+        )
+        .with_token_provider(Arc::new(RotatingToken {
+            rotated: Mutex::new(false),
+        }));
+
+        let (res, requests) = count_requests(client.get_response("endpoint")).await;
+        assert_eq!(res.unwrap().status, StatusCode::OK);
+        assert_eq!(requests, 2);
+        assert_eq!(client.request_stats().retries, 1);
+
+        // Fresh token that is rejected as well is not retried again
+        let client = GithubClient::with_transport(
+            "",
+            &TransportConfig::default(),
+            Arc::new(AuthTransport { accepted: "new" }),
+        )
+        .with_token_provider(Arc::new(|| "expired".to_string()));
+
+        let (res, requests) = count_requests(client.get_response("endpoint")).await;
+        let err = res.unwrap_err();
+        assert!(err.to_string().contains("Bad credentials"), "{}", err);
+        assert_eq!(requests, 2);
+    }
+
+    #[tokio::test]
+    async fn rejected_token_is_auth_error() {
+        let client = GithubClient::with_transport(
+            "invalid",
+            &TransportConfig::default(),
+            Arc::new(AuthTransport { accepted: "valid" }),
+        );
+
+        let (res, requests) = count_requests(client.get_response("endpoint")).await;
+        let err = res.unwrap_err();
+        assert!(err.is::<AuthError>(), "{}", err);
+        assert!(err.to_string().contains("check that it's valid"), "{}", err);
+
+        // Static token can't be refreshed, it's not retried
+        assert_eq!(requests, 1);
+        assert_eq!(client.request_stats().retries, 0);
+        assert!(client.take_warnings().is_empty());
+    }
+
+    #[test]
+    fn backoff_does_not_depend_on_order_of_retries() {
+        let config = TransportConfig {
+            seed: Some(7),
+            ..TransportConfig::default()
+        };
+        let policy = RetryPolicy {
+            jitter: 0.5,
+            ..RetryPolicy::default()
+        };
+        let client =
+            || GithubClient::with_transport("", &config, Arc::new(ReplayClient::new(vec![])));
+
+        let first = client();
+        let a = first.backoff(&policy, "a", 1);
+        let b = first.backoff(&policy, "b", 1);
+
+        // Other tasks may retry first
+        let second = client();
+        assert_eq!(second.backoff(&policy, "b", 1), b);
+        assert_eq!(second.backoff(&policy, "a", 1), a);
+    }
+
+    #[test]
+    fn pool_takes_tokens_in_turns() {
+        let pool = TokenPool::new(vec!["a".to_string(), "b".to_string(), "c".to_string()]).unwrap();
+        let tokens: Vec<String> = (0..4).map(|_| pool.token()).collect();
+        assert_eq!(tokens, ["a", "b", "c", "a"]);
+
+        // Exhausted one is skipped, also if its reset seems to have passed already
+        assert!(pool.exhausted("b", Duration::ZERO));
+        let tokens: Vec<String> = (0..4).map(|_| pool.token()).collect();
+        assert_eq!(tokens, ["c", "a", "c", "a"]);
+
+        // With all exhausted, the one that resets first is given
+        assert!(pool.exhausted("a", Duration::from_secs(120)));
+        assert!(!pool.exhausted("c", Duration::from_secs(90)));
+        assert_eq!(pool.token(), "b");
+
+        assert!(TokenPool::new(vec![]).is_err());
+    }
+
+    /// Rate limits requests with any of the given tokens, until reset_in seconds from now,
+    /// answers any other, remembers tokens of all requests
+    struct LimitedTokenTransport {
+        limited: &'static [&'static str],
+        reset_in: i64,
+        seen: Mutex<Vec<String>>,
+    }
+
+    impl Transport for LimitedTokenTransport {
+        fn get<'a>(
+            &'a self,
+            _endpoint: &'a str,
+            headers: HeaderMap,
+        ) -> BoxFuture<'a, Result<RawResponse, SendError>> {
+            Box::pin(async move {
+                let auth = headers
+                    .get(AUTHORIZATION)
+                    .unwrap()
+                    .to_str()
+                    .unwrap()
+                    .to_string();
+                let limited = self
+                    .limited
+                    .iter()
+                    .any(|token| auth == format!("Bearer {}", token));
+                self.seen.lock().unwrap().push(auth);
+
+                let mut headers = HeaderMap::new();
+                let status = if limited {
+                    headers.insert("x-ratelimit-remaining", HeaderValue::from_static("0"));
+                    let reset = (Utc::now().timestamp() + self.reset_in).to_string();
+                    headers.insert("x-ratelimit-reset", HeaderValue::from_str(&reset).unwrap());
+                    StatusCode::FORBIDDEN
+                } else {
+                    StatusCode::OK
+                };
+
+                Ok(RawResponse {
+                    status,
+                    headers,
+                    body: "[]".to_string(),
+                })
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn rate_limited_token_is_switched() {
+        let transport = Arc::new(LimitedTokenTransport {
+            limited: &["a"],
+            reset_in: 3600,
+            seen: Mutex::default(),
+        });
+        let pool = TokenPool::new(vec!["a".to_string(), "b".to_string()]).unwrap();
+        let client =
+            GithubClient::with_transport("", &TransportConfig::default(), transport.clone())
+                .with_token_provider(Arc::new(pool));
+
+        // Quota resets in an hour, waiting for it would time out the test
+        for _ in 0..2 {
+            let res = tokio::time::timeout(Duration::from_secs(5), client.get_response("endpoint"))
+                .await
+                .unwrap();
+            assert_eq!(res.unwrap().status, StatusCode::OK);
+        }
+
+        assert_eq!(
+            *transport.seen.lock().unwrap(),
+            ["Bearer a", "Bearer b", "Bearer b"]
+        );
+        assert!(client.take_warnings().is_empty());
+    }
+
+    #[tokio::test]
+    async fn switching_tokens_is_bounded() {
+        // Quota seems to reset already, like with the local clock ahead of github's
+        let transport = Arc::new(LimitedTokenTransport {
+            limited: &["a", "b"],
+            reset_in: -10,
+            seen: Mutex::default(),
+        });
+        let config = TransportConfig {
+            retry: RetryPolicy {
+                max_retries: 1,
+                ..RetryPolicy::default()
+            },
+            ..TransportConfig::default()
+        };
+        let pool = TokenPool::new(vec!["a".to_string(), "b".to_string()]).unwrap();
+        let client = GithubClient::with_transport("", &config, transport.clone())
+            .with_token_provider(Arc::new(pool));
+
+        let err = tokio::time::timeout(Duration::from_secs(5), client.get_response("endpoint"))
+            .await
+            .unwrap()
+            .unwrap_err();
+        assert!(err.is::<RateLimitError>(), "{}", err);
+
+        // Each token once, then the only retry there is
+        assert_eq!(
+            *transport.seen.lock().unwrap(),
+            ["Bearer a", "Bearer b", "Bearer a"]
+        );
+    }
+
+    fn flaky_client(kind: std::io::ErrorKind, message: &'static str) -> GithubClient {
+        let config = TransportConfig {
+            retry: RetryPolicy {
+                max_retries: 3,
+                backoff: Duration::from_millis(1),
+                ..RetryPolicy::default()
+            },
+            ..TransportConfig::default()
+        };
+
+        GithubClient::with_transport(
+            "",
+            &config,
+            Arc::new(FlakyTransport {
+                failures: AtomicUsize::new(2),
+                kind,
+                message,
+            }),
+        )
+    }
+
+    #[tokio::test]
+    async fn connection_reset_is_retried() {
+        let client = flaky_client(
+            std::io::ErrorKind::ConnectionReset,
+            "connection reset by peer",
+        );
+
+        let (res, requests) = count_requests(client.get_response("endpoint")).await;
+        assert!(res.is_ok());
+        assert_eq!(requests, 3);
+
+        let stats = client.request_stats();
+        assert_eq!((stats.requests, stats.retries), (3, 2));
+        assert_eq!(stats.rate_limit_remaining, None);
+    }
+
+    #[tokio::test]
+    async fn dns_failure_is_not_retried() {
+        let client = flaky_client(
+            std::io::ErrorKind::Other,
+            "dns error: failed to lookup address",
+        );
+
+        let err = client.get_response("endpoint").await.unwrap_err();
+        assert!(err.is::<std::io::Error>());
+    }
+
+    /// Answers right away, remembering when each request came
+    struct TimedTransport {
+        sent: Mutex<Vec<tokio::time::Instant>>,
+    }
+
+    impl Transport for TimedTransport {
+        fn get<'a>(
+            &'a self,
+            _endpoint: &'a str,
+            _headers: HeaderMap,
+        ) -> BoxFuture<'a, Result<RawResponse, SendError>> {
+            self.sent.lock().unwrap().push(tokio::time::Instant::now());
+
+            Box::pin(async move {
+                Ok(RawResponse {
+                    status: StatusCode::OK,
+                    headers: HeaderMap::new(),
+                    body: "[]".to_string(),
+                })
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn rate_is_kept_by_all_workers_together() {
+        let transport = Arc::new(TimedTransport {
+            sent: Mutex::default(),
+        });
+        let config = TransportConfig {
+            rate: Some(100.0),
+            ..TransportConfig::default()
+        };
+        let client = Arc::new(GithubClient::with_transport("", &config, transport.clone()));
+
+        let start = tokio::time::Instant::now();
+        let workers: Vec<_> = (0..5)
+            .map(|_| {
+                let client = client.clone();
+                tokio::spawn(async move {
+                    for _ in 0..4 {
+                        client.get_response("endpoint").await.unwrap();
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.await.unwrap();
+        }
+
+        let mut sent = transport.sent.lock().unwrap().clone();
+        sent.sort();
+        assert_eq!(sent.len(), 20);
+        // Turns are 10ms apart, they never come early
+        for (nth, at) in sent.iter().enumerate() {
+            assert!(
+                *at - start >= Duration::from_millis(10) * nth as u32,
+                "request {} after {:?}",
+                nth,
+                *at - start
+            );
+        }
+    }
+
+    #[test]
+    fn rate_must_be_positive() {
+        for rate in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            let config = TransportConfig {
+                rate: Some(rate),
+                ..TransportConfig::default()
+            };
+            assert!(GithubClient::with_config("", &config).is_err(), "{}", rate);
+            // Checked before recorded responses are read
+            assert!(
+                GithubClient::offline(Path::new(""), &config).is_err(),
+                "{}",
+                rate
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn concurrency_backs_off_and_ramps_up() {
+        let config = TransportConfig {
+            retry: RetryPolicy {
+                max_retries: 10,
+                ..RetryPolicy::default()
+            },
+            adaptive: Some(AdaptiveConfig {
+                initial: 8,
+                min: 1,
+                max: 8,
+                increase: 0.25,
+                decrease: 0.5,
+            }),
+            ..TransportConfig::default()
+        };
+        let client = GithubClient::with_transport(
+            "",
+            &config,
+            Arc::new(ThresholdTransport {
+                in_flight: AtomicUsize::new(0),
+                threshold: 3,
+            }),
+        );
+
+        // Too many at once, server throttles, limit goes down, all get through eventually
+        let burst = (0..8).map(|_| client.get_response("endpoint"));
+        for res in futures::future::join_all(burst).await {
+            assert!(res.is_ok());
+        }
+        let lowered = client.concurrency().unwrap().limit();
+        assert!(lowered < 3, "limit is {}", lowered);
+
+        // Without throttling limit grows back
+        for _ in 0..8 {
+            client.get_response("endpoint").await.unwrap();
+        }
+        assert!(client.concurrency().unwrap().limit() > lowered);
+    }
+
+    #[test]
+    fn last_page_is_parsed_from_link() {
+        let link = r#"<https://api.github.com/repositories/1/commits?per_page=1&page=2>; rel="next", <https://api.github.com/repositories/1/commits?per_page=1&page=1234>; rel="last""#;
+        assert_eq!(parse_last_page(link), Some(1234));
+
+        // On the last page there is no last relation
+        let link =
+            r#"<https://api.github.com/repositories/1/commits?per_page=1&page=1>; rel="first""#;
+        assert_eq!(parse_last_page(link), None);
+    }
+
+    #[test]
+    fn header_is_parsed() {
+        let (name, value) = parse_header("X-Request-ID: abc:123").unwrap();
+
+        assert_eq!(name, "x-request-id");
+        assert_eq!(value, "abc:123");
+    }
+
+    #[test]
+    fn malformed_header_is_rejected() {
+        assert!(parse_header("X-Request-ID").is_err());
+        assert!(parse_header("X Request ID: abc").is_err());
+        assert!(parse_header("X-Request-ID: abc\ndef").is_err());
+    }
+}
@@ -7,7 +7,7 @@
 //! - Visible only that data we want
 //! - Open Close principle shines by it's brightest (Wants to extend? Simply add that field)
 //! - Whole parsing and validation is done in one place:
-//! ```
+//! ```ignore
 //! // If succeeds, we know all items are valid, can reach elements without fear
 //! let contributions: Contributions = serde_json::from_str(&body)?;
 //! let leader = contributions[0];
@@ -15,7 +15,7 @@
 //!
 //! ```
 //! Instead of:
-//! ```
+//! ```ignore
 //! // Check every single field, every single time
 //! let biggest_contribution = leader["contributions"]
 //!    .as_u64()
@@ -26,7 +26,37 @@
 //!    .ok_or("Failed to retrieve login field")?;
 //! ```
 //!
-use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// Null is read as if the field was missing
+fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+/// RFC 3339 timestamp, as github gives them, like "2021-12-24T10:15:30Z".
+/// Null, or one that does not parse, is None, bad timestamp is not worth failing the page
+fn timestamp_or_none<'de, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let timestamp = match Option::<String>::deserialize(deserializer)? {
+        Some(timestamp) => timestamp,
+        None => return Ok(None),
+    };
+
+    match DateTime::parse_from_rfc3339(&timestamp) {
+        Ok(parsed) => Ok(Some(parsed.with_timezone(&Utc))),
+        Err(e) => {
+            warn!("Ignoring timestamp {}: {}", timestamp, e);
+            Ok(None)
+        }
+    }
+}
 
 // Clone, so it's possible to add RepoData from one container to another
 // Ord, used in test when transforming vec to set
@@ -34,24 +64,178 @@ use serde::{Deserialize, Serialize};
 
 /// RepoData holds information about repository from the query
 pub struct RepoData {
+    /// Stays the same when repo is renamed, 0 if unknown
+    #[serde(default)]
+    pub id: u64,
+    /// Empty if search did not give it, see contributors_endpoint
+    #[serde(default, deserialize_with = "null_as_default")]
     pub contributors_url: String,
+    /// Like "owner/name"
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub full_name: String,
+    /// Page of the repo, missing in older recordings
+    #[serde(default)]
+    pub html_url: String,
     pub name: String,
     pub stargazers_count: u64,
+    /// Main language of the repo, empty if github could not tell it
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub language: String,
+    #[serde(default)]
+    pub topics: Vec<String>,
+    /// User or organization the repo belongs to
+    #[serde(default)]
+    pub owner: OwnerData,
+    /// Repo is a fork of another one, its history is mostly the parent's
+    #[serde(default)]
+    pub fork: bool,
+    /// Issues and pull requests that are open. Api does not tell how many of them are help-wanted,
+    /// search can only be sorted by that
+    #[serde(default)]
+    pub open_issues_count: u64,
+    /// Number of repos in the fork network, present only when the repo is requested directly
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network_count: Option<u64>,
+    /// None if unknown, like in older recordings or for a local clone
+    #[serde(
+        default,
+        deserialize_with = "timestamp_or_none",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub created_at: Option<DateTime<Utc>>,
+    /// Last change of the repo, its settings included, not only of the code
+    #[serde(
+        default,
+        deserialize_with = "timestamp_or_none",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// Keeps data about owner of the repository
+pub struct OwnerData {
+    pub login: String,
 }
+impl RepoData {
+    /// Contributors url, or if it's missing, one relative to the api, made from the full name.
+    /// None if there is neither of them
+    pub fn contributors_endpoint(&self) -> Option<String> {
+        if !self.contributors_url.is_empty() {
+            Some(self.contributors_url.clone())
+        } else if !self.full_name.is_empty() {
+            Some(format!("/repos/{}/contributors", self.full_name))
+        } else {
+            None
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Default)]
 /// Repos holds list of items that are result from
 /// https://api.github.com/search/repositories
 pub struct Repos {
+    /// Set when search timed out on the server side, and items are only partial
+    #[serde(default)]
+    pub incomplete_results: bool,
+    #[serde(default)]
     pub items: Vec<RepoData>,
+    /// Error description, github sometimes sends it instead of the items, even with 200
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Number of all repos matching the search, not only of those on the page.
+    /// Only the first 1000 of them can be fetched
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_count: Option<u64>,
+    /// How many repos the search gave, before those in other language were dropped, see
+    /// RepoQuery::strict_language. Not a part of the response, set by GithubApi::get_repos
+    #[serde(skip)]
+    pub found: usize,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Logins are case-insensitive, but api gives them as the user typed them.
+/// Logins are compared by that key, original casing is kept for display
+pub fn login_key(login: &str) -> String {
+    // Logins are ASCII only
+    login.to_ascii_lowercase()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
 /// Keeps data about contributor
 pub struct ContributorData {
     pub contributions: u64,
     pub login: String,
+    /// Profile of the contributor, not given for anonymous ones, or by local clones
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub html_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avatar_url: Option<String>,
 }
 
 /// This is a list of items from
 /// https://api.github.com/repos/USER/REPO/contributors
 pub type Contributions = Vec<ContributorData>;
+
+#[derive(Serialize, Deserialize, Debug)]
+/// Keeps data about member of organization or team
+pub struct MemberData {
+    pub login: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+/// Issues holds page of results from
+/// https://api.github.com/search/issues, pull requests are issues there
+pub struct Issues {
+    #[serde(default)]
+    pub total_count: u64,
+    #[serde(default)]
+    pub items: Vec<IssueData>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+/// Keeps data about issue or pull request, only its author is of interest
+pub struct IssueData {
+    /// None if the account of the author was deleted
+    #[serde(default)]
+    pub user: Option<OwnerData>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+/// Keeps data about commit, only its author is of interest
+pub struct CommitData {
+    /// None if the commit is not linked to any account, like one with unknown email
+    #[serde(default)]
+    pub author: Option<OwnerData>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+/// Quota of a rate limit
+pub struct RateLimitData {
+    /// Requests per window, an hour for the core one
+    pub limit: u64,
+    pub remaining: u64,
+    /// Epoch seconds of the reset
+    pub reset: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+/// Keeps the core rate limit, used by every endpoint other than search
+pub struct RateResources {
+    pub core: RateLimitData,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+/// This is a response of
+/// https://api.github.com/rate_limit
+pub struct RateLimits {
+    pub resources: RateResources,
+}
+
+/// This is a list of items from
+/// https://api.github.com/repos/USER/REPO/commits
+pub type Commits = Vec<CommitData>;
+
+/// This is a list of items from
+/// https://api.github.com/orgs/ORG/members or
+/// https://api.github.com/orgs/ORG/teams/TEAM/members
+pub type Members = Vec<MemberData>;
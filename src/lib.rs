@@ -0,0 +1,26 @@
+//! Calculates bus factor for github repositories.
+//! Binary in main.rs is a thin command line layer over this library.
+#[macro_use]
+extern crate log;
+
+pub mod api_errors;
+pub mod concurrency;
+pub mod github_api;
+pub mod github_client;
+pub mod github_data;
+pub mod local_git;
+pub mod metrics;
+pub mod profile;
+pub mod progress;
+pub mod replay;
+pub mod report;
+pub mod retry;
+pub mod rng;
+pub mod score;
+pub mod share;
+pub mod snapshot;
+#[cfg(any(test, feature = "testing"))]
+pub mod synthetic;
+pub mod timing;
+pub mod transport;
+pub mod warnings;
@@ -0,0 +1,99 @@
+//! Contributor data taken from a local clone, instead of github API.
+use std::{io, path::Path, process::Command};
+
+use crate::{
+    api_errors::SendError,
+    github_data::{Contributions, ContributorData},
+};
+
+/// Counts commits of each author reachable from HEAD of the clone, with git shortlog.
+/// Authors are identified by name and email, as git knows them, most active first
+pub fn contributions(repo: &Path) -> Result<Contributions, SendError> {
+    // Without a revision shortlog reads the log from stdin
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(["shortlog", "-sne", "HEAD"])
+        .output()
+        .map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("Can't run git for {}: {}", repo.display(), e),
+            )
+        })?;
+
+    if !output.status.success() {
+        return Err(Box::new(io::Error::other(format!(
+            "git shortlog failed for {}: {}",
+            repo.display(),
+            // Usage that follows the first line says nothing about the cause
+            String::from_utf8_lossy(&output.stderr)
+                .lines()
+                .next()
+                .unwrap_or_default()
+        ))));
+    }
+
+    Ok(parse_shortlog(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Parses output of git shortlog -sne, lines like "   42\tName <email>".
+/// Lines that don't look like that are skipped
+pub fn parse_shortlog(output: &str) -> Contributions {
+    let mut contributions: Contributions = output
+        .lines()
+        .filter_map(|line| {
+            let (count, author) = line.trim().split_once('\t')?;
+            Some(ContributorData {
+                login: author.trim().to_string(),
+                contributions: count.trim().parse().ok()?,
+                ..ContributorData::default()
+            })
+        })
+        .collect();
+
+    // Shares expect the most active first, shortlog -n does that already, but it's cheap
+    contributions.sort_by_key(|contr| std::cmp::Reverse(contr.contributions));
+    contributions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shortlog_is_parsed() {
+        let output = "    3\tBob <bob@example.com>\n\
+                      \x20  12\tAlice <alice@example.com>\n\
+                      not a shortlog line\n";
+
+        let contributions = parse_shortlog(output);
+
+        assert_eq!(
+            contributions,
+            vec![
+                ContributorData {
+                    login: "Alice <alice@example.com>".to_string(),
+                    contributions: 12,
+                    ..ContributorData::default()
+                },
+                ContributorData {
+                    login: "Bob <bob@example.com>".to_string(),
+                    contributions: 3,
+                    ..ContributorData::default()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_repo_is_an_error() {
+        let err = contributions(Path::new("/nonexistent/bus_factor/repo")).unwrap_err();
+
+        assert!(
+            err.to_string().contains("/nonexistent/bus_factor/repo"),
+            "{}",
+            err
+        );
+    }
+}
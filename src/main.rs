@@ -1,16 +1,110 @@
 #[macro_use]
 extern crate log;
 
-mod api_errors;
-mod github_api;
-mod github_client;
-mod github_data;
-use std::{error::Error, fs, time::Instant};
+use std::{
+    fs,
+    io::{self, IsTerminal},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+    time::Instant,
+};
 
-use github_api::{BusFactor, GithubApi, RepoQuery};
+use bus_factor::{
+    api_errors::{InvalidQueryError, ResponseError, SendError},
+    concurrency::{AdaptiveConfig, RateLimiter},
+    github_api::{
+        BusFactor, BusFactorQuery, FullName, GithubApi, MemberScope, RepoQuery, RepoSort, Threshold,
+    },
+    github_client::{self, GithubClient, TokenPool, TransportConfig},
+    github_data::{RepoData, Repos},
+    local_git,
+    metrics::RunMetrics,
+    profile::{self, Budget, RunCost},
+    progress::{ProgressListener, ProgressWriter},
+    report::{
+        self, BatchReport, ColorChoice, CompactSummary, JsonArrayWriter, NdjsonWriter,
+        OutputFormat, Report, ReportMeta, ReportRecord, SortBy, SortOrder, SummaryWeight,
+    },
+    retry::RetryPolicy,
+    share::{self, ShareBasis, ShareSource},
+    snapshot::{self, Snapshot},
+    timing::LatencyStats,
+    warnings::RunWarning,
+};
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use reqwest::{
+    header::{HeaderName, HeaderValue},
+    StatusCode,
+};
 use structopt::StructOpt;
 
-use crate::github_api::BusFactorQuery;
+/// Name of the language in reports of --language-any
+const ANY_LANGUAGE: &str = "any";
+
+/// Above that many projects repos are processed in batches, so memory does not grow with the count
+const STREAMING_COUNT: u32 = 500;
+
+/// Machine in the .netrc with the token as password
+const NETRC_MACHINE: &str = "api.github.com";
+
+/// Process exit codes, so scripts can react to the class of the failure
+mod exit_code {
+    use std::error::Error;
+
+    use bus_factor::api_errors::{
+        AbuseError, AuthError, InvalidQueryError, PermissionError, RateLimitError, SendError,
+        ShortfallError,
+    };
+
+    pub const SUCCESS: i32 = 0;
+    /// Any failure not covered by other codes
+    pub const OTHER: i32 = 1;
+    pub const INVALID_QUERY: i32 = 2;
+    /// Token was rejected, it's invalid or expired
+    pub const AUTH: i32 = 3;
+    /// Rate limit exhausted, including secondary one
+    pub const RATE_LIMIT: i32 = 4;
+    /// Server could not be reached, or connection broke
+    pub const NETWORK: i32 = 5;
+    /// Run finished, but some languages, or repos that failed, were skipped
+    pub const PARTIAL: i32 = 6;
+    /// Connection check of --connect-only failed, for whatever reason
+    pub const CONNECT_FAILED: i32 = 7;
+    /// Search found fewer repos than requested, with --strict-count
+    pub const SHORTFALL: i32 = 8;
+    /// Token is valid, but lacks permissions needed, like to read contents of repos
+    pub const PERMISSION: i32 = 9;
+
+    /// Exit code of the run, that succeeded only partially if it gave false
+    pub fn of_run(res: &Result<bool, SendError>) -> i32 {
+        match res {
+            Ok(true) => SUCCESS,
+            Ok(false) => PARTIAL,
+            Err(e) => of(e.as_ref()),
+        }
+    }
+
+    /// Exit code for the error that ended the run
+    pub fn of(err: &(dyn Error + 'static)) -> i32 {
+        if err.is::<InvalidQueryError>() {
+            INVALID_QUERY
+        } else if err.is::<RateLimitError>() || err.is::<AbuseError>() {
+            RATE_LIMIT
+        } else if err.is::<AuthError>() {
+            AUTH
+        } else if err.is::<PermissionError>() {
+            PERMISSION
+        } else if err.is::<reqwest::Error>() {
+            NETWORK
+        } else if err.is::<ShortfallError>() {
+            SHORTFALL
+        } else {
+            OTHER
+        }
+    }
+}
 
 #[derive(Debug, StructOpt)]
 #[structopt(
@@ -20,112 +114,2532 @@ use crate::github_api::BusFactorQuery;
 /// Command line arguments
 struct Opt {
     /// Programming language name
-    #[structopt(short, long)]
-    language: String,
+    #[structopt(
+        short,
+        long,
+        required_unless_one = &["language-file", "language-any", "connect-only", "local-repo", "current-report"]
+    )]
+    language: Option<String>,
+
+    /// Calculates bus factor of a local clone from its git history, github is not used at all
+    #[structopt(
+        long,
+        parse(from_os_str),
+        conflicts_with_all = &[
+            "language", "language-any", "language-file", "repos-only", "snapshot",
+            "members-of-org", "members-of-team", "normalize", "always-include", "share-basis"
+        ]
+    )]
+    local_repo: Option<PathBuf>,
+
+    /// Stars shown for --local-repo, there is no way to tell them without github
+    #[structopt(long, requires = "local-repo")]
+    stars: Option<u64>,
+
+    /// Only check if github api can be reached, with the proxy and timeout given, and exit.
+    /// Token is used if there is one
+    #[structopt(long)]
+    connect_only: bool,
+
+    /// Search repos regardless of their language
+    #[structopt(long, conflicts_with_all = &["language", "language-file"])]
+    language_any: bool,
+
+    /// File with languages to survey, one per line, lines starting with # are skipped
+    #[structopt(long, parse(from_os_str), conflicts_with_all = &["language", "snapshot"])]
+    language_file: Option<PathBuf>,
+
+    /// With --output-dir, that many languages of --language-file are surveyed at the same time,
+    /// all of them together still within --rate and --concurrency
+    #[structopt(long, default_value = "2")]
+    languages_at_once: usize,
+
+    /// With --language-file, writes report of each language to its own file in that directory,
+    /// in the chosen format, instead of the output. Created if missing
+    #[structopt(long, parse(from_os_str))]
+    output_dir: Option<PathBuf>,
 
     /// Number of projects to consider
-    #[structopt(short, long)]
+    #[structopt(
+        short,
+        long,
+        required_unless_one = &["connect-only", "local-repo", "current-report"],
+        default_value_if("connect-only", None, "0"),
+        default_value_if("local-repo", None, "1"),
+        default_value_if("current-report", None, "0")
+    )]
     project_count: u32,
 
-    /// Filepath for token
-    #[structopt(short, long, default_value = "./.token")]
-    token_path: String,
+    /// Report only that many riskiest repos, by leader's share, or by score if there is one.
+    /// Applied after the threshold, before --sort-by. Repos from --always-include are still reported
+    #[structopt(long)]
+    top: Option<usize>,
+
+    /// Calculate bus factor only for that many most starred of the repos found, the rest is
+    /// reported without it. Repos are not streamed then, even above 500 projects
+    #[structopt(long)]
+    factor_limit: Option<usize>,
+
+    /// Fail if the search finds fewer repos than --project-count, instead of going on with those found
+    #[structopt(long)]
+    strict_count: bool,
+
+    /// Drop repos whose detected language is not the requested one, ignoring case, cpp matches C++.
+    /// Search also gives repos that are not mostly in the language, this keeps exact matches only.
+    /// With --strict-count the run fails whenever any repo is dropped
+    #[structopt(long)]
+    include_only_language_repos: bool,
+
+    /// Filepath for token. If there is no such file, password of api.github.com
+    /// from ~/.netrc (or file given by NETRC) is used. Can be repeated, tokens are then
+    /// used in turns, so their rate limits add up, each needs the same scopes
+    #[structopt(short, long, default_value = "./.token", number_of_values = 1)]
+    token_path: Vec<String>,
 
     #[structopt(short, long, default_value = "0")]
-    delay_sec: f64
+    delay_sec: f64,
+
+    /// Max requests per second, of all workers and the search together, retries included.
+    /// Unlike --delay-sec it bounds the rate regardless of how many requests are in flight
+    #[structopt(long)]
+    rate: Option<f64>,
+
+    /// Check the rate limit left and how many repos the search matches first, then pick
+    /// concurrency and rate of the run, they are printed before it starts. If the quota left
+    /// is not enough, the run is paced to go on after the reset, and --project-count that fits is suggested.
+    /// Not with --share-basis pull-requests, its pages spend the search quota, not the core one
+    #[structopt(
+        long,
+        conflicts_with_all = &["rate", "adaptive-concurrency", "offline", "language-file", "connect-only"]
+    )]
+    profile: bool,
+
+    /// Order of searched repos: stars, best-match, help-wanted-issues
+    #[structopt(long, default_value = "stars")]
+    repo_sort: RepoSort,
+
+    /// Search only for repos with at least that many stars
+    #[structopt(long)]
+    min_stars: Option<u64>,
+
+    /// Search only for repos with at most that many stars
+    #[structopt(long)]
+    max_stars: Option<u64>,
+
+    /// Search only for repos tagged with the topic
+    #[structopt(long)]
+    topic: Option<String>,
+
+    /// Drop repos that no longer exist before calculating bus factor, one more request per repo
+    #[structopt(long)]
+    verify_repos: bool,
+
+    /// Only list searched repos, without calculating bus factor
+    #[structopt(
+        long,
+        conflicts_with_all = &["language-file", "snapshot", "members-of-org", "members-of-team"]
+    )]
+    repos_only: bool,
+
+    /// Report shares relative to all commits in the repo, instead of only those of top
+    /// contributors. Numbers are lower than without it, costs one more request per repo
+    #[structopt(long)]
+    normalize: bool,
+
+    /// What shares are counted from: commits, or pull-requests, merged ones by their authors.
+    /// Pull requests cost up to --max-contributor-pages search requests per repo, and search
+    /// allows only 30 requests a minute
+    #[structopt(long, default_value = "commits", conflicts_with = "normalize")]
+    share_basis: ShareBasis,
+
+    /// Consider only contributors that are members of the organization
+    #[structopt(long, conflicts_with = "members-of-team")]
+    members_of_org: Option<String>,
+
+    /// Consider only contributors that are members of the team, given as org/team
+    #[structopt(long)]
+    members_of_team: Option<MemberScope>,
+
+    /// Proxy url used for all requests, by default taken from HTTPS_PROXY and friends
+    #[structopt(long)]
+    proxy: Option<String>,
+
+    /// PEM file with a root certificate to trust besides the system ones, like a corporate CA
+    #[structopt(long, parse(from_os_str))]
+    ca_cert: Option<PathBuf>,
+
+    /// UNSAFE: accept any certificate, even an expired or self-signed one. Only for testing
+    /// behind a dev proxy, anyone in between can read the token then
+    #[structopt(long)]
+    danger_accept_invalid_certs: bool,
+
+    /// Time limit for a single request
+    #[structopt(long, parse(try_from_str = parse_seconds))]
+    timeout_sec: Option<Duration>,
+
+    /// Time limit for all requests of a single repo, repos that take longer are skipped
+    #[structopt(long, parse(try_from_str = parse_seconds))]
+    timeout_per_repo: Option<Duration>,
+
+    /// Stop on the first repo that fails, nothing is reported then. By default such repos
+    /// are skipped, the rest is reported, and the run ends with exit code 6, partial success
+    #[structopt(long)]
+    fail_fast: bool,
+
+    /// Rounds in which repos that failed are tried again, once all others are done
+    #[structopt(long, default_value = "0", conflicts_with = "fail-fast")]
+    retry_failed_rounds: u32,
+
+    /// Delay before the first round of --retry-failed-rounds, doubled for each next one
+    #[structopt(long, default_value = "5", parse(try_from_str = parse_seconds))]
+    retry_failed_backoff_sec: Duration,
+
+    /// Extra header added to every request, in name:value format, can be repeated
+    #[structopt(long = "header", parse(try_from_str = github_client::parse_header))]
+    headers: Vec<(HeaderName, HeaderValue)>,
+
+    /// How many times failed request is repeated
+    #[structopt(long, default_value = "0")]
+    max_retries: u32,
+
+    /// Part of each retry delay that is random, from 0 to 1, of --max-retries and
+    /// --retry-failed-rounds. Delays are exact by default
+    #[structopt(long, default_value = "0")]
+    retry_jitter: f64,
+
+    /// Seeds everything random, like --retry-jitter, so the run can be repeated exactly.
+    /// Seeded differently on each run if not given
+    #[structopt(long)]
+    seed: Option<u64>,
+
+    /// Lower number of requests in flight when throttled, and slowly raise it back.
+    /// Throttled requests are repeated only with --max-retries
+    #[structopt(long)]
+    adaptive_concurrency: bool,
+
+    /// Requests in flight at the start, with --adaptive-concurrency
+    #[structopt(long, default_value = "5")]
+    concurrency: usize,
+
+    /// Lower bound of requests in flight, with --adaptive-concurrency
+    #[structopt(long, default_value = "1")]
+    min_concurrency: usize,
+
+    /// Upper bound of requests in flight, with --adaptive-concurrency
+    #[structopt(long, default_value = "20")]
+    max_concurrency: usize,
+
+    /// Added to the number of requests in flight after each successful one
+    #[structopt(long, default_value = "0.1")]
+    concurrency_increase: f64,
+
+    /// Number of requests in flight is multiplied by that on throttling
+    #[structopt(long, default_value = "0.5")]
+    concurrency_decrease: f64,
+
+    /// Repos with less contributors than that are not reported
+    #[structopt(long, default_value = "0")]
+    min_contributors: u32,
+
+    /// Share of the leader above which repo is at risk, or "auto" to take it from
+    /// the results: repos with share higher than 90% of others are at risk
+    #[structopt(long, default_value = "0.75")]
+    threshold: Threshold,
+
+    /// Mark repos where the leader is the owner of the repo as owner-led,
+    /// for solo projects high share is expected, not alarming
+    #[structopt(long)]
+    flag_owner_led: bool,
+
+    /// Number of top contributors shares are computed from, within 1..=500. Above 100
+    /// they are fetched in pages of 100, at most --max-contributor-pages of them
+    #[structopt(long, default_value = "25")]
+    users_to_consider: u32,
+
+    /// At most that many pages of contributors are fetched for a single repo,
+    /// if more are needed, share is approximate. At least 1
+    #[structopt(long, default_value = "3")]
+    max_contributor_pages: u32,
+
+    /// Calculate bus factor of forks too, by default they are skipped,
+    /// as their history is mostly the one of the parent repo
+    #[structopt(long)]
+    include_forks: bool,
+
+    /// Report repos without contributors, like new or empty ones, with N/A share,
+    /// regardless of the threshold. By default they are skipped
+    #[structopt(long)]
+    include_empty_repos: bool,
+
+    /// Do not report owner-led repos above the threshold, they are not at risk
+    #[structopt(long)]
+    exclude_owner_led: bool,
+
+    /// Always report the repo, given as owner/name, regardless of the threshold and filters.
+    /// Fetched by name if the search did not find it, can be repeated
+    #[structopt(long, number_of_values = 1)]
+    always_include: Vec<FullName>,
+
+    /// Show leader share over all reported repos, weighted by usage, fetched with one
+    /// more request per reported repo. Weighted by stars if usage of any repo is unknown
+    #[structopt(long)]
+    weight_by_usage: bool,
+
+    /// Report only repos with bus factor above the threshold, that's the default
+    #[structopt(long, conflicts_with = "only-below")]
+    only_above: bool,
+
+    /// Report only repos with bus factor below the threshold, the healthy ones
+    #[structopt(long)]
+    only_below: bool,
+
+    /// File with the result of previous run, shows what changed since then and gets overwritten.
+    /// Changes go to stderr unless the output is text
+    #[structopt(long, parse(from_os_str))]
+    snapshot: Option<PathBuf>,
+
+    /// Show how many stars repos gained since the snapshot, or the --diff-against report,
+    /// fastest growing first
+    #[structopt(long)]
+    since_stars: bool,
+
+    /// Report saved with --format json before, shows what changed since then.
+    /// Unlike --snapshot the file is not overwritten
+    #[structopt(
+        long,
+        parse(from_os_str),
+        conflicts_with_all = &["language-file", "compact", "repos-only", "connect-only", "local-repo"]
+    )]
+    diff_against: Option<PathBuf>,
+
+    /// Report to compare with --diff-against instead of running, so two saved reports are compared
+    #[structopt(long, parse(from_os_str), requires = "diff-against")]
+    current_report: Option<PathBuf>,
+
+    /// Format of the results: text, json, csv, tsv, html, json-array, ndjson-report
+    #[structopt(long, default_value = "text")]
+    format: OutputFormat,
+
+    /// Sort reported repos by: share, stars, name, score, issues. Search order by default.
+    /// Above 500 projects repos are sorted within the batch only
+    #[structopt(long)]
+    sort_by: Option<SortBy>,
+
+    /// Order of --sort-by: asc, desc
+    #[structopt(long, default_value = "desc")]
+    order: SortOrder,
+
+    /// Color risk levels in the text output: auto, always, never.
+    /// Auto colors only if the output is a terminal
+    #[structopt(long, default_value = "auto")]
+    color: ColorChoice,
+
+    /// Separate fields of the text output with a single space, without padding, easier for awk.
+    /// By default columns are as wide as the names they hold
+    #[structopt(long)]
+    no_align: bool,
+
+    /// Name repos in the text output by name alone, not by "owner/name". Repos of different
+    /// owners can have the same name, json has both of them anyway
+    #[structopt(long)]
+    short_names: bool,
+
+    /// Show the counts behind the share in the text output, leader's contributions over the total.
+    /// Json always has them, as leader_contributions, total_considered_contributions and true_total
+    #[structopt(long)]
+    show_counts: bool,
+
+    /// Compute truck factor: how many top contributors together did that fraction of the work,
+    /// like 0.8. Shown in the text output, truck_factor in json
+    #[structopt(long)]
+    coverage: Option<f64>,
+
+    /// Weight of commits from the last --recent-days in shares, from 0 to 1, so a leader who is
+    /// no longer active counts less. Costs requests for recent commits of each repo
+    #[structopt(long, default_value = "0")]
+    recency_weight: f64,
+
+    /// How far back commits count as recent, for --recency-weight
+    #[structopt(long, default_value = "90")]
+    recent_days: u32,
+
+    /// Indent json output, compact by default
+    #[structopt(long)]
+    pretty: bool,
+
+    /// Show that many most common topics and languages among reported repos
+    #[structopt(long)]
+    facets: Option<usize>,
+
+    /// Show how long search and bus factor phases took, and latencies of repos
+    #[structopt(long)]
+    trace_timing: bool,
+
+    /// Show how many requests each repo cost, also added to json as requests
+    #[structopt(long)]
+    stats: bool,
+
+    /// Show how old each repo is in the text output, json always has created_at
+    #[structopt(long)]
+    show_age: bool,
+
+    /// File the metrics of the run are written to once it's done, in Prometheus text format:
+    /// repos processed, high risk ones, skipped ones, requests, retries, remaining rate limit and duration
+    #[structopt(long, parse(from_os_str))]
+    metrics_file: Option<PathBuf>,
+
+    /// File progress events are written to as they happen, as newline-delimited JSON:
+    /// started, repo-completed, rate-limited and finished. Like /dev/fd/3 for an open descriptor
+    #[structopt(long, parse(from_os_str))]
+    progress_to: Option<PathBuf>,
+
+    /// Instead of the report, print one line per language: high risk and processed repos,
+    /// the worst one, errors and time taken. In the chosen format, text for html
+    #[structopt(long)]
+    compact: bool,
+
+    /// Directory where all responses are saved, so they can be used as test fixtures
+    #[structopt(long, parse(from_os_str))]
+    record: Option<PathBuf>,
+
+    /// Directory where contributors of every repo are written as fetched, for archival or
+    /// independent analysis, to owner/name/contributors-N.json for each page N
+    #[structopt(long, parse(from_os_str))]
+    dump_raw: Option<PathBuf>,
+
+    /// Send no request at all, take every response from those saved with --record to the same
+    /// directory before. Request that was not saved ends the run, naming what is missing
+    #[structopt(long, requires = "record", conflicts_with = "connect-only")]
+    offline: bool,
+
+    /// Take responses already saved with --record to the same directory, and request
+    /// only the rest from github, those are saved as well
+    #[structopt(long, requires = "record", conflicts_with = "offline")]
+    record_new: bool,
+
+    /// Base url contributors and repos are requested from instead of api.github.com,
+    /// like a mock server or a mirror. Search still goes to github, unless --api-base-for-search is given
+    #[structopt(long, alias = "api-base-for-contributors")]
+    contributors_endpoint_override: Option<String>,
+
+    /// Base url search is requested from instead of api.github.com, like a mirror.
+    /// Contributor urls from the results are used as they are, unless --api-base-for-contributors is given
+    #[structopt(long)]
+    api_base_for_search: Option<String>,
 }
 
 /// Reads token from the file
 fn get_token(filepath: &str) -> String {
-    fs::read_to_string(filepath).expect("Something went wrong reading the file")
+    find_token(filepath).expect("Something went wrong reading the file")
+}
+
+/// Token from the file, or if it can't be read, from the .netrc
+fn find_token(filepath: &str) -> Option<String> {
+    fs::read_to_string(filepath).ok().or_else(|| {
+        let path = std::env::var_os("NETRC")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".netrc")))?;
+
+        let token = netrc_password(&fs::read_to_string(&path).ok()?, NETRC_MACHINE)?;
+        debug!("Token taken from {}", path.display());
+        Some(token)
+    })
 }
 
-/// Pretty printing of the result
-fn show_result(res: &[BusFactor]) {
+/// Password of the machine from content of .netrc. Entries are made of whitespace
+/// separated pairs, like "machine api.github.com login user password token".
+/// Default entry is used if there is no entry for the machine
+fn netrc_password(content: &str, machine: &str) -> Option<String> {
+    let mut words = content.split_whitespace();
+    let mut current = None;
+    let mut default = None;
+    let mut found = None;
+
+    while let Some(word) = words.next() {
+        match word {
+            "machine" => current = words.next(),
+            "default" => current = Some(""),
+            "password" => {
+                let password = words.next().map(str::to_string);
+                match current {
+                    Some(name) if name == machine => found = found.or(password),
+                    Some("") => default = default.or(password),
+                    _ => {}
+                }
+            }
+            // Macro definition is ended by an empty line, tokens of it would be taken as pairs
+            "macdef" => break,
+            // Value of login or account
+            _ => {
+                words.next();
+            }
+        }
+    }
+
+    found.or(default)
+}
+
+/// Pretty printing of the result, optionally with owner-led repos marked,
+/// and with age of repos at given time. Repos are named by full name unless short_names
+fn show_result(
+    res: &[BusFactor],
+    flag_owner_led: bool,
+    color: bool,
+    short_names: bool,
+    show_counts: bool,
+    align: bool,
+    age_at: Option<DateTime<Utc>>,
+) {
+    let widths = if align {
+        Some(name_widths(res, short_names))
+    } else {
+        None
+    };
     for repo in res {
         println!(
-            "project: {project:20} user: {user:20} percentage: {bus_factor:.2} stars: {stars}",
-            project = repo.repo_name,
-            user = repo.leader.user_name,
-            bus_factor = repo.leader.bus_factor,
-            stars = repo.stars
+            "{}",
+            result_line(
+                repo,
+                flag_owner_led,
+                color,
+                short_names,
+                show_counts,
+                widths,
+                age_at
+            )
         )
     }
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
-    env_logger::init();
+/// Time age of repos is counted to, with --show-age
+fn age_at(opt: &Opt) -> Option<DateTime<Utc>> {
+    opt.show_age.then(Utc::now)
+}
 
-    let opt = Opt::from_args();
+/// Widths of the project and user columns, fitting names of all results
+fn name_widths(res: &[BusFactor], short_names: bool) -> (usize, usize) {
+    (
+        report::column_width(
+            res.iter().map(|repo| project_text(repo, short_names)),
+            report::MAX_NAME_WIDTH,
+        ),
+        report::column_width(res.iter().map(leader_text), report::MAX_NAME_WIDTH),
+    )
+}
+
+/// Project of the text output, names alone are ambiguous across owners, so full name is
+/// preferred. Results of older reports may not have it
+fn project_text(repo: &BusFactor, short_names: bool) -> &str {
+    if short_names || repo.full_name.is_empty() {
+        &repo.repo_name
+    } else {
+        &repo.full_name
+    }
+}
+
+/// Leader of the text output, unknown one would leave an empty field
+fn leader_text(repo: &BusFactor) -> &str {
+    if repo.has_share() {
+        &repo.leader.user_name
+    } else {
+        "-"
+    }
+}
+
+/// Single line of the text output, with project and user columns of given widths,
+/// or not padded at all without them. Age at given time is shown if the repo has created_at,
+/// counts behind the share if they are known and show_counts
+fn result_line(
+    repo: &BusFactor,
+    flag_owner_led: bool,
+    color: bool,
+    short_names: bool,
+    show_counts: bool,
+    widths: Option<(usize, usize)>,
+    age_at: Option<DateTime<Utc>>,
+) -> String {
+    // Optional columns at the end of the line
+    let mut extra = match repo.requests {
+        Some(requests) => format!(" requests: {}", requests),
+        None => String::new(),
+    };
+    if let (true, Some(leader), Some(total)) = (
+        show_counts,
+        repo.leader_contributions,
+        repo.true_total.or(repo.total_considered_contributions),
+    ) {
+        extra.push_str(&format!(" contributions: {}/{}", leader, total));
+    }
+    if let Some(truck_factor) = repo.truck_factor {
+        extra.push_str(&format!(" truck-factor: {}", truck_factor));
+    }
+    if let (Some(at), Some(created_at)) = (age_at, repo.created_at) {
+        extra.push_str(&format!(" age: {}", report::age_text(created_at, at)));
+    }
+    if flag_owner_led && repo.owner_led {
+        extra.push_str(" owner-led");
+    }
+    if repo.always_included {
+        extra.push_str(" always-included");
+    }
+
+    let leader = leader_text(repo);
+    let name = project_text(repo, short_names);
+    let (project, user, risk) = match widths {
+        Some((project, user)) => (
+            report::fit_width(name, project),
+            report::fit_width(leader, user),
+            // Padded before painting, escape codes would count as width
+            format!("{:8}", report::risk_text(repo)),
+        ),
+        None => (
+            name.to_string(),
+            leader.to_string(),
+            report::risk_text(repo),
+        ),
+    };
+    let risk = if color {
+        report::paint_risk(repo.risk_level, &risk)
+    } else {
+        risk
+    };
+
+    format!(
+        "project: {project} user: {user} percentage: {bus_factor} risk: {risk} stars: {stars}{extra}",
+        project = project,
+        user = user,
+        bus_factor = report::share_text(repo, 2),
+        risk = risk,
+        stars = repo.stars,
+        extra = extra
+    )
+}
+
+/// Writes report of each language to dir, file is named after the language,
+/// with extension of the format
+fn write_language_files(dir: &Path, batch: &BatchReport, opt: &Opt) -> Result<(), SendError> {
+    fs::create_dir_all(dir).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("Can't create directory {}: {}", dir.display(), e),
+        )
+    })?;
+
+    for (language, report) in &batch.languages {
+        let content = match opt.format {
+            OutputFormat::Text => {
+                let widths = if opt.no_align {
+                    None
+                } else {
+                    Some(name_widths(&report.results, opt.short_names))
+                };
+                let lines: String = report
+                    .results
+                    .iter()
+                    .map(|repo| {
+                        result_line(
+                            repo,
+                            opt.flag_owner_led,
+                            false,
+                            opt.short_names,
+                            opt.show_counts,
+                            widths,
+                            age_at(opt),
+                        ) + "\n"
+                    })
+                    .collect();
+                report.meta.comment() + "\n" + &lines
+            }
+            OutputFormat::Json | OutputFormat::JsonArray => {
+                report::to_json(report, opt.pretty)? + "\n"
+            }
+            OutputFormat::Csv | OutputFormat::Tsv => {
+                let delimiter = opt.format.delimiter().unwrap_or_default();
+                report.meta.comment()
+                    + "\n"
+                    + &report::results_to_delimited(std::iter::once(report), delimiter)
+            }
+            OutputFormat::Html => report::results_to_html(std::iter::once(report)),
+            OutputFormat::NdjsonReport => {
+                let mut lines = String::new();
+                for repo in &report.results {
+                    let record = ReportRecord::new(report::run_id(), language, repo, Utc::now());
+                    lines += &(serde_json::to_string(&record)? + "\n");
+                }
+                lines
+            }
+        };
+
+        // Languages like "c++" or "c#" are fine, only separators would leave the directory
+        let name = language.replace(['/', '\\'], "_");
+        let path = dir.join(format!("{}.{}", name, opt.format.extension()));
+        fs::write(&path, content).map_err(|e| {
+            io::Error::new(e.kind(), format!("Can't write {}: {}", path.display(), e))
+        })?;
+    }
 
-    let token = get_token(&opt.token_path);
+    Ok(())
+}
+
+/// Pretty printing of the weighted summary
+fn show_summary(results: &[BusFactor]) {
+    if let Some(summary) = report::weighted_summary(results) {
+        let weight = match summary.weight {
+            SummaryWeight::Usage => "usage",
+            SummaryWeight::Stars => "stars",
+        };
+        println!(
+            "Summary of {} repos, leader share weighted by {}: {:.2}",
+            summary.repos, weight, summary.leader_share
+        );
+    }
+}
+
+/// Pretty printing of the most common topics and languages
+fn show_facets(results: &[BusFactor], top: usize) {
+    let facets = report::facet_counts(results);
+
+    println!("Top topics:");
+    for (topic, count) in facets.topics.iter().take(top) {
+        println!("topic: {:20} repos: {}", topic, count);
+    }
+
+    println!("Top languages:");
+    for (language, count) in facets.languages.iter().take(top) {
+        println!("lang: {:20} repos: {}", language, count);
+    }
+}
+
+/// Pretty printing of searched repos, aligned in columns unless told otherwise
+fn show_repos<'a>(repos: &'a [RepoData], short_names: bool, align: bool) {
+    let name = |repo: &'a RepoData| -> &'a str {
+        if short_names || repo.full_name.is_empty() {
+            &repo.name
+        } else {
+            &repo.full_name
+        }
+    };
+    let width = report::column_width(repos.iter().map(name), report::MAX_NAME_WIDTH);
+    for repo in repos {
+        if align {
+            println!(
+                "project: {project} stars: {stars:8} url: {url}",
+                project = report::fit_width(name(repo), width),
+                stars = repo.stargazers_count,
+                url = repo.html_url
+            )
+        } else {
+            println!(
+                "project: {} stars: {} url: {}",
+                name(repo),
+                repo.stargazers_count,
+                repo.html_url
+            )
+        }
+    }
+}
+
+/// Keeps only the riskiest reported results, if there is the limit, noting how many were left out
+fn limit_to_top(res: &mut Vec<BusFactor>, top: Option<usize>) {
+    if let Some(top) = top {
+        let omitted = report::keep_riskiest(res, top);
+        if omitted > 0 {
+            eprintln!("Showing top {} riskiest repos, {} omitted", top, omitted);
+        }
+    }
+}
+
+/// Builds search query from the arguments
+fn repo_query<'a>(opt: &'a Opt, language: Option<&'a str>) -> RepoQuery<'a> {
+    RepoQuery {
+        language,
+        count: opt.project_count,
+        delay_sec: opt.delay_sec,
+        sort: opt.repo_sort,
+        min_stars: opt.min_stars,
+        max_stars: opt.max_stars,
+        topic: opt.topic.as_deref(),
+        strict_count: opt.strict_count,
+        strict_language: opt.include_only_language_repos,
+    }
+}
+
+/// Prints how long the run took, on stderr so it does not mix with the results
+fn print_elapsed(language: &str, count: u32, start: Instant) {
+    eprintln!(
+        "For lang {}, count {} it took {}ms",
+        language,
+        count,
+        start.elapsed().as_millis(),
+    );
+}
+
+/// Shows how long phases took, and distribution of repo latencies.
+/// Goes to stderr, like other progress messages
+fn show_timing(language: &str, phases: &[(&str, Duration)], latencies: &[Duration]) {
+    eprintln!("Timing for lang {}:", language);
+    for (phase, took) in phases {
+        eprintln!("phase: {:24} took: {}ms", phase, took.as_millis());
+    }
 
-    let api = GithubApi::new(&token);
+    // Repos that timed out are not there, they were cancelled
+    match LatencyStats::of(latencies) {
+        Some(stats) => eprintln!(
+            "repos: {} min: {}ms median: {}ms p95: {}ms max: {}ms",
+            stats.count,
+            stats.min.as_millis(),
+            stats.median.as_millis(),
+            stats.p95.as_millis(),
+            stats.max.as_millis()
+        ),
+        None => eprintln!("repos: 0"),
+    }
+}
+
+/// Parses duration given in seconds, fractions allowed. Negative one is rejected
+fn parse_seconds(secs: &str) -> Result<Duration, InvalidQueryError> {
+    let invalid = || InvalidQueryError::new(&format!("{} is not a valid number of seconds", secs));
+    let secs = secs.parse::<f64>().map_err(|_| invalid())?;
+
+    Duration::try_from_secs_f64(secs).map_err(|_| invalid())
+}
+
+/// Extracts languages from content of the language file
+fn parse_languages(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Builds bus factor query from the arguments, fetches members if calculation is restricted to them
+async fn bus_factor_query(api: &GithubApi, opt: &Opt) -> Result<BusFactorQuery, SendError> {
+    let scope = match (&opt.members_of_org, &opt.members_of_team) {
+        (Some(org), _) => Some(MemberScope::Org(org.to_string())),
+        (None, team) => team.clone(),
+    };
+
+    let members = match scope {
+        Some(scope) => Some(api.get_members(&scope).await?),
+        None => None,
+    };
+
+    let query = BusFactorQuery::builder()
+        .bus_threshold(match opt.threshold {
+            Threshold::Fixed(threshold) => threshold,
+            // Actual one is known only once all results are there
+            Threshold::Auto => BusFactorQuery::default().bus_threshold,
+        })
+        .users_to_consider(opt.users_to_consider)
+        .delay_sec(opt.delay_sec)
+        .min_contributors(opt.min_contributors)
+        .verify_repos(opt.verify_repos)
+        .members(members)
+        .normalize(opt.normalize)
+        .basis(opt.share_basis)
+        .timeout_per_repo(opt.timeout_per_repo)
+        .count_requests(opt.stats)
+        .max_contributor_pages(opt.max_contributor_pages)
+        .include_forks(opt.include_forks)
+        .include_empty(opt.include_empty_repos)
+        .coverage(opt.coverage)
+        .recency_weight(opt.recency_weight)
+        .recent_window(Duration::from_secs(
+            u64::from(opt.recent_days) * 24 * 60 * 60,
+        ))
+        // Report regenerated offline would silently miss repos that were not recorded
+        .fail_fast(opt.fail_fast || opt.offline)
+        .repo_retries(RetryPolicy {
+            max_retries: opt.retry_failed_rounds,
+            backoff: opt.retry_failed_backoff_sec,
+            jitter: opt.retry_jitter,
+        })
+        .build()?;
+
+    Ok(query)
+}
+
+/// Reported results of a language, with number of repos the search found for it
+struct LanguageRun {
+    results: Vec<BusFactor>,
+    /// Every result computed, reported or not, kept only for --snapshot
+    computed: Vec<BusFactor>,
+    /// Before anything is filtered out, not even by --strict-language, 0 means nothing
+    /// matched the query. Above 500 projects it's what the search is expected to give
+    found: u64,
+}
 
+/// Finds repos for the language, calculates their bus factor and shows the ones that are reported.
+/// Results are shown right away for text, json-array and ndjson-report formats, json is written once everything is done.
+async fn run_language(
+    api: &GithubApi,
+    opt: &Opt,
+    query: &BusFactorQuery,
+    language: Option<&str>,
+    run_metrics: &mut RunMetrics,
+) -> Result<LanguageRun, SendError> {
     let now = Instant::now();
+    let mut found = 0;
+    let mut metrics = RunMetrics::default();
+
+    let repo_query = repo_query(opt, language);
+    let label = language.unwrap_or(ANY_LANGUAGE);
+
+    // Pick the side of the threshold that is reported, above by default
+    let report_above = opt.only_above || !opt.only_below;
+    let is_reported = |repo: &BusFactor, threshold: f64| {
+        // Such repo has no share to compare, it's there only with --include-empty-repos or --factor-limit
+        if repo.always_included || !repo.has_share() {
+            return true;
+        }
+        // Owner-led repo above the threshold is not at risk, but it's not healthy either
+        if opt.exclude_owner_led && repo.owner_led && repo.is_significant(threshold) {
+            return false;
+        }
+        repo.is_significant(threshold) == report_above
+    };
+    // Batch results go to files, it's not the output
+    let to_files = opt.language_file.is_some() && opt.output_dir.is_some();
+    if opt.format == OutputFormat::Text && !to_files && !opt.compact {
+        println!("{}", ReportMeta::new(label, opt.project_count).comment());
+    }
+    let mut writer = match opt.format {
+        OutputFormat::JsonArray if !opt.compact => Some(JsonArrayWriter::new(io::stdout())?),
+        _ => None,
+    };
+    let mut ndjson = match opt.format {
+        OutputFormat::NdjsonReport if !to_files && !opt.compact => {
+            Some(NdjsonWriter::new(io::stdout(), report::run_id(), label))
+        }
+        _ => None,
+    };
+    let color = opt.color.enabled(io::stdout().is_terminal());
+    let streamed = writer.is_some() || ndjson.is_some();
+    let mut show = |res: &mut [BusFactor]| -> io::Result<()> {
+        if let Some(by) = opt.sort_by {
+            report::sort_results(res, by, opt.order);
+        }
+
+        if let Some(writer) = &mut writer {
+            for repo in res {
+                writer.write(repo)?;
+            }
+        } else if let Some(ndjson) = &mut ndjson {
+            for repo in res {
+                ndjson.write(repo)?;
+            }
+        } else if opt.format == OutputFormat::Text && !to_files && !opt.compact {
+            show_result(
+                res,
+                opt.flag_owner_led,
+                color,
+                opt.short_names,
+                opt.show_counts,
+                !opt.no_align,
+                age_at(opt),
+            )
+        }
+        Ok(())
+    };
+    // Results written as they come are not needed later, unless snapshot is saved
+    let keep_streamed = !streamed || opt.snapshot.is_some();
+    let keep_computed = opt.snapshot.is_some();
+    let mut computed = vec![];
+
+    // Repos to always include that were not among the results so far
+    let mut missing: Vec<&FullName> = opt.always_include.iter().collect();
+
+    // Phases that could be told apart, with how long they took
+    let mut phases: Vec<(&str, Duration)> = vec![];
+
+    let mut res = if opt.threshold == Threshold::Auto {
+        // Threshold depends on all results, nothing can be reported before they are there
+        eprintln!("Querying for repos and calculating bus factor for all of them...");
+        let mut all = vec![];
+        if opt.project_count > STREAMING_COUNT && opt.factor_limit.is_none() {
+            let start = Instant::now();
+            api.for_each_batch(&repo_query, query, |batch, expected| {
+                found = expected as u64;
+                all.extend(batch)
+            })
+            .await?;
+            phases.push(("search and bus factor", start.elapsed()));
+        } else {
+            let start = Instant::now();
+            let repos = api.get_repos(&repo_query).await?;
+            phases.push(("search", start.elapsed()));
+            found = repos.found as u64;
+
+            let start = Instant::now();
+            all = repos_bus_factor(api, opt, &repos, query).await?;
+            phases.push(("bus factor", start.elapsed()));
+        }
 
-    println!("Querying for repos...");
-    let repos = api
-        .get_repos(&RepoQuery {
-            language: &opt.language,
-            count: opt.project_count,
-            delay_sec: opt.delay_sec
+        let threshold = opt.threshold.resolve(&all);
+        eprintln!("Threshold taken from the results: {:.2}", threshold);
+        metrics.add_results(&all, threshold);
+        mark_always_included(&mut all, &mut missing);
+        if keep_computed {
+            computed = all.clone();
+        }
+
+        let mut res: Vec<BusFactor> = all
+            .into_iter()
+            .filter(|repo| is_reported(repo, threshold))
+            .collect();
+
+        print_elapsed(label, opt.project_count, now);
+        limit_to_top(&mut res, opt.top);
+        show(&mut res)?;
+        res
+    } else if opt.project_count > STREAMING_COUNT && opt.factor_limit.is_none() {
+        // Results are shown as soon as batch is ready
+        eprintln!("Streaming repos and calculating bus factor for them...");
+        let mut res = vec![];
+        let mut written = Ok(());
+        let mut announced = false;
+        api.for_each_batch(&repo_query, query, |mut batch, expected| {
+            let requested = GithubApi::expected_repos(None, opt.project_count);
+            if !announced && expected < requested && expected > 0 {
+                eprintln!("Search matches only {} repos", expected);
+            }
+            announced = true;
+            found = expected as u64;
+            metrics.add_results(&batch, query.bus_threshold);
+            mark_always_included(&mut batch, &mut missing);
+            if keep_computed {
+                computed.extend(batch.iter().cloned());
+            }
+            let mut batch: Vec<BusFactor> = batch
+                .into_iter()
+                .filter(|repo| is_reported(repo, query.bus_threshold))
+                .collect();
+            if opt.top.is_some() {
+                // Riskiest ones are known only once all batches are there
+                res.extend(batch);
+                return;
+            }
+            if written.is_ok() {
+                written = show(&mut batch);
+            }
+            if keep_streamed {
+                res.extend(batch);
+            }
         })
         .await?;
+        written?;
+        // Next page is searched after the batch is done, both phases are interleaved
+        phases.push(("search and bus factor", now.elapsed()));
+
+        print_elapsed(label, opt.project_count, now);
+        if opt.top.is_some() {
+            limit_to_top(&mut res, opt.top);
+            show(&mut res)?;
+        }
+        res
+    } else {
+        eprintln!("Querying for repos...");
+        let start = Instant::now();
+        let repos = api.get_repos(&repo_query).await?;
+        phases.push(("search", start.elapsed()));
+        found = repos.found as u64;
+
+        eprintln!("Calculating bus factor for {} repos...", repos.items.len());
+        let start = Instant::now();
+        let mut all = repos_bus_factor(api, opt, &repos, query).await?;
+        metrics.add_results(&all, query.bus_threshold);
+        mark_always_included(&mut all, &mut missing);
+        if keep_computed {
+            computed = all.clone();
+        }
+        let mut res: Vec<BusFactor> = all
+            .into_iter()
+            .filter(|repo| is_reported(repo, query.bus_threshold))
+            .collect();
+        phases.push(("bus factor", start.elapsed()));
+
+        print_elapsed(label, opt.project_count, now);
+        limit_to_top(&mut res, opt.top);
+        show(&mut res)?;
+        res
+    };
+
+    // Empty report is not an error, nothing matched
+    if found == 0 && opt.project_count > 0 {
+        eprintln!("0 repositories matched your query for {}", label);
+    }
+
+    if !missing.is_empty() {
+        eprintln!("Fetching repos to always include, the search did not find them...");
+        let names: Vec<FullName> = missing.into_iter().cloned().collect();
+        let repos = api.get_repos_by_name(&names).await?;
+
+        // Asked for by name, filters of the query do not apply to them
+        let forced_query = BusFactorQuery {
+            include_forks: true,
+            min_contributors: 0,
+            ..query.clone()
+        };
+        let mut forced = api.get_repos_bus_factor(&repos, &forced_query).await?;
+        metrics.add_results(&forced, query.bus_threshold);
+        if forced.len() < names.len() {
+            warn!("Some repos to always include have no contributions to consider");
+        }
+
+        for repo in &mut forced {
+            repo.always_included = true;
+        }
+        if keep_computed {
+            computed.extend(forced.iter().cloned());
+        }
+        show(&mut forced)?;
+        if keep_streamed {
+            res.extend(forced);
+        }
+    }
+
+    if let Some(writer) = writer {
+        writer.finish()?;
+    }
+
+    if opt.weight_by_usage {
+        eprintln!("Fetching usage of reported repos...");
+        let start = Instant::now();
+        api.add_usage(&mut res).await;
+        phases.push(("usage", start.elapsed()));
+
+        if opt.format == OutputFormat::Text && !opt.compact {
+            show_summary(&res);
+        }
+    }
+
+    if opt.trace_timing {
+        show_timing(label, &phases, &api.take_repo_latencies());
+    }
+
+    // Counted on every path, the run is partial then
+    metrics.repos_skipped = api.take_skipped_repos();
+    if opt.compact {
+        let summary = CompactSummary::new(label, &metrics, metrics.repos_skipped, now.elapsed());
+        show_compact(&summary, opt.format)?;
+    }
+    run_metrics.merge(metrics);
+
+    Ok(LanguageRun {
+        results: res,
+        computed,
+        found,
+    })
+}
+
+/// Prints the one line summary, machine readable unless the format is text or html
+fn show_compact(summary: &CompactSummary, format: OutputFormat) -> Result<(), SendError> {
+    match format {
+        OutputFormat::Text | OutputFormat::Html => println!("{}", summary.to_text()),
+        OutputFormat::Json | OutputFormat::JsonArray | OutputFormat::NdjsonReport => {
+            println!("{}", serde_json::to_string(summary)?)
+        }
+        OutputFormat::Csv | OutputFormat::Tsv => {
+            println!(
+                "{}",
+                summary.to_delimited(format.delimiter().unwrap_or_default())
+            )
+        }
+    }
+
+    Ok(())
+}
 
-    println!("Calculating bus factor for them...");
-    let res = api
-        .get_repos_bus_factor(
-            &repos,
-            &BusFactorQuery {
-                bus_threshold: 0.75,
-                users_to_consider: 25,
-                delay_sec: opt.delay_sec,
-            },
+/// Report of the language, with the search query its results come from
+fn language_report(
+    opt: &Opt,
+    language: Option<&str>,
+    run: LanguageRun,
+    warnings: Vec<RunWarning>,
+) -> Report {
+    let mut report = Report {
+        warnings,
+        ..Report::new(
+            language.unwrap_or(ANY_LANGUAGE),
+            opt.project_count,
+            run.results,
         )
-        .await?;
+    };
+    report.meta.search_query = GithubApi::search_query(&repo_query(opt, language)).ok();
+    report.meta.total_count = Some(run.found);
+    report
+}
 
-    println!(
-        "For lang {}, count {} it took {}ms",
-        opt.language,
-        opt.project_count,
-        now.elapsed().as_millis(),
+/// Bus factor of the repos, only of --factor-limit most starred ones if it's given
+async fn repos_bus_factor(
+    api: &GithubApi,
+    opt: &Opt,
+    repos: &Repos,
+    query: &BusFactorQuery,
+) -> Result<Vec<BusFactor>, SendError> {
+    match opt.factor_limit {
+        Some(limit) => api.get_top_repos_bus_factor(repos, query, limit).await,
+        None => api.get_repos_bus_factor(repos, query).await,
+    }
+}
+
+/// Marks results asked for with --always-include, those are no longer missing
+fn mark_always_included(results: &mut [BusFactor], missing: &mut Vec<&FullName>) {
+    for repo in results {
+        let len = missing.len();
+        missing.retain(|name| !name.matches(&repo.full_name));
+        repo.always_included |= missing.len() < len;
+    }
+}
+
+/// Runs all languages from the file, --languages-at-once of them at the same time if reports
+/// go to files, one after another otherwise, so reports on the output are not interleaved.
+/// Starts of languages are paced so the search rate limit is not exceeded, requests of all
+/// of them share the client, with its concurrency and rate limits.
+/// Languages not known to the search are skipped.
+async fn run_languages(
+    api: &GithubApi,
+    opt: &Opt,
+    query: &BusFactorQuery,
+    languages: &[String],
+    run_metrics: &mut RunMetrics,
+) -> Result<BatchReport, SendError> {
+    let mut batch = BatchReport::default();
+
+    // Timings are shown for each language as it ends, they would be interleaved too
+    let at_once = match opt.output_dir {
+        Some(_) if !opt.trace_timing => opt.languages_at_once.max(1),
+        _ => 1,
+    };
+    let searches = RateLimiter::every(GithubApi::search_pacing(opt.project_count));
+    let mut runs = futures::stream::iter(languages)
+        .map(|language| {
+            let searches = &searches;
+            async move {
+                searches.acquire().await;
+                let api = api.scoped();
+                let mut metrics = RunMetrics::default();
+                let res = run_language(&api, opt, query, Some(language), &mut metrics).await;
+
+                (language, res, metrics, api.take_warnings())
+            }
+        })
+        .buffered(at_once);
+
+    while let Some((language, res, metrics, warnings)) = runs.next().await {
+        run_metrics.merge(metrics);
+        // Warnings of a skipped language are only logged
+        match res {
+            Ok(res) => {
+                batch.languages.insert(
+                    language.to_string(),
+                    language_report(opt, Some(language), res, warnings),
+                );
+            }
+            // Search responds with unprocessable entity for unknown languages
+            Err(e)
+                if e.downcast_ref::<ResponseError>()
+                    .and_then(ResponseError::status)
+                    == Some(StatusCode::UNPROCESSABLE_ENTITY) =>
+            {
+                warn!("Skipping language {}: {}", language, e);
+                batch.skipped.push(language.to_string());
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(batch)
+}
+
+/// Picks concurrency and rate of the run from the rate limit left and the cost of the run,
+/// see --profile. Costs a search request for one repo, the rate limit is free
+async fn apply_profile(opt: &Opt, config: &mut TransportConfig) -> Result<(), SendError> {
+    // Search quota renews every minute, plan knows only the hourly core one
+    if opt.share_basis == ShareBasis::PullRequests {
+        return Err(Box::new(InvalidQueryError::new(
+            "--profile can't estimate --share-basis pull-requests, it's paced by the search quota",
+        )));
+    }
+
+    let client = GithubClient::with_config(&get_token(&opt.token_path[0]), config)?;
+    let api = GithubApi::with_client(with_token_pool(client, opt)?);
+    let api = match &opt.api_base_for_search {
+        Some(base) => api.with_search_base(base),
+        None => api,
+    };
+
+    let language = if opt.language_any {
+        None
+    } else {
+        opt.language.as_deref()
+    };
+    let repos = api.count_repos(&repo_query(opt, language)).await?;
+    let query = bus_factor_query(&api, opt).await?;
+    let core = api.get_rate_limit().await?;
+
+    // Every token of the pool is taken to have as much left as the one that was asked
+    let tokens = opt.token_path.len() as u64;
+    let budget = Budget {
+        limit: core.limit * tokens,
+        remaining: core.remaining * tokens,
+        reset_in: Duration::from_secs((core.reset - Utc::now().timestamp()).max(0) as u64),
+    };
+    // Past the limit repos are only listed
+    let repos = match opt.factor_limit {
+        Some(limit) => repos.min(limit.min(u32::MAX as usize) as u32),
+        None => repos,
+    };
+    let cost = RunCost {
+        repos,
+        requests_per_repo: query.requests_per_repo(),
+    };
+    let profile = profile::plan(&cost, &budget);
+
+    eprintln!(
+        "Profile: about {} requests for {} repos, {} of {} left, reset in {}s",
+        cost.total(),
+        repos,
+        budget.remaining,
+        budget.limit,
+        budget.reset_in.as_secs()
+    );
+    eprintln!(
+        "Profile: concurrency {}, rate {:.2} requests per second",
+        profile.concurrency, profile.rate
     );
+    if !profile.fits {
+        eprintln!(
+            "Profile: rate limit left is not enough for the run, it's paced to go on after the reset. \
+             --project-count {} would fit",
+            profile.max_repos
+        );
+    }
 
-    show_result(&res);
+    config.rate = Some(profile.rate);
+    config.adaptive = Some(AdaptiveConfig {
+        initial: profile.concurrency,
+        min: opt.min_concurrency.min(profile.concurrency),
+        max: profile.concurrency,
+        increase: opt.concurrency_increase,
+        decrease: opt.concurrency_decrease,
+    });
 
     Ok(())
 }
 
+/// Client that uses all tokens in turns, if --token-path is given more than once
+fn with_token_pool(client: GithubClient, opt: &Opt) -> Result<GithubClient, SendError> {
+    if opt.token_path.len() < 2 {
+        return Ok(client);
+    }
+
+    let tokens = opt.token_path.iter().map(|path| get_token(path)).collect();
+    Ok(client.with_token_provider(Arc::new(TokenPool::new(tokens)?)))
+}
+
+/// Builds configuration of requests from the arguments
+fn transport_config(opt: &Opt) -> TransportConfig {
+    TransportConfig {
+        proxy: opt.proxy.clone(),
+        ca_cert: opt.ca_cert.clone(),
+        accept_invalid_certs: opt.danger_accept_invalid_certs,
+        timeout: opt.timeout_sec,
+        retry: RetryPolicy {
+            max_retries: opt.max_retries,
+            jitter: opt.retry_jitter,
+            ..RetryPolicy::default()
+        },
+        headers: opt.headers.iter().cloned().collect(),
+        record_dir: opt.record.clone(),
+        rate: opt.rate,
+        seed: opt.seed,
+        adaptive: if opt.adaptive_concurrency {
+            Some(AdaptiveConfig {
+                initial: opt.concurrency,
+                min: opt.min_concurrency,
+                max: opt.max_concurrency,
+                increase: opt.concurrency_increase,
+                decrease: opt.concurrency_decrease,
+            })
+        } else {
+            None
+        },
+        ..TransportConfig::default()
+    }
+}
+
+/// Checks connection to the api, returns exit code
+async fn check_connection(opt: &Opt) -> i32 {
+    // Reaching the api does not need token, but it's checked as well, if given
+    let token = opt
+        .token_path
+        .first()
+        .and_then(|path| find_token(path))
+        .unwrap_or_default();
+
+    let res = match GithubApi::with_config(&token, &transport_config(opt)) {
+        Ok(api) => api.check_connection().await,
+        Err(e) => Err(e),
+    };
+
+    match res {
+        Ok(elapsed) => {
+            println!("Connected to github api in {}ms", elapsed.as_millis());
+            exit_code::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("Failed to connect to github api: {}", e);
+            exit_code::CONNECT_FAILED
+        }
+    }
+}
+
+/// Writes report in formats that need all results, text, json-array and ndjson-report
+/// are written as they come
+fn show_report(report: &Report, opt: &Opt) -> Result<(), SendError> {
+    match opt.format {
+        OutputFormat::Text | OutputFormat::JsonArray | OutputFormat::NdjsonReport => {}
+        OutputFormat::Json => println!("{}", report::to_json(report, opt.pretty)?),
+        OutputFormat::Csv | OutputFormat::Tsv => {
+            let delimiter = opt.format.delimiter().unwrap_or_default();
+            println!("{}", report.meta.comment());
+            print!(
+                "{}",
+                report::results_to_delimited(std::iter::once(report), delimiter)
+            )
+        }
+        OutputFormat::Html => print!("{}", report::results_to_html(std::iter::once(report))),
+    }
+
+    Ok(())
+}
+
+/// Calculates bus factor of the local clone, with the same computation as for github repos.
+/// All authors are considered, history of the clone is complete
+fn run_local(opt: &Opt, path: &Path) -> Result<(), SendError> {
+    let contributions = local_git::contributions(path)?;
+    let share = share::compute_repo_share(&contributions, usize::MAX, None, ShareSource::LocalGit)
+        .ok_or_else(|| {
+            InvalidQueryError::new(&format!("No commits to consider in {}", path.display()))
+        })?;
+
+    // Canonical path has a name even for "."
+    let name = fs::canonicalize(path)?
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let res = vec![BusFactor::from_share(
+        &name,
+        opt.stars.unwrap_or_default(),
+        share,
+    )];
+
+    match opt.format {
+        OutputFormat::Text => show_result(
+            &res,
+            false,
+            opt.color.enabled(io::stdout().is_terminal()),
+            // Local clone has no owner
+            true,
+            opt.show_counts,
+            !opt.no_align,
+            None,
+        ),
+        OutputFormat::JsonArray => {
+            let mut writer = JsonArrayWriter::new(io::stdout())?;
+            writer.write(&res[0])?;
+            writer.finish()?;
+        }
+        OutputFormat::NdjsonReport => {
+            NdjsonWriter::new(io::stdout(), report::run_id(), "local").write(&res[0])?
+        }
+        _ => show_report(&Report::new("local", 1, res), opt)?,
+    }
+
+    Ok(())
+}
+
+/// Writes metrics of the run to --metrics-file, if given
+fn save_metrics(
+    api: &GithubApi,
+    opt: &Opt,
+    metrics: RunMetrics,
+    start: Instant,
+) -> Result<(), SendError> {
+    if let Some(path) = &opt.metrics_file {
+        let metrics = RunMetrics {
+            requests: api.request_stats(),
+            duration: start.elapsed(),
+            ..metrics
+        };
+        metrics
+            .save(path)
+            .map_err(|e| format!("Can't write metrics to {}: {}", path.display(), e))?;
+    }
+
+    Ok(())
+}
+
+/// Does the actual work, returns false if it succeeded only partially, like when repos failed.
+/// Progress gets events of the api, if given
+async fn run(opt: &Opt, progress: Option<Arc<dyn ProgressListener>>) -> Result<bool, SendError> {
+    // Either of them is enough, so it can't be told to clap
+    if opt.since_stars && opt.snapshot.is_none() && opt.diff_against.is_none() {
+        return Err(Box::new(InvalidQueryError::new(
+            "--since-stars needs --snapshot or --diff-against to compare with",
+        )));
+    }
+
+    if let Some(path) = &opt.local_repo {
+        run_local(opt, path)?;
+        return Ok(true);
+    }
+
+    let diff_against = match &opt.diff_against {
+        // Diff is a text listing, it would break other formats
+        Some(_) if opt.format != OutputFormat::Text => {
+            return Err(Box::new(InvalidQueryError::new(
+                "--diff-against shows the changes as text, it can be used only with text format",
+            )))
+        }
+        // Loaded first, so a bad file does not cost the whole run
+        Some(path) => Some(snapshot::load_report(path)?),
+        None => None,
+    };
+    if let (Some(previous), Some(path)) = (&diff_against, &opt.current_report) {
+        let current = snapshot::load_report(path)?;
+        print_diff(
+            "report",
+            previous,
+            &current,
+            &previous.results,
+            &current.results,
+            opt,
+        );
+        return Ok(true);
+    }
+
+    let start = Instant::now();
+    let mut config = transport_config(opt);
+    if opt.profile {
+        apply_profile(opt, &mut config).await?;
+    }
+    let client = match &opt.record {
+        Some(dir) if opt.offline => GithubClient::offline(dir, &config)?,
+        Some(dir) if opt.record_new => with_token_pool(
+            GithubClient::replaying(dir, &get_token(&opt.token_path[0]), &config)?,
+            opt,
+        )?,
+        _ => with_token_pool(
+            GithubClient::with_config(&get_token(&opt.token_path[0]), &config)?,
+            opt,
+        )?,
+    };
+    let api = GithubApi::with_client(match progress {
+        Some(listener) => client.with_progress(listener),
+        None => client,
+    });
+    let api = match &opt.contributors_endpoint_override {
+        Some(base) => api.with_contributors_base(base),
+        None => api,
+    };
+    let api = match &opt.api_base_for_search {
+        Some(base) => api.with_search_base(base),
+        None => api,
+    };
+    let api = if opt.trace_timing {
+        api.with_timing()
+    } else {
+        api
+    };
+    let api = match &opt.dump_raw {
+        Some(dir) => api.with_raw_dump(dir),
+        None => api,
+    };
+    let query = bus_factor_query(&api, opt).await?;
+
+    if opt.output_dir.is_some() && opt.language_file.is_none() {
+        warn!("--output-dir is used only with --language-file, ignoring it");
+    }
+
+    let language = match &opt.language_file {
+        Some(path) => {
+            if opt.format == OutputFormat::JsonArray {
+                // Report of the batch is keyed by language, it's not an array
+                return Err(Box::new(InvalidQueryError::new(
+                    "Json-array format is not supported with --language-file",
+                )));
+            }
+
+            let languages = parse_languages(&fs::read_to_string(path)?);
+            let mut metrics = RunMetrics::default();
+            let batch = run_languages(&api, opt, &query, &languages, &mut metrics).await?;
+            let complete = batch.skipped.is_empty() && metrics.repos_skipped == 0;
+            save_metrics(&api, opt, metrics, start)?;
+
+            if let Some(dir) = &opt.output_dir {
+                // Summary still goes to the output
+                write_language_files(dir, &batch, opt)?;
+                eprintln!("Reports written to {}", dir.display());
+            } else if opt.compact {
+                // Line of every language is written already
+                return Ok(complete);
+            } else if opt.format == OutputFormat::Json {
+                println!("{}", report::to_json(&batch, opt.pretty)?);
+                return Ok(complete);
+            } else if opt.format == OutputFormat::NdjsonReport {
+                // Records of every language are written already
+                return Ok(complete);
+            } else if let Some(delimiter) = opt.format.delimiter() {
+                if let Some(first) = batch.languages.values().next() {
+                    println!("{}", first.meta.comment());
+                }
+                print!(
+                    "{}",
+                    report::results_to_delimited(batch.languages.values(), delimiter)
+                );
+                return Ok(complete);
+            } else if opt.format == OutputFormat::Html {
+                print!("{}", report::results_to_html(batch.languages.values()));
+                return Ok(complete);
+            }
+
+            if opt.compact {
+                return Ok(complete);
+            }
+
+            println!("Summary:");
+            for (language, report) in &batch.languages {
+                println!("lang: {:20} reported: {}", language, report.results.len());
+            }
+            for language in &batch.skipped {
+                println!("lang: {:20} skipped, unknown to the search", language);
+            }
+
+            if let Some(top) = opt.facets {
+                let results: Vec<BusFactor> = batch
+                    .languages
+                    .into_values()
+                    .flat_map(|report| report.results)
+                    .collect();
+                show_facets(&results, top);
+            }
+
+            return Ok(complete);
+        }
+        None if opt.language_any => None,
+        None => opt.language.as_deref(),
+    };
+
+    if opt.repos_only {
+        if opt.format == OutputFormat::Html {
+            return Err(Box::new(InvalidQueryError::new(
+                "Html format is not supported with --repos-only",
+            )));
+        }
+        if opt.format == OutputFormat::NdjsonReport {
+            // Records are results of bus factor
+            return Err(Box::new(InvalidQueryError::new(
+                "Ndjson-report format is not supported with --repos-only",
+            )));
+        }
+
+        let repos = api.get_repos(&repo_query(opt, language)).await?;
+
+        match opt.format {
+            OutputFormat::Text => show_repos(&repos.items, opt.short_names, !opt.no_align),
+            OutputFormat::Json | OutputFormat::JsonArray => {
+                println!("{}", report::to_json(&repos.items, opt.pretty)?)
+            }
+            OutputFormat::Csv | OutputFormat::Tsv => {
+                let delimiter = opt.format.delimiter().unwrap_or_default();
+                print!("{}", report::repos_to_delimited(&repos.items, delimiter))
+            }
+            OutputFormat::Html | OutputFormat::NdjsonReport => {}
+        }
+
+        return Ok(true);
+    }
+
+    let mut metrics = RunMetrics::default();
+    let mut run = run_language(&api, opt, &query, language, &mut metrics).await?;
+    let computed = std::mem::take(&mut run.computed);
+    let complete = metrics.repos_skipped == 0;
+    save_metrics(&api, opt, metrics, start)?;
+    let report = language_report(opt, language, run, api.take_warnings());
+
+    if !opt.compact {
+        show_report(&report, opt)?;
+
+        if let (Some(top), OutputFormat::Text) = (opt.facets, opt.format) {
+            show_facets(&report.results, top);
+        }
+    }
+
+    if let Some(previous) = &diff_against {
+        print_diff(
+            "report",
+            previous,
+            &report,
+            &previous.results,
+            &report.results,
+            opt,
+        );
+    }
+
+    if let Some(path) = &opt.snapshot {
+        let current = Snapshot { report, computed };
+        match snapshot::load(path)? {
+            Some(previous) => print_diff(
+                "snapshot",
+                &previous.report,
+                &current.report,
+                previous.results(),
+                current.results(),
+                opt,
+            ),
+            None => eprintln!("No previous snapshot at {}, creating one", path.display()),
+        }
+
+        snapshot::save(path, &current)?;
+    }
+
+    Ok(complete)
+}
+
+/// Shows what changed between results of the previous and current report, with --since-stars
+/// fastest growing repos first.
+/// Differences of the reports themselves, like of versions, are noted on stderr. So is the listing
+/// unless the output is text, it would break other formats, or the one line summary of --compact
+fn print_diff(
+    what: &str,
+    previous: &Report,
+    current: &Report,
+    previous_results: &[BusFactor],
+    current_results: &[BusFactor],
+    opt: &Opt,
+) {
+    for note in snapshot::mismatches(previous, current) {
+        eprintln!("{}", note);
+    }
+
+    let mut diffs = snapshot::diff(previous_results, current_results);
+    if opt.since_stars {
+        // Repos not present in both runs have no delta, they go last
+        diffs.sort_by_key(|diff| std::cmp::Reverse(diff.star_delta()));
+    }
+
+    let text = snapshot::diff_to_text(what, previous, &diffs, opt.since_stars);
+    if opt.format == OutputFormat::Text && !opt.compact {
+        print!("{}", text)
+    } else {
+        eprint!("{}", text)
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    env_logger::init();
+
+    let opt = Opt::from_args();
+
+    if opt.connect_only {
+        std::process::exit(check_connection(&opt).await);
+    }
+
+    let progress = match &opt.progress_to {
+        Some(path) => match fs::File::create(path) {
+            Ok(file) => Some(Arc::new(ProgressWriter::new(file))),
+            Err(e) => {
+                eprintln!("Error: Can't write progress to {}: {}", path.display(), e);
+                std::process::exit(exit_code::of(&e));
+            }
+        },
+        None => None,
+    };
+    let start = Instant::now();
+    if let Some(progress) = &progress {
+        progress.start(opt.project_count);
+    }
+
+    let res = run(
+        &opt,
+        progress
+            .clone()
+            .map(|progress| progress as Arc<dyn ProgressListener>),
+    )
+    .await;
+    if let Some(progress) = &progress {
+        progress.finish(res.is_ok(), start.elapsed());
+    }
+
+    if let Err(e) = &res {
+        eprintln!("Error: {}", e);
+    }
+
+    std::process::exit(exit_code::of_run(&res));
+}
+
 #[cfg(test)]
-/// Integration tests, use actual Github API
+/// Integration tests, by default use responses recorded from Github API in tests/fixtures.
+/// Each test has also live variant, that hits actual API, run them with:
+/// cargo test -- --ignored
 mod tests {
-    use std::{collections::BTreeSet, fs, path::PathBuf};
+    use std::{
+        collections::{BTreeSet, HashMap},
+        fs,
+        path::PathBuf,
+        sync::Arc,
+    };
 
-    use crate::api_errors::{InvalidQueryError, ResponseError};
+    use bus_factor::{
+        api_errors::{InvalidQueryError, ResponseError},
+        github_api::UserShare,
+        github_client::GithubClient,
+        replay::{Fixture, ReplayClient},
+        transport::{RawResponse, Transport},
+    };
+    use futures::future::BoxFuture;
+    use reqwest::header::HeaderMap;
 
     use super::*;
 
+    /// Generates test functions for each test, one using fixtures, second one live
+    macro_rules! replay_and_live {
+        ($($name:ident),*) => {
+            $(
+                mod $name {
+                    #[tokio::test]
+                    async fn replay() {
+                        super::$name(super::replay_api()).await
+                    }
+
+                    #[tokio::test]
+                    #[ignore]
+                    async fn live() {
+                        super::$name(super::live_api()).await
+                    }
+                }
+            )*
+        };
+    }
+
+    #[test]
+    fn errors_have_distinct_exit_codes() {
+        use bus_factor::api_errors::{AuthError, PermissionError, RateLimitError};
+
+        let code = |err: SendError| exit_code::of(err.as_ref());
+
+        assert_eq!(
+            code(Box::new(InvalidQueryError::new(""))),
+            exit_code::INVALID_QUERY
+        );
+        assert_eq!(code(Box::new(AuthError::new(""))), exit_code::AUTH);
+        assert_eq!(
+            code(Box::new(PermissionError::new(""))),
+            exit_code::PERMISSION
+        );
+        assert_eq!(
+            code(Box::new(RateLimitError::new("", Duration::ZERO))),
+            exit_code::RATE_LIMIT
+        );
+        assert_eq!(
+            code(Box::new(ResponseError::with_status(
+                StatusCode::NOT_FOUND,
+                ""
+            ))),
+            exit_code::OTHER
+        );
+        assert_eq!(
+            code(Box::new(bus_factor::api_errors::ShortfallError::new(""))),
+            exit_code::SHORTFALL
+        );
+        assert_eq!(code("something else".into()), exit_code::OTHER);
+    }
+
+    /// Finds a single repo with more contributors than fit on a page, remembering endpoints.
+    /// Contributions go down from 1000 across the pages, as github lists them
+    struct HugeRepoTransport {
+        sent: std::sync::Mutex<Vec<String>>,
+    }
+
+    impl HugeRepoTransport {
+        fn api() -> (GithubApi, Arc<HugeRepoTransport>) {
+            let transport = Arc::new(HugeRepoTransport {
+                sent: Default::default(),
+            });
+            let api = GithubApi::with_client(GithubClient::with_transport(
+                "",
+                &TransportConfig::default(),
+                transport.clone(),
+            ));
+            (api, transport)
+        }
+
+        /// Contributor pages requested
+        fn pages(&self) -> usize {
+            let sent = self.sent.lock().unwrap();
+            sent.iter()
+                .filter(|endpoint| endpoint.contains("/contributors"))
+                .count()
+        }
+    }
+
+    impl Transport for HugeRepoTransport {
+        fn get<'a>(
+            &'a self,
+            endpoint: &'a str,
+            _headers: HeaderMap,
+        ) -> BoxFuture<'a, Result<RawResponse, SendError>> {
+            self.sent.lock().unwrap().push(endpoint.to_string());
+
+            let body = if endpoint.contains("search/repositories") {
+                serde_json::json!({
+                    "total_count": 1,
+                    "incomplete_results": false,
+                    "items": [{
+                        "id": 1,
+                        "name": "huge",
+                        "full_name": "o/huge",
+                        "contributors_url": "https://api.github.com/repos/o/huge/contributors",
+                        "stargazers_count": 1,
+                        "language": "rust"
+                    }]
+                })
+                .to_string()
+            } else {
+                let page: u64 = endpoint
+                    .split("page=")
+                    .nth(2)
+                    .and_then(|page| page.parse().ok())
+                    .unwrap_or(1);
+                let contributors: Vec<serde_json::Value> = (0..100)
+                    .map(|idx| {
+                        serde_json::json!({
+                            "login": format!("u{}-{}", page, idx),
+                            "contributions": 1000 - (page - 1) * 100 - idx
+                        })
+                    })
+                    .collect();
+                serde_json::Value::from(contributors).to_string()
+            };
+
+            Box::pin(async move {
+                Ok(RawResponse {
+                    status: StatusCode::OK,
+                    headers: HeaderMap::new(),
+                    body,
+                })
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn users_to_consider_spans_contributor_pages() {
+        let (api, transport) = HugeRepoTransport::api();
+        let args = "bus_factor -l rust -p 1 --only-below --format json --users-to-consider 250";
+        let opt = Opt::from_iter(args.split(' '));
+        let query = bus_factor_query(&api, &opt).await.unwrap();
+
+        let res = run_language(&api, &opt, &query, Some("rust"), &mut RunMetrics::default())
+            .await
+            .unwrap();
+
+        assert_eq!(transport.pages(), 3);
+        assert_eq!(res.results.len(), 1);
+        assert_eq!(res.results[0].leader.user_name, "u1-0");
+        // Top 250 of them, from 1000 down to 751
+        let total: u64 = (751..=1000).sum();
+        assert!((res.results[0].leader.bus_factor - 1000.0 / total as f64).abs() < 1e-9);
+
+        for users in ["0", "501"] {
+            let opt = Opt::from_iter(&[
+                "bus_factor",
+                "-l",
+                "rust",
+                "-p",
+                "1",
+                "--users-to-consider",
+                users,
+            ]);
+            let res = bus_factor_query(&api, &opt).await;
+            assert!(
+                matches!(res, Err(e) if e.is::<InvalidQueryError>()),
+                "{}",
+                users
+            );
+        }
+    }
+
+    #[test]
+    fn connect_only_needs_no_search_parameters() {
+        let opt = Opt::from_iter(&["bus_factor", "--connect-only"]);
+        assert!(opt.connect_only);
+
+        assert!(Opt::from_iter_safe(&["bus_factor", "--language", "rust"]).is_err());
+    }
+
+    #[test]
+    fn language_files_are_written_to_dir() {
+        let dir = std::env::temp_dir().join(format!("bus_factor_out_{}", std::process::id()));
+        let opt = Opt::from_iter(&[
+            "bus_factor",
+            "--language-file",
+            "languages",
+            "--project-count",
+            "1",
+            "--format",
+            "csv",
+            "--output-dir",
+            dir.to_str().unwrap(),
+        ]);
+
+        let mut batch = BatchReport::default();
+        for language in ["rust", "c++"].iter() {
+            batch
+                .languages
+                .insert(language.to_string(), Report::new(language, 1, vec![]));
+        }
+
+        write_language_files(&dir, &batch, &opt).unwrap();
+        assert!(dir.join("rust.csv").is_file());
+        assert!(dir.join("c++.csv").is_file());
+
+        // Directory can't be created where file is
+        let err = write_language_files(&dir.join("rust.csv"), &batch, &opt).unwrap_err();
+        assert!(err.to_string().contains("rust.csv"), "{}", err);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Finds a single repo named like the searched language, remembering when each request came
+    struct LanguagesTransport {
+        sent: std::sync::Mutex<Vec<tokio::time::Instant>>,
+    }
+
+    impl Transport for LanguagesTransport {
+        fn get<'a>(
+            &'a self,
+            endpoint: &'a str,
+            _headers: HeaderMap,
+        ) -> BoxFuture<'a, Result<RawResponse, SendError>> {
+            self.sent.lock().unwrap().push(tokio::time::Instant::now());
+
+            let body = match endpoint.split("language:").nth(1) {
+                Some(rest) => {
+                    let language = rest.split('&').next().unwrap_or_default();
+                    serde_json::json!({
+                        "total_count": 1,
+                        "incomplete_results": false,
+                        "items": [{
+                            "id": 1,
+                            "name": language,
+                            "full_name": format!("o/{}", language),
+                            "contributors_url": format!("https://api.github.com/repos/o/{}/contributors", language),
+                            "stargazers_count": 1,
+                            "language": language
+                        }]
+                    })
+                    .to_string()
+                }
+                None => {
+                    r#"[{"login": "a", "contributions": 10}, {"login": "b", "contributions": 1}]"#
+                        .to_string()
+                }
+            };
+
+            Box::pin(async move {
+                Ok(RawResponse {
+                    status: StatusCode::OK,
+                    headers: HeaderMap::new(),
+                    body,
+                })
+            })
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn languages_at_once_keep_the_rate() {
+        let transport = Arc::new(LanguagesTransport {
+            sent: Default::default(),
+        });
+        // Slower than search pacing, so languages overlap
+        let config = TransportConfig {
+            rate: Some(0.4),
+            ..TransportConfig::default()
+        };
+        let api =
+            GithubApi::with_client(GithubClient::with_transport("", &config, transport.clone()));
+        let opt = Opt::from_iter(&[
+            "bus_factor",
+            "--language-file",
+            "languages",
+            "--project-count",
+            "1",
+            "--output-dir",
+            "out",
+            "--languages-at-once",
+            "3",
+        ]);
+        let query = bus_factor_query(&api, &opt).await.unwrap();
+        let languages: Vec<String> = ["a", "b", "c", "d"].iter().map(|l| l.to_string()).collect();
+
+        let batch = run_languages(&api, &opt, &query, &languages, &mut RunMetrics::default())
+            .await
+            .unwrap();
+
+        assert_eq!(batch.languages.len(), 4);
+        for (language, report) in &batch.languages {
+            assert_eq!(report.meta.language, *language);
+            assert_eq!(report.results.len(), 1);
+            assert_eq!(report.results[0].repo_name, *language);
+        }
+
+        let mut sent = transport.sent.lock().unwrap().clone();
+        sent.sort();
+        assert_eq!(sent.len(), 8);
+        for pair in sent.windows(2) {
+            assert!(
+                pair[1] - pair[0] >= Duration::from_millis(2500),
+                "{:?}",
+                pair[1] - pair[0]
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn contributor_pages_cap_truncates_the_share() {
+        let (api, transport) = HugeRepoTransport::api();
+        let args = "bus_factor -l rust -p 1 --threshold 0 --format json --users-to-consider 500 \
+                    --max-contributor-pages 2";
+        let opt = Opt::from_iter(args.split_whitespace());
+        let query = bus_factor_query(&api, &opt).await.unwrap();
+
+        let run = run_language(&api, &opt, &query, Some("rust"), &mut RunMetrics::default())
+            .await
+            .unwrap();
+
+        assert_eq!(transport.pages(), 2);
+        let repo = &run.results[0];
+        assert!(repo.approximate);
+        assert_eq!(
+            repo.total_considered_contributions,
+            Some((801..=1000).sum())
+        );
+
+        let warnings = api.take_warnings();
+        assert!(
+            warnings.iter().any(|warning| warning.category
+                == bus_factor::warnings::WarningCategory::ApproximateShare
+                && warning.message.contains("truncated at 2 pages")),
+            "{:?}",
+            warnings
+        );
+    }
+
+    #[tokio::test]
+    async fn nothing_matched_is_an_empty_report() {
+        // Search that matches nothing
+        let search = Fixture {
+            endpoint: concat!(
+                "https://api.github.com/search/repositories",
+                "?q=language:cobol+stars:>=100000&sort=stars&order=desc&per_page=10&page=1"
+            )
+            .to_string(),
+            status: 200,
+            body: serde_json::json!({"total_count": 0, "incomplete_results": false, "items": []}),
+            headers: HashMap::new(),
+        };
+        let api = GithubApi::with_client(GithubClient::with_transport(
+            "",
+            &TransportConfig::default(),
+            Arc::new(ReplayClient::new(vec![search])),
+        ));
+        let opt = Opt::from_iter(&[
+            "bus_factor",
+            "--language",
+            "cobol",
+            "--project-count",
+            "10",
+            "--min-stars",
+            "100000",
+            "--format",
+            "json",
+        ]);
+        let query = bus_factor_query(&api, &opt).await.unwrap();
+
+        let run = run_language(
+            &api,
+            &opt,
+            &query,
+            Some("cobol"),
+            &mut RunMetrics::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(run.found, 0);
+
+        let report = language_report(&opt, Some("cobol"), run, vec![]);
+        assert!(report.results.is_empty());
+        assert_eq!(report.meta.total_count, Some(0));
+
+        let json = serde_json::to_string(&report).unwrap();
+        assert!(json.contains(r#""total_count":0"#), "{}", json);
+        assert!(json.contains(r#""results":[]"#), "{}", json);
+    }
+
+    #[test]
+    fn invalid_enum_values_are_rejected() {
+        for (flag, value) in [
+            ("--color", "yes"),
+            ("--sort-by", "forks"),
+            ("--order", "up"),
+        ]
+        .iter()
+        {
+            assert!(Opt::from_iter_safe(&["bus_factor", "--connect-only", flag, value]).is_err());
+        }
+
+        let opt = Opt::from_iter(&["bus_factor", "--connect-only", "--sort-by", "share"]);
+        assert_eq!(
+            (opt.sort_by, opt.order),
+            (Some(SortBy::Share), SortOrder::Desc)
+        );
+    }
+
+    #[tokio::test]
+    async fn since_stars_needs_something_to_compare_with() {
+        let opt = Opt::from_iter(&[
+            "bus_factor",
+            "--language",
+            "rust",
+            "--project-count",
+            "1",
+            "--since-stars",
+        ]);
+        let err = run(&opt, None).await.unwrap_err();
+        assert!(err.is::<InvalidQueryError>(), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn skipped_repo_is_a_partial_success() {
+        let dir = std::env::temp_dir().join(format!("bus_factor_partial_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        // Contributors of the only repo found fail
+        let fixtures = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        for name in [
+            "search_c_per_page_1_page_1.json",
+            "contributors_torvalds_linux_per_page_25.json",
+        ] {
+            fs::copy(fixtures.join(name), dir.join(name)).unwrap();
+        }
+        let token = dir.join("token");
+        fs::write(&token, "token").unwrap();
+        let opt = Opt::from_iter(&[
+            "bus_factor",
+            "--language",
+            "C",
+            "--project-count",
+            "1",
+            "--record",
+            dir.to_str().unwrap(),
+            "--record-new",
+            "--token-path",
+            token.to_str().unwrap(),
+        ]);
+
+        let res = run(&opt, None).await;
+        let fail_fast = Opt {
+            fail_fast: true,
+            ..opt
+        };
+        let failed = run(&fail_fast, None).await;
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(exit_code::of_run(&res), exit_code::PARTIAL);
+        assert!(failed.is_err());
+    }
+
+    #[tokio::test]
+    async fn profile_is_not_picked_for_pull_requests() {
+        let opt = Opt::from_iter(&[
+            "bus_factor",
+            "--language",
+            "rust",
+            "--project-count",
+            "1",
+            "--profile",
+            "--share-basis",
+            "pull-requests",
+        ]);
+        let err = apply_profile(&opt, &mut transport_config(&opt))
+            .await
+            .unwrap_err();
+        assert!(err.is::<InvalidQueryError>(), "{}", err);
+    }
+
+    #[test]
+    fn invalid_durations_are_rejected() {
+        for flag in [
+            "--timeout-sec",
+            "--timeout-per-repo",
+            "--retry-failed-backoff-sec",
+        ] {
+            for value in ["-1", "NaN", "1s"] {
+                // Otherwise -1 is taken for a flag
+                let arg = format!("{}={}", flag, value);
+                let args = ["bus_factor", "--connect-only", &arg];
+                assert!(Opt::from_iter_safe(&args).is_err(), "{}", arg);
+            }
+        }
+
+        let opt = Opt::from_iter(&["bus_factor", "--connect-only", "--timeout-sec", "0.5"]);
+        assert_eq!(opt.timeout_sec, Some(Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn netrc_password_is_found_for_machine() {
+        let netrc = "machine example.com login a password wrong\n\
+                     machine api.github.com\n  login user\n  password secret\n\
+                     default login anonymous password guest\n";
+
+        assert_eq!(
+            netrc_password(netrc, "api.github.com").as_deref(),
+            Some("secret")
+        );
+        assert_eq!(netrc_password(netrc, "other.com").as_deref(), Some("guest"));
+        assert_eq!(netrc_password("machine a.com password p", "b.com"), None);
+    }
+
+    #[test]
+    fn always_included_repos_are_marked() {
+        let args = "bus_factor -l rust -p 5 --always-include a/one --always-include b/two";
+        let opt = Opt::from_iter(args.split(' '));
+        let repo = |full_name: &str| BusFactor {
+            full_name: full_name.to_string(),
+            ..BusFactor::new(
+                full_name,
+                1,
+                UserShare {
+                    user_name: "leader".to_string(),
+                    bus_factor: 0.1,
+                    ..UserShare::default()
+                },
+            )
+        };
+        let mut results = vec![repo("A/One"), repo("c/three")];
+        let mut missing: Vec<&FullName> = opt.always_include.iter().collect();
+
+        mark_always_included(&mut results, &mut missing);
+
+        assert!(results[0].always_included);
+        assert!(!results[1].always_included);
+        assert_eq!(missing, [&"b/two".parse::<FullName>().unwrap()]);
+    }
+
+    #[test]
+    fn columns_fit_the_names() {
+        let repo = |name: &str, user: &str| {
+            BusFactor::new(
+                name,
+                7,
+                UserShare {
+                    user_name: user.to_string(),
+                    bus_factor: 0.9,
+                    ..UserShare::default()
+                },
+            )
+        };
+        let res = vec![repo("serde", "dtolnay"), repo("日本語", "a")];
+        let widths = Some(name_widths(&res, false));
+
+        assert_eq!(
+            result_line(&res[0], false, false, false, false, widths, None),
+            "project: serde  user: dtolnay percentage: 0.90 risk: Critical stars: 7"
+        );
+        assert_eq!(
+            result_line(&res[1], false, false, false, false, widths, None),
+            "project: 日本語 user: a       percentage: 0.90 risk: Critical stars: 7"
+        );
+        assert_eq!(
+            result_line(&res[1], false, false, false, false, None, None),
+            "project: 日本語 user: a percentage: 0.90 risk: Critical stars: 7"
+        );
+    }
+
+    #[test]
+    fn repos_are_named_by_full_name() {
+        let repo = |full_name: &str| BusFactor {
+            full_name: full_name.to_string(),
+            ..BusFactor::new(
+                "example",
+                7,
+                UserShare {
+                    user_name: "a".to_string(),
+                    bus_factor: 0.9,
+                    ..UserShare::default()
+                },
+            )
+        };
+        let res = vec![repo("one/example"), repo("other/example")];
+
+        assert_eq!(
+            result_line(
+                &res[1],
+                false,
+                false,
+                false,
+                false,
+                Some(name_widths(&res, false)),
+                None
+            ),
+            "project: other/example user: a percentage: 0.90 risk: Critical stars: 7"
+        );
+        assert_eq!(
+            result_line(
+                &res[1],
+                false,
+                false,
+                true,
+                false,
+                Some(name_widths(&res, true)),
+                None
+            ),
+            "project: example user: a percentage: 0.90 risk: Critical stars: 7"
+        );
+    }
+
+    #[test]
+    fn repo_without_contributors_is_not_available() {
+        let res = vec![BusFactor::without_contributors("empty", 3)];
+
+        assert_eq!(
+            result_line(
+                &res[0],
+                false,
+                false,
+                false,
+                false,
+                Some(name_widths(&res, false)),
+                None
+            ),
+            "project: empty user: - percentage: N/A risk: N/A      stars: 3"
+        );
+    }
+
+    #[test]
+    fn age_is_shown_if_asked_for() {
+        let repo = BusFactor {
+            created_at: Some("2021-09-01T10:15:30Z".parse().unwrap()),
+            ..BusFactor::without_contributors("young", 3)
+        };
+        let at = "2021-12-24T10:15:30Z".parse().ok();
+
+        assert!(result_line(&repo, false, false, false, false, None, at)
+            .ends_with("stars: 3 age: 114d"));
+        assert!(result_line(&repo, false, false, false, false, None, None).ends_with("stars: 3"));
+    }
+
+    #[test]
+    fn counts_are_shown_if_asked_for() {
+        let repo = BusFactor {
+            leader_contributions: Some(61),
+            total_considered_contributions: Some(97),
+            ..BusFactor::without_contributors("counted", 3)
+        };
+
+        assert!(result_line(&repo, false, false, false, true, None, None)
+            .ends_with("stars: 3 contributions: 61/97"));
+        assert!(result_line(&repo, false, false, false, false, None, None).ends_with("stars: 3"));
+
+        let normalized = BusFactor {
+            true_total: Some(1013),
+            ..repo
+        };
+        assert!(
+            result_line(&normalized, false, false, false, true, None, None)
+                .ends_with("contributions: 61/1013")
+        );
+    }
+
+    #[test]
+    fn local_repo_needs_no_search_parameters() {
+        let opt = Opt::from_iter(&["bus_factor", "--local-repo", ".", "--stars", "10"]);
+        assert_eq!(opt.local_repo, Some(PathBuf::from(".")));
+        assert_eq!(opt.stars, Some(10));
+
+        assert!(
+            Opt::from_iter_safe(&["bus_factor", "--local-repo", ".", "--language", "rust"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn saved_reports_are_compared_without_search_parameters() {
+        let opt = Opt::from_iter(&[
+            "bus_factor",
+            "--diff-against",
+            "a.json",
+            "--current-report",
+            "b.json",
+        ]);
+        assert_eq!(opt.diff_against, Some(PathBuf::from("a.json")));
+
+        assert!(Opt::from_iter_safe(&["bus_factor", "--current-report", "b.json"]).is_err());
+    }
+
+    #[test]
+    fn languages_are_parsed() {
+        let languages = parse_languages("rust\n\n# systems\n  C \ngo\n");
+
+        assert_eq!(languages, vec!["rust", "C", "go"]);
+    }
+
+    replay_and_live!(
+        simple_call_works,
+        empty_call_does_not_blow_up,
+        pagination_works,
+        api_fails_response_error_is_propagated,
+        invalid_repo_query,
+        invalid_language
+    );
+
     fn load_token() -> String {
         let mut filepath = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         filepath.push(".token");
 
-        let token = fs::read_to_string(filepath).expect("Something went wrong reading the file");
+        fs::read_to_string(filepath).expect("Something went wrong reading the file")
+    }
 
-        token
+    /// Api that talks to github, requires token in root/.token
+    fn live_api() -> GithubApi {
+        GithubApi::new(&load_token())
     }
 
-    #[tokio::test]
-    /// Simple call to the API
-    async fn simple_call_works() {
-        let token = load_token();
-        let api = GithubApi::new(&token);
+    /// Api that answers with fixtures, token is not needed
+    fn replay_api() -> GithubApi {
+        let mut dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("tests/fixtures");
+
+        let replay = ReplayClient::from_dir(&dir).unwrap();
+
+        GithubApi::with_client(GithubClient::with_transport(
+            "",
+            &TransportConfig::default(),
+            Arc::new(replay),
+        ))
+    }
 
+    /// Simple call to the API
+    async fn simple_call_works(api: GithubApi) {
         let res = api
             .get_repos(&RepoQuery {
-                language: "rust",
+                language: Some("rust"),
                 count: 1,
+                ..RepoQuery::default()
             })
             .await
             .unwrap();
@@ -133,16 +2647,13 @@ mod tests {
         assert_eq!(res.items.len(), 1);
     }
 
-    #[tokio::test]
     /// Request 0 elements, expect 0
-    async fn empty_call_does_not_blow_up() {
-        let token = load_token();
-        let api = GithubApi::new(&token);
-
+    async fn empty_call_does_not_blow_up(api: GithubApi) {
         let repos = api
             .get_repos(&RepoQuery {
-                language: "rust",
+                language: Some("rust"),
                 count: 0,
+                ..RepoQuery::default()
             })
             .await
             .unwrap();
@@ -150,32 +2661,23 @@ mod tests {
         assert_eq!(repos.items.len(), 0);
 
         let res = api
-            .get_repos_bus_factor(
-                &repos,
-                &BusFactorQuery {
-                    bus_threshold: 0.75,
-                    users_to_consider: 25,
-                },
-            )
+            .get_repos_bus_factor(&repos, &BusFactorQuery::default())
             .await
             .unwrap();
 
         assert_eq!(res.len(), 0);
     }
 
-    #[tokio::test]
     // Requests # of repos that does not fit on one page
-    async fn pagination_works() {
-        let token = load_token();
-        let api = GithubApi::new(&token);
-
+    async fn pagination_works(api: GithubApi) {
         for repo_count in [0, 1, 50, 99, 100, 101, 150, 200] {
             println!("pagination_works: testing repo_count {}...", repo_count);
 
             let repos = api
                 .get_repos(&RepoQuery {
-                    language: "rust",
+                    language: Some("rust"),
                     count: repo_count,
+                    ..RepoQuery::default()
                 })
                 .await
                 .unwrap();
@@ -189,16 +2691,13 @@ mod tests {
         }
     }
 
-    #[tokio::test]
     /// Test failure on contributions endpoint
-    async fn api_fails_response_error_is_propagated() {
-        let token = load_token();
-        let api = GithubApi::new(&token);
-
+    async fn api_fails_response_error_is_propagated(api: GithubApi) {
         let repo = api
             .get_repos(&RepoQuery {
-                language: "C",
+                language: Some("C"),
                 count: 1,
+                ..RepoQuery::default()
             })
             .await
             .unwrap();
@@ -208,8 +2707,8 @@ mod tests {
             .get_repos_bus_factor(
                 &repo,
                 &BusFactorQuery {
-                    bus_threshold: 0.75,
-                    users_to_consider: 25,
+                    fail_fast: true,
+                    ..BusFactorQuery::default()
                 },
             )
             .await
@@ -220,16 +2719,13 @@ mod tests {
         // TODO: might want check the message too
     }
 
-    #[tokio::test]
     /// Test failure on BusFactorQuery
-    async fn invalid_repo_query() {
-        let token = load_token();
-        let api = GithubApi::new(&token);
-
+    async fn invalid_repo_query(api: GithubApi) {
         let repo = api
             .get_repos(&RepoQuery {
-                language: "rust",
+                language: Some("rust"),
                 count: 1,
+                ..RepoQuery::default()
             })
             .await
             .unwrap();
@@ -239,8 +2735,8 @@ mod tests {
             .get_repos_bus_factor(
                 &repo,
                 &BusFactorQuery {
-                    bus_threshold: 0.75,
                     users_to_consider: 0,
+                    ..BusFactorQuery::default()
                 },
             )
             .await
@@ -249,17 +2745,14 @@ mod tests {
         assert!(err.is::<InvalidQueryError>());
     }
 
-    #[tokio::test]
     /// Check failure on repos endpoint
-    async fn invalid_language() {
-        let token = load_token();
-        let api = GithubApi::new(&token);
-
+    async fn invalid_language(api: GithubApi) {
         // Invalid language, api will fail
         let err = api
             .get_repos(&RepoQuery {
-                language: "asdf",
+                language: Some("asdf"),
                 count: 1,
+                ..RepoQuery::default()
             })
             .await
             .unwrap_err();
@@ -0,0 +1,178 @@
+//! Metrics of a run in Prometheus text exposition format, so a scheduled run can be scraped.
+use std::{fmt::Write as _, fs, path::Path, time::Duration};
+
+use crate::{api_errors::SendError, github_api::BusFactor, github_client::RequestStats};
+
+#[derive(Debug, Default, Clone, PartialEq)]
+/// Counters of a single run
+pub struct RunMetrics {
+    /// Repos bus factor was calculated for, reported or not
+    pub repos_processed: u64,
+    /// Processed repos where the leader's share reaches the threshold
+    pub high_risk: u64,
+    /// Repos left out because they failed or timed out, see GithubApi::take_skipped_repos
+    pub repos_skipped: u64,
+    /// Processed repo with the highest leader's share: its full name, or name if it's unknown,
+    /// and the share
+    pub worst: Option<(String, f64)>,
+    pub requests: RequestStats,
+    pub duration: Duration,
+}
+
+impl RunMetrics {
+    /// Counts processed results, before they are filtered for the report
+    pub fn add_results(&mut self, results: &[BusFactor], bus_threshold: f64) {
+        self.repos_processed += results.iter().filter(|repo| !repo.not_calculated).count() as u64;
+        self.high_risk += results
+            .iter()
+            .filter(|repo| repo.is_significant(bus_threshold))
+            .count() as u64;
+
+        for repo in results {
+            let name = if repo.full_name.is_empty() {
+                &repo.repo_name
+            } else {
+                &repo.full_name
+            };
+            self.note_worst(name, repo.leader.bus_factor);
+        }
+    }
+
+    /// Adds counters of another part of the run, like of the next language.
+    /// Request stats are totals already, the later ones are taken
+    pub fn merge(&mut self, other: RunMetrics) {
+        self.repos_processed += other.repos_processed;
+        self.high_risk += other.high_risk;
+        self.repos_skipped += other.repos_skipped;
+        if let Some((name, share)) = &other.worst {
+            self.note_worst(name, *share);
+        }
+        self.requests = other.requests;
+        self.duration += other.duration;
+    }
+
+    /// NaN share is never the worst
+    fn note_worst(&mut self, name: &str, share: f64) {
+        if !share.is_nan() && self.worst.as_ref().is_none_or(|(_, worst)| share > *worst) {
+            self.worst = Some((name.to_string(), share));
+        }
+    }
+
+    /// Text exposition format, every metric is a gauge describing the last run.
+    /// Remaining rate limit is left out if no response carried it
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        let mut gauge = |name: &str, help: &str, value: String| {
+            // Writing to a String does not fail
+            let _ = write!(
+                out,
+                "# HELP {name} {help}\n# TYPE {name} gauge\n{name} {value}\n",
+                name = name,
+                help = help,
+                value = value
+            );
+        };
+
+        gauge(
+            "bus_factor_repos_processed",
+            "Repos bus factor was calculated for",
+            self.repos_processed.to_string(),
+        );
+        gauge(
+            "bus_factor_high_risk_repos",
+            "Repos where the leader's share reaches the threshold",
+            self.high_risk.to_string(),
+        );
+        gauge(
+            "bus_factor_repos_skipped",
+            "Repos left out because they failed or timed out",
+            self.repos_skipped.to_string(),
+        );
+        gauge(
+            "bus_factor_requests",
+            "Requests sent to the api, retries included",
+            self.requests.requests.to_string(),
+        );
+        gauge(
+            "bus_factor_retries",
+            "Requests repeated after a failure or throttling",
+            self.requests.retries.to_string(),
+        );
+        if let Some(remaining) = self.requests.rate_limit_remaining {
+            gauge(
+                "bus_factor_rate_limit_remaining",
+                "Requests left in the rate limit, as of the last response",
+                remaining.to_string(),
+            );
+        }
+        gauge(
+            "bus_factor_duration_seconds",
+            "Duration of the run",
+            format!("{:.3}", self.duration.as_secs_f64()),
+        );
+
+        out
+    }
+
+    /// Writes metrics to a temporary file next to the path and renames it,
+    /// so a scraper never reads a half written file
+    pub fn save(&self, path: &Path) -> Result<(), SendError> {
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+
+        fs::write(&tmp, self.to_prometheus())?;
+        fs::rename(&tmp, path)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::github_api::UserShare;
+
+    #[test]
+    fn metrics_are_formatted() {
+        let repo = |share: f64| {
+            BusFactor::new(
+                "repo",
+                1,
+                UserShare {
+                    user_name: "leader".to_string(),
+                    bus_factor: share,
+                    ..UserShare::default()
+                },
+            )
+        };
+        let mut metrics = RunMetrics {
+            requests: RequestStats {
+                requests: 12,
+                retries: 2,
+                rate_limit_remaining: Some(4980),
+            },
+            duration: Duration::from_millis(1500),
+            repos_skipped: 1,
+            ..RunMetrics::default()
+        };
+        metrics.add_results(&[repo(0.9), repo(0.2), repo(0.75)], 0.75);
+        assert_eq!(metrics.worst, Some(("repo".to_string(), 0.9)));
+
+        let text = metrics.to_prometheus();
+
+        assert!(text.contains(
+            "# HELP bus_factor_repos_processed Repos bus factor was calculated for\n\
+             # TYPE bus_factor_repos_processed gauge\n\
+             bus_factor_repos_processed 3\n"
+        ));
+        assert!(text.contains("\nbus_factor_high_risk_repos 2\n"));
+        assert!(text.contains("\nbus_factor_repos_skipped 1\n"));
+        assert!(text.contains("\nbus_factor_requests 12\n"));
+        assert!(text.contains("\nbus_factor_retries 2\n"));
+        assert!(text.contains("\nbus_factor_rate_limit_remaining 4980\n"));
+        assert!(text.ends_with("\nbus_factor_duration_seconds 1.500\n"));
+
+        metrics.requests.rate_limit_remaining = None;
+        assert!(!metrics.to_prometheus().contains("rate_limit"));
+    }
+}
@@ -0,0 +1,151 @@
+//! Picks concurrency and pacing of a run from the rate limit left, for those who don't know
+//! what to choose. Numbers are estimates, repos that need more pages than expected cost more.
+use std::time::Duration;
+
+/// Requests per second at most. Secondary rate limit allows about 900 requests a minute
+/// to the REST api, that is 15 per second, some margin is left
+pub const MAX_RATE: f64 = 12.0;
+/// Requests in flight at most, more of them get throttled by the secondary rate limit
+pub const MAX_CONCURRENCY: usize = 20;
+/// Small runs need few workers, one is added for every that many requests
+const REQUESTS_PER_WORKER: u64 = 25;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Requests the bus factor of a run is expected to cost. Search of repos is not counted,
+/// it has its own quota, reset every minute, and it's paced anyway
+pub struct RunCost {
+    pub repos: u32,
+    pub requests_per_repo: u64,
+}
+
+impl RunCost {
+    pub fn total(&self) -> u64 {
+        self.repos as u64 * self.requests_per_repo
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Quota of the rate limit, as /rate_limit reports it
+pub struct Budget {
+    /// Requests per hour
+    pub limit: u64,
+    pub remaining: u64,
+    pub reset_in: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Settings picked for the run
+pub struct Profile {
+    pub concurrency: usize,
+    /// Requests per second
+    pub rate: f64,
+    /// False if the run costs more than the quota that is left
+    pub fits: bool,
+    /// Repos the quota that is left is enough for
+    pub max_repos: u32,
+}
+
+/// Run that fits in the quota goes as fast as the secondary rate limit allows. Otherwise
+/// the quota left is spread until the reset, but not slower than the quota renews,
+/// so the run never stops to wait for the reset
+pub fn plan(cost: &RunCost, budget: &Budget) -> Profile {
+    let total = cost.total();
+    let concurrency = (total / REQUESTS_PER_WORKER).clamp(1, MAX_CONCURRENCY as u64) as usize;
+    let fits = total <= budget.remaining;
+
+    let rate = if fits {
+        MAX_RATE
+    } else {
+        let until_reset = budget.remaining as f64 / budget.reset_in.as_secs_f64().max(1.0);
+        let renewed = budget.limit as f64 / 3600.0;
+        // Rate has to be positive, even without any quota
+        until_reset.max(renewed).clamp(0.01, MAX_RATE)
+    };
+
+    let max_repos = budget.remaining / cost.requests_per_repo.max(1);
+
+    Profile {
+        concurrency,
+        rate,
+        fits,
+        max_repos: max_repos.min(u32::MAX as u64) as u32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_approx_eq::assert_approx_eq;
+
+    use super::*;
+
+    const BUDGET: Budget = Budget {
+        limit: 5000,
+        remaining: 1000,
+        reset_in: Duration::from_secs(500),
+    };
+
+    #[test]
+    fn run_within_quota_is_not_paced() {
+        let profile = plan(
+            &RunCost {
+                repos: 50,
+                requests_per_repo: 2,
+            },
+            &BUDGET,
+        );
+
+        assert_eq!(
+            profile,
+            Profile {
+                concurrency: 4,
+                rate: MAX_RATE,
+                fits: true,
+                max_repos: 500,
+            }
+        );
+
+        let small = plan(
+            &RunCost {
+                repos: 1,
+                requests_per_repo: 1,
+            },
+            &BUDGET,
+        );
+        assert_eq!(small.concurrency, 1);
+    }
+
+    #[test]
+    fn run_above_quota_is_spread_until_reset() {
+        let cost = RunCost {
+            repos: 1000,
+            requests_per_repo: 2,
+        };
+
+        let profile = plan(&cost, &BUDGET);
+        assert!(!profile.fits);
+        assert_eq!(profile.concurrency, MAX_CONCURRENCY);
+        assert_eq!(profile.max_repos, 500);
+        assert_approx_eq!(profile.rate, 2.0);
+
+        // Never slower than the quota renews
+        let profile = plan(
+            &cost,
+            &Budget {
+                remaining: 10,
+                ..BUDGET
+            },
+        );
+        assert_approx_eq!(profile.rate, 5000.0 / 3600.0);
+
+        let profile = plan(
+            &cost,
+            &Budget {
+                limit: 0,
+                remaining: 0,
+                reset_in: Duration::ZERO,
+            },
+        );
+        assert!(profile.rate > 0.0);
+        assert_eq!(profile.max_repos, 0);
+    }
+}
@@ -0,0 +1,149 @@
+//! Progress of a run as events, so a wrapping process can drive its own UI.
+//! Events are written as newline-delimited JSON, schema is described in the README.
+use std::{
+    io::Write,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Bumped when an event changes in a way that breaks existing readers,
+/// new events or fields are added without bumping it
+pub const PROGRESS_SCHEMA: u32 = 1;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+/// Single event, written as one JSON object, told apart by its "event" field
+pub enum ProgressEvent {
+    /// First event of the run
+    Started { schema: u32, project_count: u32 },
+    /// Bus factor of the repo is known, once per repo in the results, reported or not.
+    /// Share and leader are None if the repo has no contributions to consider
+    RepoCompleted {
+        repo: String,
+        /// Like "owner/name", empty if unknown
+        full_name: String,
+        bus_factor: Option<f64>,
+        leader: Option<String>,
+    },
+    /// Request was throttled, by the primary or the secondary rate limit
+    RateLimited {
+        endpoint: String,
+        secondary: bool,
+        wait_ms: u64,
+    },
+    /// Last event of the run, success is false if it failed
+    Finished {
+        success: bool,
+        /// Repo-completed events sent before
+        repos: u64,
+        elapsed_ms: u64,
+    },
+}
+
+/// Gets events as they happen, possibly from many workers at once
+pub trait ProgressListener: Send + Sync {
+    fn on_event(&self, event: &ProgressEvent);
+}
+
+impl<F> ProgressListener for F
+where
+    F: Fn(&ProgressEvent) + Send + Sync,
+{
+    fn on_event(&self, event: &ProgressEvent) {
+        self(event)
+    }
+}
+
+/// Writes every event as a line of JSON, flushed right away, so the other side can tail it
+pub struct ProgressWriter<W: Write + Send> {
+    out: Mutex<W>,
+    completed: AtomicU64,
+    /// Failed write is logged once, progress is not worth failing the run
+    failed: AtomicBool,
+}
+
+impl<W: Write + Send> ProgressWriter<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out: Mutex::new(out),
+            completed: AtomicU64::new(0),
+            failed: AtomicBool::new(false),
+        }
+    }
+
+    pub fn start(&self, project_count: u32) {
+        self.on_event(&ProgressEvent::Started {
+            schema: PROGRESS_SCHEMA,
+            project_count,
+        });
+    }
+
+    /// Ends the events, with number of repos completed so far
+    pub fn finish(&self, success: bool, elapsed: Duration) {
+        self.on_event(&ProgressEvent::Finished {
+            success,
+            repos: self.completed.load(Ordering::Relaxed),
+            elapsed_ms: elapsed.as_millis() as u64,
+        });
+    }
+}
+
+impl<W: Write + Send> ProgressListener for ProgressWriter<W> {
+    fn on_event(&self, event: &ProgressEvent) {
+        if let ProgressEvent::RepoCompleted { .. } = event {
+            self.completed.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let written = serde_json::to_string(event)
+            .map_err(std::io::Error::from)
+            .and_then(|line| {
+                let mut out = self.out.lock().unwrap();
+                writeln!(out, "{}", line)?;
+                out.flush()
+            });
+
+        if let Err(e) = written {
+            if !self.failed.swap(true, Ordering::Relaxed) {
+                warn!("Can't write progress events: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_are_json_lines() {
+        let writer = ProgressWriter::new(Vec::new());
+        writer.start(2);
+        writer.on_event(&ProgressEvent::RepoCompleted {
+            repo: "a".to_string(),
+            full_name: "owner/a".to_string(),
+            bus_factor: None,
+            leader: None,
+        });
+        writer.finish(true, Duration::from_millis(1500));
+
+        let out = String::from_utf8(writer.out.into_inner().unwrap()).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+
+        assert_eq!(
+            lines,
+            [
+                r#"{"event":"started","schema":1,"project_count":2}"#,
+                r#"{"event":"repo-completed","repo":"a","full_name":"owner/a","bus_factor":null,"leader":null}"#,
+                r#"{"event":"finished","success":true,"repos":1,"elapsed_ms":1500}"#,
+            ]
+        );
+        for line in lines {
+            serde_json::from_str::<ProgressEvent>(line).unwrap();
+        }
+    }
+}
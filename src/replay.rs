@@ -0,0 +1,390 @@
+//! Plays back responses recorded from the real API, so code that talks to
+//! github can be exercised without network or a token.
+//!
+//! Each fixture is a separate JSON file in the directory:
+//! ```json
+//! {
+//!     "endpoint": "https://api.github.com/search/repositories?q=language:rust&sort=stars&order=desc&per_page=1&page=1",
+//!     "status": 200,
+//!     "body": { "total_count": 1, "incomplete_results": false, "items": [] }
+//! }
+//! ```
+//! Endpoint has to match exactly what is requested, including the query.
+//! Fixtures can be captured from a live run with `--record <dir>`.
+//!
+//! Recorded fixture always takes precedence, also one of a failed request, like 404.
+//! Endpoint without a fixture goes to the fallback transport if there is one,
+//! see ReplayClient::with_fallback, and is an error otherwise.
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use futures::future::BoxFuture;
+use reqwest::{
+    header::{HeaderMap, HeaderName},
+    StatusCode,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api_errors::{MissingFixtureError, SendError},
+    rng,
+    transport::{RawResponse, Transport},
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+/// Single recorded response
+pub struct Fixture {
+    pub endpoint: String,
+    pub status: u16,
+    /// Response body, kept as JSON so fixtures are readable. Body that is
+    /// not a valid JSON is stored as a string.
+    pub body: serde_json::Value,
+    /// Only headers that matter for the client, like Retry-After
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+}
+
+/// Readable part of fixture file names is cut there, file systems limit names to 255 bytes
+const MAX_READABLE_NAME: usize = 200;
+
+/// Response headers that are kept in recorded fixtures, everything else is dropped
+const RECORDED_HEADERS: &[&str] = &[
+    "retry-after",
+    "x-ratelimit-limit",
+    "x-ratelimit-remaining",
+    "x-ratelimit-reset",
+];
+
+impl Fixture {
+    /// Captures response for the endpoint. Secret is removed from everything that is kept,
+    /// request headers, where the token lives, are not part of the fixture at all.
+    pub fn from_response(endpoint: &str, response: &RawResponse, secret: &str) -> Self {
+        let scrub = |text: &str| match secret.trim() {
+            "" => text.to_string(),
+            secret => text.replace(secret, "REDACTED"),
+        };
+
+        let body = scrub(&response.body);
+        let body = serde_json::from_str(&body).unwrap_or(serde_json::Value::String(body));
+
+        let headers = response
+            .headers
+            .iter()
+            .filter(|(name, _)| RECORDED_HEADERS.contains(&name.as_str()))
+            .filter_map(|(name, value)| Some((name.to_string(), scrub(value.to_str().ok()?))))
+            .collect();
+
+        Self {
+            endpoint: scrub(endpoint),
+            status: response.status.as_u16(),
+            body,
+            headers,
+        }
+    }
+
+    /// Name of the file fixture is stored in, derived from the endpoint. Readable part
+    /// loses case and punctuation, hash of the whole endpoint tells apart those it makes alike
+    fn file_name(&self) -> String {
+        let path = self
+            .endpoint
+            .trim_start_matches("https://api.github.com/")
+            .replace("search/repositories", "search")
+            .replace("repos/", "");
+
+        let name: String = path
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_lowercase()
+                } else {
+                    '_'
+                }
+            })
+            .take(MAX_READABLE_NAME)
+            .collect();
+
+        format!("{}_{:016x}.json", name, rng::fnv1a(&self.endpoint))
+    }
+
+    /// Writes fixture to the directory, file of the same endpoint is overwritten
+    pub fn save(&self, dir: &Path) -> Result<(), SendError> {
+        fs::create_dir_all(dir)?;
+
+        let path = dir.join(self.file_name());
+        fs::write(&path, serde_json::to_string_pretty(self)?)?;
+
+        debug!("Recorded {} to {}", self.endpoint, path.display());
+
+        Ok(())
+    }
+
+    fn to_response(&self) -> Result<RawResponse, SendError> {
+        let body = match &self.body {
+            serde_json::Value::String(body) => body.clone(),
+            body => body.to_string(),
+        };
+
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            headers.insert(HeaderName::from_bytes(name.as_bytes())?, value.parse()?);
+        }
+
+        Ok(RawResponse {
+            status: StatusCode::from_u16(self.status)?,
+            headers,
+            body,
+        })
+    }
+}
+
+/// Transport that answers with recorded fixtures instead of asking the server
+pub struct ReplayClient {
+    fixtures: HashMap<String, Fixture>,
+    /// Directory fixtures were loaded from, named when one is missing
+    dir: Option<PathBuf>,
+    /// Asked for endpoints that were not recorded
+    fallback: Option<Arc<dyn Transport>>,
+}
+
+impl ReplayClient {
+    pub fn new(fixtures: Vec<Fixture>) -> Self {
+        Self {
+            fixtures: fixtures
+                .into_iter()
+                .map(|fixture| (fixture.endpoint.clone(), fixture))
+                .collect(),
+            dir: None,
+            fallback: None,
+        }
+    }
+
+    /// Endpoints that have no fixture are requested from the fallback, like the live api,
+    /// instead of failing with MissingFixtureError
+    pub fn with_fallback(self, fallback: Arc<dyn Transport>) -> Self {
+        Self {
+            fallback: Some(fallback),
+            ..self
+        }
+    }
+
+    /// Loads all *.json fixtures from the directory
+    pub fn from_dir(dir: &Path) -> Result<Self, SendError> {
+        let mut fixtures = vec![];
+
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+
+            if path.extension().is_some_and(|ext| ext == "json") {
+                let content = fs::read_to_string(&path)?;
+                fixtures.push(serde_json::from_str(&content)?);
+            }
+        }
+
+        debug!("Loaded {} fixtures from {}", fixtures.len(), dir.display());
+
+        Ok(Self {
+            dir: Some(dir.to_path_buf()),
+            ..Self::new(fixtures)
+        })
+    }
+}
+
+impl Transport for ReplayClient {
+    fn get<'a>(
+        &'a self,
+        endpoint: &'a str,
+        headers: HeaderMap,
+    ) -> BoxFuture<'a, Result<RawResponse, SendError>> {
+        Box::pin(async move {
+            match (self.fixtures.get(endpoint), &self.fallback) {
+                (Some(fixture), _) => {
+                    trace!("Replaying {}", endpoint);
+                    fixture.to_response()
+                }
+                (None, Some(fallback)) => {
+                    trace!("No recorded response for {}, requesting it", endpoint);
+                    fallback.get(endpoint, headers).await
+                }
+                (None, None) => Err(Box::new(MissingFixtureError::new(&match &self.dir {
+                    Some(dir) => {
+                        format!("No recorded response for {} in {}", endpoint, dir.display())
+                    }
+                    None => format!("No recorded response for {}", endpoint),
+                })) as SendError),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::RETRY_AFTER;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn recorded_response_is_replayed() {
+        let client = ReplayClient::new(vec![Fixture {
+            endpoint: "https://api.github.com/zen".to_string(),
+            status: 200,
+            body: serde_json::json!({"items": []}),
+            headers: HashMap::new(),
+        }]);
+
+        let res = client
+            .get("https://api.github.com/zen", HeaderMap::new())
+            .await
+            .unwrap();
+
+        assert_eq!(res.status, StatusCode::OK);
+        assert_eq!(res.body, r#"{"items":[]}"#);
+    }
+
+    #[tokio::test]
+    async fn missing_fixture_is_an_error() {
+        let client = ReplayClient::new(vec![]);
+
+        let err = client
+            .get("https://api.github.com/zen", HeaderMap::new())
+            .await
+            .unwrap_err();
+
+        assert!(err.is::<MissingFixtureError>());
+    }
+
+    /// Answers every request with the same body, counting them
+    struct LiveTransport(std::sync::atomic::AtomicUsize);
+
+    impl Transport for LiveTransport {
+        fn get<'a>(
+            &'a self,
+            _endpoint: &'a str,
+            _headers: HeaderMap,
+        ) -> BoxFuture<'a, Result<RawResponse, SendError>> {
+            self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Box::pin(async {
+                Ok(RawResponse {
+                    status: StatusCode::OK,
+                    headers: HeaderMap::new(),
+                    body: "live".to_string(),
+                })
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn only_missing_fixture_goes_to_fallback() {
+        let live = Arc::new(LiveTransport(Default::default()));
+        let client = ReplayClient::new(vec![Fixture {
+            endpoint: "https://api.github.com/zen".to_string(),
+            status: 404,
+            body: serde_json::json!("Not Found"),
+            headers: HashMap::new(),
+        }])
+        .with_fallback(live.clone());
+
+        let res = client
+            .get("https://api.github.com/zen", HeaderMap::new())
+            .await
+            .unwrap();
+        assert_eq!(res.status, StatusCode::NOT_FOUND);
+        assert_eq!(res.body, "Not Found");
+        assert_eq!(live.0.load(std::sync::atomic::Ordering::SeqCst), 0);
+
+        let res = client
+            .get("https://api.github.com/octocat", HeaderMap::new())
+            .await
+            .unwrap();
+        assert_eq!(res.body, "live");
+        assert_eq!(live.0.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn recorded_fixture_has_no_secrets() {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, "30".parse().unwrap());
+        headers.insert("x-oauth-scopes", "repo".parse().unwrap());
+        headers.insert("set-cookie", "session=secret-token".parse().unwrap());
+
+        let response = RawResponse {
+            status: StatusCode::OK,
+            headers,
+            body: r#"{"echo": "secret-token"}"#.to_string(),
+        };
+
+        let fixture = Fixture::from_response(
+            "https://api.github.com/repos/owner/repo/contributors?per_page=25",
+            &response,
+            "secret-token\n",
+        );
+
+        assert_eq!(fixture.body, serde_json::json!({"echo": "REDACTED"}));
+        assert_eq!(fixture.headers.len(), 1);
+        assert_eq!(fixture.headers["retry-after"], "30");
+        assert!(fixture
+            .file_name()
+            .starts_with("owner_repo_contributors_per_page_25_"));
+    }
+
+    #[tokio::test]
+    async fn saved_fixture_is_replayed() {
+        let dir = std::env::temp_dir().join(format!("bus_factor_record_{}", std::process::id()));
+        let response = RawResponse {
+            status: StatusCode::NOT_FOUND,
+            headers: HeaderMap::new(),
+            body: "Not Found".to_string(),
+        };
+
+        Fixture::from_response("https://api.github.com/zen", &response, "")
+            .save(&dir)
+            .unwrap();
+        let client = ReplayClient::from_dir(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let res = client
+            .get("https://api.github.com/zen", HeaderMap::new())
+            .await
+            .unwrap();
+
+        assert_eq!(res.status, StatusCode::NOT_FOUND);
+        assert_eq!(res.body, "Not Found");
+    }
+
+    #[tokio::test]
+    async fn endpoints_alike_get_fixtures_of_their_own() {
+        let dir = std::env::temp_dir().join(format!("bus_factor_alike_{}", std::process::id()));
+        let endpoints = [
+            "https://api.github.com/search/repositories?q=stars:>=100",
+            "https://api.github.com/search/repositories?q=stars:<=100",
+            "https://api.github.com/repos/o/a+b/contributors",
+            "https://api.github.com/repos/o/a&b/contributors",
+            "https://api.github.com/repos/Owner/Repo/contributors",
+            "https://api.github.com/repos/owner/repo/contributors",
+        ];
+
+        for endpoint in endpoints {
+            let response = RawResponse {
+                status: StatusCode::OK,
+                headers: HeaderMap::new(),
+                body: serde_json::json!({ "endpoint": endpoint }).to_string(),
+            };
+            Fixture::from_response(endpoint, &response, "")
+                .save(&dir)
+                .unwrap();
+        }
+        let client = ReplayClient::from_dir(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        for endpoint in endpoints {
+            let res = client.get(endpoint, HeaderMap::new()).await.unwrap();
+            assert_eq!(
+                res.body,
+                serde_json::json!({ "endpoint": endpoint }).to_string()
+            );
+        }
+    }
+}
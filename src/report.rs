@@ -0,0 +1,1326 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+    str::FromStr,
+    sync::OnceLock,
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::{
+    api_errors::InvalidQueryError,
+    github_api::{BusFactor, RiskLevel, UserShare},
+    github_data::RepoData,
+    metrics::RunMetrics,
+    warnings::RunWarning,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// How results are written to the standard output
+pub enum OutputFormat {
+    /// Human readable lines, one per repo
+    Text,
+    /// Single JSON document with the whole report
+    Json,
+    /// Comma separated values, with a header row
+    Csv,
+    /// Tab separated values, with a header row
+    Tsv,
+    /// Self-contained HTML page with a sortable table
+    Html,
+    /// JSON array of results only, written as they come, so they are not kept in memory
+    JsonArray,
+    /// JSON object per line, each result with the run id and language, see ReportRecord
+    NdjsonReport,
+}
+
+impl OutputFormat {
+    /// Extension of the file with output in that format
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Text => "txt",
+            OutputFormat::Json | OutputFormat::JsonArray => "json",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Tsv => "tsv",
+            OutputFormat::Html => "html",
+            OutputFormat::NdjsonReport => "ndjson",
+        }
+    }
+
+    /// Separator of values, for formats that are delimited
+    pub fn delimiter(self) -> Option<char> {
+        match self {
+            OutputFormat::Csv => Some(','),
+            OutputFormat::Tsv => Some('\t'),
+            OutputFormat::Text
+            | OutputFormat::Json
+            | OutputFormat::Html
+            | OutputFormat::JsonArray
+            | OutputFormat::NdjsonReport => None,
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = InvalidQueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            "tsv" => Ok(OutputFormat::Tsv),
+            "html" => Ok(OutputFormat::Html),
+            "json-array" => Ok(OutputFormat::JsonArray),
+            "ndjson-report" => Ok(OutputFormat::NdjsonReport),
+            _ => Err(InvalidQueryError::new(&format!(
+                "Unknown output format '{}', expected one of: text, json, csv, tsv, html, \
+                 json-array, ndjson-report",
+                s
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Key reported repos are sorted by
+pub enum SortBy {
+    /// Leader's share
+    Share,
+    Stars,
+    Name,
+    /// Score given by the scorer of the query, leader's share without one
+    Score,
+    /// Open issues and pull requests
+    Issues,
+}
+
+impl FromStr for SortBy {
+    type Err = InvalidQueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "share" => Ok(SortBy::Share),
+            "stars" => Ok(SortBy::Stars),
+            "name" => Ok(SortBy::Name),
+            "score" => Ok(SortBy::Score),
+            "issues" => Ok(SortBy::Issues),
+            _ => Err(InvalidQueryError::new(&format!(
+                "Unknown sort key '{}', expected one of: share, stars, name, score, issues",
+                s
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+impl FromStr for SortOrder {
+    type Err = InvalidQueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "asc" => Ok(SortOrder::Asc),
+            "desc" => Ok(SortOrder::Desc),
+            _ => Err(InvalidQueryError::new(&format!(
+                "Unknown sort order '{}', expected one of: asc, desc",
+                s
+            ))),
+        }
+    }
+}
+
+/// Sorts results by the key, stable, so repos with equal keys keep search order
+pub fn sort_results(results: &mut [BusFactor], by: SortBy, order: SortOrder) {
+    results.sort_by(|a, b| {
+        let ordering = match by {
+            SortBy::Share => BusFactor::by_leader_share(a, b),
+            SortBy::Stars => BusFactor::by_stars(a, b),
+            SortBy::Name => a.repo_name.cmp(&b.repo_name),
+            SortBy::Score => BusFactor::by_score(a, b),
+            SortBy::Issues => BusFactor::by_open_issues(a, b),
+        };
+
+        match order {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse(),
+        }
+    });
+}
+
+/// Keeps only the top riskiest results, by score, leader's share unless a scorer gave another one.
+/// Kept ones are ordered by risk, highest first, repos without a share go last.
+/// Always included repos are kept on top of them. Returns how many were omitted
+pub fn keep_riskiest(results: &mut Vec<BusFactor>, top: usize) -> usize {
+    sort_results(results, SortBy::Score, SortOrder::Desc);
+
+    let before = results.len();
+    let mut left = top;
+    results.retain(|repo| {
+        if repo.always_included {
+            return true;
+        }
+        let kept = left > 0;
+        left = left.saturating_sub(1);
+        kept
+    });
+    before - results.len()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+/// Order of contributors within a single repo
+pub enum ContributorOrder {
+    /// Highest share first, as they are computed
+    #[default]
+    Share,
+    /// By login, so lists of two runs can be diffed
+    Name,
+}
+
+impl FromStr for ContributorOrder {
+    type Err = InvalidQueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "share" => Ok(ContributorOrder::Share),
+            "name" => Ok(ContributorOrder::Name),
+            _ => Err(InvalidQueryError::new(&format!(
+                "Unknown contributor order '{}', expected one of: share, name",
+                s
+            ))),
+        }
+    }
+}
+
+/// Sorts contributors of a repo, like RepoShare::contributors, for display only.
+/// Equal shares are ordered by login, so the order is the same between runs
+pub fn sort_contributors(contributors: &mut [UserShare], order: ContributorOrder) {
+    contributors.sort_by(|a, b| match order {
+        ContributorOrder::Share => b
+            .bus_factor
+            .partial_cmp(&a.bus_factor)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.user_name.cmp(&b.user_name)),
+        ContributorOrder::Name => a.user_name.cmp(&b.user_name),
+    });
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// When text output is colored
+pub enum ColorChoice {
+    /// Only if the output is a terminal
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn enabled(self, is_terminal: bool) -> bool {
+        match self {
+            ColorChoice::Auto => is_terminal,
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+impl FromStr for ColorChoice {
+    type Err = InvalidQueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err(InvalidQueryError::new(&format!(
+                "Unknown color choice '{}', expected one of: auto, always, never",
+                s
+            ))),
+        }
+    }
+}
+
+/// Shown instead of the share and risk of a repo without contributions to consider
+pub const NOT_AVAILABLE: &str = "N/A";
+
+/// Names wider than that are cut in the aligned text output
+pub const MAX_NAME_WIDTH: usize = 40;
+
+/// Leader's share with given number of decimals, NOT_AVAILABLE for repo without contributions
+pub fn share_text(repo: &BusFactor, decimals: usize) -> String {
+    if repo.has_share() {
+        format!("{:.*}", decimals, repo.leader.bus_factor)
+    } else {
+        NOT_AVAILABLE.to_string()
+    }
+}
+
+/// Risk level, NOT_AVAILABLE for repo without contributions
+pub fn risk_text(repo: &BusFactor) -> String {
+    if repo.has_share() {
+        repo.risk_level.to_string()
+    } else {
+        NOT_AVAILABLE.to_string()
+    }
+}
+
+/// Age of the repo at given time, in days below a year, in years otherwise
+pub fn age_text(created_at: DateTime<Utc>, at: DateTime<Utc>) -> String {
+    let days = (at - created_at).num_days().max(0);
+    if days < 365 {
+        format!("{}d", days)
+    } else {
+        format!("{:.1}y", days as f64 / 365.25)
+    }
+}
+
+/// Wraps text in the color of the risk level, as ANSI escape codes.
+/// Low and medium risk stay uncolored
+pub fn paint_risk(risk: RiskLevel, text: &str) -> String {
+    let color = match risk {
+        RiskLevel::Critical => "31",
+        RiskLevel::High => "33",
+        RiskLevel::Medium | RiskLevel::Low => return text.to_string(),
+    };
+
+    format!("\x1b[{}m{}\x1b[0m", color, text)
+}
+
+/// Pads text with spaces to the width in terminal columns, wide characters like CJK or emoji
+/// take two of them. Longer text is cut at a character boundary and ends with an ellipsis,
+/// so the columns after it stay aligned
+pub fn fit_width(text: &str, width: usize) -> String {
+    let text_width = text.width();
+    if text_width <= width {
+        return format!("{}{}", text, " ".repeat(width - text_width));
+    }
+
+    let mut out = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let char_width = c.width().unwrap_or(0);
+        // Last column is for the ellipsis
+        if used + char_width >= width {
+            break;
+        }
+        out.push(c);
+        used += char_width;
+    }
+    out.push('…');
+    // Wide character that did not fit leaves a gap
+    out.push_str(&" ".repeat(width.saturating_sub(used + 1)));
+
+    out
+}
+
+/// Width in terminal columns of the widest value, but no more than max
+pub fn column_width<'a>(values: impl IntoIterator<Item = &'a str>, max: usize) -> usize {
+    values
+        .into_iter()
+        .map(|value| value.width())
+        .max()
+        .unwrap_or_default()
+        .min(max)
+}
+
+/// Serializes report to JSON, compact unless pretty is requested
+pub fn to_json<T: Serialize>(report: &T, pretty: bool) -> serde_json::Result<String> {
+    if pretty {
+        serde_json::to_string_pretty(report)
+    } else {
+        serde_json::to_string(report)
+    }
+}
+
+/// Writes JSON array element by element, so they don't have to be kept in memory
+/// until all of them are there. Array is closed by finish
+pub struct JsonArrayWriter<W: Write> {
+    writer: W,
+    written: usize,
+}
+
+impl<W: Write> JsonArrayWriter<W> {
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(b"[")?;
+        Ok(Self { writer, written: 0 })
+    }
+
+    pub fn write<T: Serialize>(&mut self, element: &T) -> io::Result<()> {
+        if self.written > 0 {
+            self.writer.write_all(b",")?;
+        }
+        serde_json::to_writer(&mut self.writer, element)?;
+        self.written += 1;
+
+        // Element is visible right away, like lines of the text output
+        self.writer.flush()
+    }
+
+    /// Closes the array, returns the writer
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.write_all(b"]\n")?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Identifies all records written by a single run, like "20211224T101530.123Z-4242",
+/// time of the start followed by the process id
+pub fn new_run_id() -> String {
+    format!(
+        "{}-{}",
+        Utc::now().format("%Y%m%dT%H%M%S%.3fZ"),
+        std::process::id()
+    )
+}
+
+/// Id of this process, the same for all reports and records it writes
+pub fn run_id() -> &'static str {
+    static RUN_ID: OnceLock<String> = OnceLock::new();
+    RUN_ID.get_or_init(new_run_id)
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+/// Single line of the ndjson-report format, self-describing, so lines of many runs
+/// and languages can be put together. Fields are not renamed or removed, only added
+pub struct ReportRecord {
+    pub run_id: String,
+    pub language: String,
+    /// Like "owner/name", or only the name if the full one is unknown
+    pub repo: String,
+    pub leader: String,
+    /// Share of the leader, null for repo without contributions
+    #[serde(deserialize_with = "crate::github_api::nan_if_null")]
+    pub bus_factor: f64,
+    pub stars: u64,
+    /// When the record was written
+    pub ts: DateTime<Utc>,
+}
+
+impl ReportRecord {
+    pub fn new(run_id: &str, language: &str, repo: &BusFactor, ts: DateTime<Utc>) -> Self {
+        Self {
+            run_id: run_id.to_string(),
+            language: language.to_string(),
+            repo: if repo.full_name.is_empty() {
+                repo.repo_name.clone()
+            } else {
+                repo.full_name.clone()
+            },
+            leader: repo.leader.user_name.clone(),
+            bus_factor: repo.leader.bus_factor,
+            stars: repo.stars,
+            ts,
+        }
+    }
+}
+
+/// Writes results as ReportRecord lines, as they come
+pub struct NdjsonWriter<W: Write> {
+    writer: W,
+    run_id: String,
+    language: String,
+}
+
+impl<W: Write> NdjsonWriter<W> {
+    pub fn new(writer: W, run_id: &str, language: &str) -> Self {
+        Self {
+            writer,
+            run_id: run_id.to_string(),
+            language: language.to_string(),
+        }
+    }
+
+    pub fn write(&mut self, repo: &BusFactor) -> io::Result<()> {
+        let record = ReportRecord::new(&self.run_id, &self.language, repo, Utc::now());
+        serde_json::to_writer(&mut self.writer, &record)?;
+        self.writer.write_all(b"\n")?;
+
+        self.writer.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Escapes value, so it does not break the row. Tab separated values have no quoting,
+/// tabs and new lines are written as \t and \n. For other delimiters value is quoted,
+/// if needed, as in CSV.
+fn escape(value: &str, delimiter: char) -> String {
+    if delimiter == '\t' {
+        return value
+            .replace('\\', "\\\\")
+            .replace('\t', "\\t")
+            .replace('\n', "\\n")
+            .replace('\r', "\\r");
+    }
+
+    if value.contains([delimiter, '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Writes header and rows as lines of values separated with the delimiter
+fn to_delimited(header: &[&str], rows: Vec<Vec<String>>, delimiter: char) -> String {
+    let line = |values: Vec<String>| {
+        values
+            .iter()
+            .map(|value| escape(value, delimiter))
+            .collect::<Vec<_>>()
+            .join(&delimiter.to_string())
+    };
+
+    let mut out = line(header.iter().map(|name| name.to_string()).collect());
+    out.push('\n');
+    for row in rows {
+        out.push_str(&line(row));
+        out.push('\n');
+    }
+
+    out
+}
+
+/// Writes results of all reports as delimited rows, one per repo
+pub fn results_to_delimited<'a>(
+    reports: impl IntoIterator<Item = &'a Report>,
+    delimiter: char,
+) -> String {
+    let rows = reports
+        .into_iter()
+        .flat_map(|report| {
+            report.results.iter().map(move |repo| {
+                vec![
+                    report.meta.language.clone(),
+                    repo.repo_name.clone(),
+                    repo.leader.user_name.clone(),
+                    share_text(repo, 4),
+                    risk_text(repo),
+                    repo.stars.to_string(),
+                ]
+            })
+        })
+        .collect();
+
+    to_delimited(
+        &["language", "project", "user", "share", "risk", "stars"],
+        rows,
+        delimiter,
+    )
+}
+
+/// Writes searched repos as delimited rows
+pub fn repos_to_delimited(repos: &[RepoData], delimiter: char) -> String {
+    let rows = repos
+        .iter()
+        .map(|repo| {
+            vec![
+                repo.name.clone(),
+                repo.stargazers_count.to_string(),
+                repo.html_url.clone(),
+            ]
+        })
+        .collect();
+
+    to_delimited(&["project", "stars", "url"], rows, delimiter)
+}
+
+/// Escapes text, so it's safe both as element content and attribute value
+fn escape_html(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+const HTML_STYLE: &str = "\
+body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; margin-bottom: 2em; }
+th, td { border: 1px solid #ccc; padding: 4px 8px; text-align: left; }
+th { cursor: pointer; background: #eee; }
+.Low { background: #d4edda; }
+.Medium { background: #fff3cd; }
+.High { background: #ffd8a8; }
+.Critical { background: #f8d7da; }
+";
+
+// Sorts table by the clicked column, numbers numerically, click again to reverse
+const HTML_SCRIPT: &str = "\
+document.querySelectorAll('th').forEach(function (th) {
+  th.addEventListener('click', function () {
+    var table = th.closest('table');
+    var body = table.tBodies[0];
+    var idx = Array.prototype.indexOf.call(th.parentNode.children, th);
+    var asc = th.dataset.order !== 'asc';
+    th.dataset.order = asc ? 'asc' : 'desc';
+    var rows = Array.prototype.slice.call(body.rows);
+    rows.sort(function (a, b) {
+      var x = a.cells[idx].dataset.value || a.cells[idx].textContent;
+      var y = b.cells[idx].dataset.value || b.cells[idx].textContent;
+      var cmp = isNaN(x) || isNaN(y) ? x.localeCompare(y) : x - y;
+      return asc ? cmp : -cmp;
+    });
+    rows.forEach(function (row) { body.appendChild(row); });
+  });
+});
+";
+
+/// Writes reports as a self-contained HTML page, one table per report
+pub fn results_to_html<'a>(reports: impl IntoIterator<Item = &'a Report>) -> String {
+    let mut out = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    out.push_str("<title>Bus factor</title>\n<style>\n");
+    out.push_str(HTML_STYLE);
+    out.push_str("</style>\n</head>\n<body>\n");
+
+    for report in reports {
+        out.push_str(&format!(
+            "<h2>{}</h2>\n<p>{} projects, generated {}</p>\n",
+            escape_html(&report.meta.language),
+            report.meta.project_count,
+            report.meta.timestamp.to_rfc3339()
+        ));
+        out.push_str("<table>\n<thead><tr><th>project</th><th>user</th><th>share</th><th>risk</th><th>stars</th></tr></thead>\n<tbody>\n");
+
+        for repo in &report.results {
+            // Risk is sorted by level, not alphabetically
+            out.push_str(&format!(
+                "<tr class=\"{risk}\"><td>{project}</td><td>{user}</td><td>{share}</td><td data-value=\"{level}\">{risk}</td><td>{stars}</td></tr>\n",
+                risk = risk_text(repo),
+                level = repo.risk_level as u8,
+                project = escape_html(&repo.repo_name),
+                user = escape_html(&repo.leader.user_name),
+                share = share_text(repo, 2),
+                stars = repo.stars
+            ));
+        }
+
+        out.push_str("</tbody>\n</table>\n");
+    }
+
+    out.push_str("<script>\n");
+    out.push_str(HTML_SCRIPT);
+    out.push_str("</script>\n</body>\n</html>\n");
+
+    out
+}
+
+#[derive(Debug, Default, PartialEq)]
+/// How many repos have given topic or language, most common first
+pub struct Facets {
+    pub topics: Vec<(String, usize)>,
+    pub languages: Vec<(String, usize)>,
+}
+
+/// Counts repos per topic and per language. Repo has usually many topics,
+/// so topic counts don't sum up to the number of repos
+pub fn facet_counts(results: &[BusFactor]) -> Facets {
+    let mut topics = BTreeMap::<&str, usize>::new();
+    let mut languages = BTreeMap::<&str, usize>::new();
+
+    for repo in results {
+        for topic in &repo.topics {
+            *topics.entry(topic).or_default() += 1;
+        }
+        if !repo.language.is_empty() {
+            *languages.entry(&repo.language).or_default() += 1;
+        }
+    }
+
+    // Map is ordered by name, stable sort keeps it for equal counts
+    let by_count = |counts: BTreeMap<&str, usize>| {
+        let mut counts: Vec<(String, usize)> = counts
+            .into_iter()
+            .map(|(name, count)| (name.to_string(), count))
+            .collect();
+        counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        counts
+    };
+
+    Facets {
+        topics: by_count(topics),
+        languages: by_count(languages),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// What repos are weighted by in the summary
+pub enum SummaryWeight {
+    Stars,
+    /// See GithubApi::add_usage
+    Usage,
+}
+
+#[derive(Debug, PartialEq)]
+/// Leader share over the whole ecosystem, popular repos count more
+pub struct Summary {
+    pub repos: usize,
+    pub weight: SummaryWeight,
+    /// Weighted mean of leader shares
+    pub leader_share: f64,
+}
+
+/// Summarizes results, weighted by usage if every repo has it, by stars otherwise,
+/// mixing both would compare different scales. Plain mean if all weights are 0.
+/// Repos without contributions have no share and are left out, None if nothing is left
+pub fn weighted_summary(results: &[BusFactor]) -> Option<Summary> {
+    let results: Vec<&BusFactor> = results.iter().filter(|repo| repo.has_share()).collect();
+    if results.is_empty() {
+        return None;
+    }
+
+    let weight = if results.iter().all(|repo| repo.usage.is_some()) {
+        SummaryWeight::Usage
+    } else {
+        SummaryWeight::Stars
+    };
+    let weight_of = |repo: &BusFactor| match weight {
+        SummaryWeight::Usage => repo.usage.unwrap_or_default() as f64,
+        SummaryWeight::Stars => repo.stars as f64,
+    };
+
+    let total: f64 = results.iter().copied().map(weight_of).sum();
+    let leader_share = if total > 0.0 {
+        results
+            .iter()
+            .map(|repo| repo.leader.bus_factor * weight_of(repo))
+            .sum::<f64>()
+            / total
+    } else {
+        results
+            .iter()
+            .map(|repo| repo.leader.bus_factor)
+            .sum::<f64>()
+            / results.len() as f64
+    };
+
+    Some(Summary {
+        repos: results.len(),
+        weight,
+        leader_share,
+    })
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+/// Whole run of a language in one line, for logs of scheduled jobs
+pub struct CompactSummary {
+    pub language: String,
+    /// Processed repos where the leader's share reaches the threshold
+    pub high_risk: u64,
+    pub processed: u64,
+    /// Repo with the highest leader's share, None if nothing was processed
+    pub worst: Option<String>,
+    pub worst_share: Option<f64>,
+    /// Repos skipped because of errors
+    pub errors: u64,
+    pub elapsed_sec: f64,
+}
+
+impl CompactSummary {
+    pub fn new(language: &str, metrics: &RunMetrics, errors: u64, elapsed: Duration) -> Self {
+        Self {
+            language: language.to_string(),
+            high_risk: metrics.high_risk,
+            processed: metrics.repos_processed,
+            worst: metrics.worst.as_ref().map(|(name, _)| name.clone()),
+            worst_share: metrics.worst.as_ref().map(|(_, share)| *share),
+            errors,
+            elapsed_sec: elapsed.as_secs_f64(),
+        }
+    }
+
+    /// Like "rust: 12/50 repos high-risk, worst tokio-rs/x at 91%, 0 errors, 3.2s"
+    pub fn to_text(&self) -> String {
+        let worst = match (&self.worst, self.worst_share) {
+            (Some(worst), Some(share)) => format!(" worst {} at {:.0}%,", worst, share * 100.0),
+            _ => String::new(),
+        };
+
+        format!(
+            "{}: {}/{} repos high-risk,{} {} errors, {:.1}s",
+            self.language, self.high_risk, self.processed, worst, self.errors, self.elapsed_sec
+        )
+    }
+
+    /// Values in the order of fields, without header, so lines of many runs can be appended
+    pub fn to_delimited(&self, delimiter: char) -> String {
+        let row = [
+            self.language.clone(),
+            self.high_risk.to_string(),
+            self.processed.to_string(),
+            self.worst.clone().unwrap_or_default(),
+            self.worst_share
+                .map(|share| format!("{:.4}", share))
+                .unwrap_or_default(),
+            self.errors.to_string(),
+            format!("{:.3}", self.elapsed_sec),
+        ];
+
+        row.iter()
+            .map(|value| escape(value, delimiter))
+            .collect::<Vec<_>>()
+            .join(&delimiter.to_string())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+/// Parameters of the run that produced the report
+pub struct ReportMeta {
+    pub timestamp: DateTime<Utc>,
+    pub language: String,
+    pub project_count: u32,
+    /// See run_id, empty in reports written by older versions
+    #[serde(default)]
+    pub run_id: String,
+    /// Version of bus_factor that wrote the report, empty in reports written by older versions
+    #[serde(default)]
+    pub version: String,
+    /// Search query the results come from, see GithubApi::search_query. None without a search,
+    /// like for a local clone, or in reports written by older versions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub search_query: Option<String>,
+    /// Repos the search found, before any of them were filtered out, 0 if nothing matched.
+    /// None without a search, or in reports written by older versions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_count: Option<u64>,
+}
+
+impl ReportMeta {
+    pub fn new(language: &str, project_count: u32) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            language: language.to_string(),
+            project_count,
+            run_id: run_id().to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            search_query: None,
+            total_count: None,
+        }
+    }
+
+    /// Line telling where the report comes from, like
+    /// "# bus_factor 0.1.0, run 20211224T101530.123Z-4242, 2021-12-24T10:15:30+00:00".
+    /// Goes before text and delimited output, it's not one of the rows
+    pub fn comment(&self) -> String {
+        format!(
+            "# bus_factor {}, run {}, {}",
+            self.version,
+            self.run_id,
+            self.timestamp.to_rfc3339()
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+/// Outcome of a single run, together with information how it was obtained
+pub struct Report {
+    pub meta: ReportMeta,
+    pub results: Vec<BusFactor>,
+    /// Raised while the report was made, like of skipped repos, see GithubApi::take_warnings.
+    /// Empty in reports written by older versions
+    #[serde(default)]
+    pub warnings: Vec<RunWarning>,
+}
+
+impl Report {
+    pub fn new(language: &str, project_count: u32, results: Vec<BusFactor>) -> Self {
+        Self {
+            meta: ReportMeta::new(language, project_count),
+            results,
+            warnings: Vec::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+/// Reports of several languages from one run, keyed by language
+pub struct BatchReport {
+    pub languages: BTreeMap<String, Report>,
+    /// Languages that were not recognized by the search
+    pub skipped: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_approx_eq::assert_approx_eq;
+
+    use super::*;
+
+    #[test]
+    fn output_format_from_str() {
+        assert_eq!("text".parse::<OutputFormat>().unwrap(), OutputFormat::Text);
+        assert_eq!("json".parse::<OutputFormat>().unwrap(), OutputFormat::Json);
+        assert_eq!("tsv".parse::<OutputFormat>().unwrap(), OutputFormat::Tsv);
+        assert!("yaml".parse::<OutputFormat>().is_err());
+    }
+
+    fn report() -> Report {
+        use crate::github_api::UserShare;
+
+        Report::new(
+            "rust",
+            1,
+            vec![BusFactor::new(
+                "tab\there, \"quoted\"",
+                10,
+                UserShare {
+                    bus_factor: 0.8,
+                    user_name: "new\nline".to_string(),
+                    ..UserShare::default()
+                },
+            )],
+        )
+    }
+
+    #[test]
+    fn wide_characters_are_aligned() {
+        assert_eq!(fit_width("serde", 8), "serde   ");
+        // Each takes two columns
+        assert_eq!(fit_width("日本語", 8), "日本語  ");
+        assert_eq!(fit_width("🦀rs", 8), "🦀rs    ");
+
+        assert_eq!(fit_width("very-long-repo-name", 8), "very-lo…");
+        // Cut does not split the wide character, the gap is padded
+        assert_eq!(fit_width("日本語の名前", 8), "日本語… ");
+
+        for name in ["serde", "日本語の名前", "🦀🦀🦀🦀🦀", "x"] {
+            assert_eq!(fit_width(name, 8).width(), 8, "{}", name);
+        }
+    }
+
+    #[test]
+    fn age_is_in_days_or_years() {
+        let at: DateTime<Utc> = "2021-12-24T10:15:30Z".parse().unwrap();
+        let age = |created_at: &str| age_text(created_at.parse().unwrap(), at);
+
+        assert_eq!(age("2021-12-24T00:00:00Z"), "0d");
+        assert_eq!(age("2021-09-01T10:15:30Z"), "114d");
+        assert_eq!(age("2015-10-21T07:28:00Z"), "6.2y");
+        // Clocks can disagree
+        assert_eq!(age("2021-12-25T00:00:00Z"), "0d");
+    }
+
+    #[test]
+    fn column_fits_widest_value() {
+        assert_eq!(column_width(["a", "日本語", "abcd"], 40), 6);
+        assert_eq!(column_width(["a-very-long-repository-name"], 10), 10);
+        assert_eq!(column_width([], 10), 0);
+    }
+
+    #[test]
+    fn csv_quotes_values() {
+        assert_eq!(
+            results_to_delimited(std::iter::once(&report()), ','),
+            "language,project,user,share,risk,stars\n\
+             rust,\"tab\there, \"\"quoted\"\"\",\"new\nline\",0.8000,High,10\n"
+        );
+    }
+
+    #[test]
+    fn tsv_escapes_tabs_and_new_lines() {
+        assert_eq!(
+            results_to_delimited(std::iter::once(&report()), '\t'),
+            "language\tproject\tuser\tshare\trisk\tstars\n\
+             rust\ttab\\there, \"quoted\"\tnew\\nline\t0.8000\tHigh\t10\n"
+        );
+    }
+
+    #[test]
+    fn html_escapes_names() {
+        let mut report = report();
+        report.results[0].repo_name = "<script>alert('x')</script>".to_string();
+
+        let html = results_to_html(std::iter::once(&report));
+
+        assert!(html.contains("<td>&lt;script&gt;alert(&#39;x&#39;)&lt;/script&gt;</td>"));
+        assert!(html.contains("<tr class=\"High\">"));
+        assert!(html.contains("<h2>rust</h2>"));
+    }
+
+    #[test]
+    fn repo_without_contributors_is_not_available() {
+        let mut report = report();
+        report
+            .results
+            .push(BusFactor::without_contributors("empty", 2));
+
+        assert!(results_to_delimited(std::iter::once(&report), ',')
+            .ends_with("\nrust,empty,,N/A,N/A,2\n"));
+        assert!(results_to_html(std::iter::once(&report))
+            .contains("<td>empty</td><td></td><td>N/A</td>"));
+
+        let json = serde_json::to_string(&report.results[1]).unwrap();
+        assert!(json.contains(r#""bus_factor":null"#), "{}", json);
+        assert!(json.contains(r#""no_contributors":true"#), "{}", json);
+        let parsed: BusFactor = serde_json::from_str(&json).unwrap();
+        assert!(parsed.no_contributors && parsed.leader.bus_factor.is_nan());
+
+        // Share of the other repo only
+        assert_approx_eq!(weighted_summary(&report.results).unwrap().leader_share, 0.8);
+    }
+
+    #[test]
+    fn warnings_are_part_of_json_report() {
+        use crate::warnings::WarningCategory;
+
+        let mut report = report();
+        report.warnings.push(RunWarning {
+            category: WarningCategory::SkippedRepo,
+            message: "Project huge failed".to_string(),
+        });
+
+        let json = to_json(&report, false).unwrap();
+        assert!(
+            json.ends_with(
+                r#""warnings":[{"category":"skipped-repo","message":"Project huge failed"}]}"#
+            ),
+            "{}",
+            json
+        );
+
+        // Written before warnings were there
+        let mut older = serde_json::to_value(&report).unwrap();
+        older.as_object_mut().unwrap().remove("warnings");
+        assert!(serde_json::from_value::<Report>(older)
+            .unwrap()
+            .warnings
+            .is_empty());
+    }
+
+    #[test]
+    fn facets_are_counted() {
+        let repo = |language: &str, topics: &[&str]| BusFactor {
+            language: language.to_string(),
+            topics: topics.iter().map(|topic| topic.to_string()).collect(),
+            ..report().results.remove(0)
+        };
+        let results = vec![
+            repo("Rust", &["cli", "async"]),
+            repo("Rust", &["cli"]),
+            repo("C", &["embedded"]),
+            repo("", &[]),
+        ];
+
+        let facets = facet_counts(&results);
+
+        assert_eq!(
+            facets.topics,
+            vec![
+                ("cli".to_string(), 2),
+                ("async".to_string(), 1),
+                ("embedded".to_string(), 1)
+            ]
+        );
+        assert_eq!(
+            facets.languages,
+            vec![("Rust".to_string(), 2), ("C".to_string(), 1)]
+        );
+    }
+
+    #[test]
+    fn summary_is_weighted_by_usage_if_all_repos_have_it() {
+        let repo = |bus_factor: f64, stars: u64, usage: Option<u64>| {
+            let mut repo = report().results.remove(0);
+            repo.leader.bus_factor = bus_factor;
+            repo.stars = stars;
+            repo.usage = usage;
+            repo
+        };
+
+        let by_usage = weighted_summary(&[repo(1.0, 100, Some(1)), repo(0.5, 1, Some(3))]).unwrap();
+        assert_eq!(by_usage.weight, SummaryWeight::Usage);
+        assert_approx_eq!(by_usage.leader_share, 0.625);
+
+        // One repo without usage, all are weighted by stars
+        let by_stars = weighted_summary(&[repo(1.0, 3, Some(1)), repo(0.5, 1, None)]).unwrap();
+        assert_eq!(by_stars.weight, SummaryWeight::Stars);
+        assert_approx_eq!(by_stars.leader_share, 0.875);
+
+        let unweighted = weighted_summary(&[repo(1.0, 0, None), repo(0.5, 0, None)]).unwrap();
+        assert_approx_eq!(unweighted.leader_share, 0.75);
+
+        assert_eq!(weighted_summary(&[]), None);
+    }
+
+    #[test]
+    fn cli_enums_are_parsed() {
+        assert_eq!(
+            "json-array".parse::<OutputFormat>().unwrap(),
+            OutputFormat::JsonArray
+        );
+        assert_eq!("share".parse::<SortBy>().unwrap(), SortBy::Share);
+        assert_eq!("asc".parse::<SortOrder>().unwrap(), SortOrder::Asc);
+        assert_eq!("never".parse::<ColorChoice>().unwrap(), ColorChoice::Never);
+
+        let err = "up".parse::<SortOrder>().unwrap_err();
+        assert!(
+            err.to_string().contains("expected one of: asc, desc"),
+            "{}",
+            err
+        );
+        assert!("xml".parse::<OutputFormat>().is_err());
+        assert!("forks".parse::<SortBy>().is_err());
+        assert!("yes".parse::<ColorChoice>().is_err());
+    }
+
+    #[test]
+    fn contributors_are_sorted() {
+        let user = |name: &str, share: f64| UserShare {
+            user_name: name.to_string(),
+            bus_factor: share,
+            ..UserShare::default()
+        };
+        let mut contributors = vec![user("bob", 0.2), user("carol", 0.6), user("alice", 0.2)];
+        let names = |contributors: &[UserShare]| -> Vec<String> {
+            contributors
+                .iter()
+                .map(|user| user.user_name.clone())
+                .collect()
+        };
+
+        sort_contributors(&mut contributors, ContributorOrder::Share);
+        assert_eq!(names(&contributors), ["carol", "alice", "bob"]);
+
+        sort_contributors(&mut contributors, ContributorOrder::Name);
+        assert_eq!(names(&contributors), ["alice", "bob", "carol"]);
+
+        assert_eq!(
+            "name".parse::<ContributorOrder>().unwrap(),
+            ContributorOrder::Name
+        );
+        assert!("stars".parse::<ContributorOrder>().is_err());
+    }
+
+    #[test]
+    fn results_are_sorted_by_key_and_order() {
+        let repo = |name: &str, stars: u64| BusFactor {
+            repo_name: name.to_string(),
+            stars,
+            ..report().results.remove(0)
+        };
+        let mut results = vec![repo("b", 1), repo("c", 3), repo("a", 2)];
+        let names = |results: &[BusFactor]| -> Vec<String> {
+            results.iter().map(|repo| repo.repo_name.clone()).collect()
+        };
+
+        sort_results(&mut results, SortBy::Stars, SortOrder::Desc);
+        assert_eq!(names(&results), ["c", "a", "b"]);
+
+        sort_results(&mut results, SortBy::Name, SortOrder::Asc);
+        assert_eq!(names(&results), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn only_riskiest_are_kept() {
+        let repo = |name: &str, share: f64| BusFactor {
+            repo_name: name.to_string(),
+            leader: UserShare {
+                user_name: "leader".to_string(),
+                bus_factor: share,
+                ..UserShare::default()
+            },
+            ..report().results.remove(0)
+        };
+        let mut results = vec![
+            repo("low", 0.2),
+            BusFactor::without_contributors("empty", 1),
+            repo("critical", 0.95),
+            repo("high", 0.8),
+        ];
+
+        assert_eq!(keep_riskiest(&mut results, 2), 2);
+        let names: Vec<&str> = results.iter().map(|repo| repo.repo_name.as_str()).collect();
+        assert_eq!(names, ["critical", "high"]);
+
+        // Json has only the kept ones
+        let parsed: Vec<BusFactor> =
+            serde_json::from_str(&to_json(&results, false).unwrap()).unwrap();
+        assert_eq!(parsed.len(), 2);
+
+        // Score decides, if there is one
+        let mut results = vec![
+            BusFactor {
+                score: Some(0.9),
+                ..repo("scored", 0.1)
+            },
+            repo("shared", 0.5),
+        ];
+        assert_eq!(keep_riskiest(&mut results, 5), 0);
+        assert_eq!(results[0].repo_name, "scored");
+
+        // Always included ones don't count to the top
+        let mut results = vec![
+            repo("high", 0.8),
+            BusFactor {
+                always_included: true,
+                ..repo("included", 0.1)
+            },
+            repo("critical", 0.95),
+        ];
+        assert_eq!(keep_riskiest(&mut results, 1), 1);
+        let names: Vec<&str> = results.iter().map(|repo| repo.repo_name.as_str()).collect();
+        assert_eq!(names, ["critical", "included"]);
+    }
+
+    #[test]
+    fn only_high_risk_is_painted() {
+        assert_eq!(paint_risk(RiskLevel::Low, "Low"), "Low");
+        assert_eq!(
+            paint_risk(RiskLevel::Critical, "Critical"),
+            "\x1b[31mCritical\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn json_array_is_valid_for_any_length() {
+        for count in [0, 1, 5].iter().cloned() {
+            let mut writer = JsonArrayWriter::new(Vec::new()).unwrap();
+            for _ in 0..count {
+                writer.write(&report().results[0]).unwrap();
+            }
+            let out = writer.finish().unwrap();
+
+            let parsed: Vec<BusFactor> = serde_json::from_slice(&out).unwrap();
+            assert_eq!(parsed.len(), count);
+        }
+    }
+
+    #[test]
+    fn ndjson_record_schema_is_stable() {
+        let ts = "2021-12-24T10:15:30Z".parse().unwrap();
+        let repo = BusFactor {
+            full_name: "owner/repo".to_string(),
+            ..BusFactor::new(
+                "repo",
+                3,
+                UserShare {
+                    bus_factor: 0.5,
+                    user_name: "leader".to_string(),
+                    ..UserShare::default()
+                },
+            )
+        };
+
+        let line = serde_json::to_string(&ReportRecord::new("run", "rust", &repo, ts)).unwrap();
+
+        assert_eq!(
+            line,
+            r#"{"run_id":"run","language":"rust","repo":"owner/repo","leader":"leader","bus_factor":0.5,"stars":3,"ts":"2021-12-24T10:15:30Z"}"#
+        );
+    }
+
+    #[test]
+    fn ndjson_writer_writes_line_per_result() {
+        let mut writer = NdjsonWriter::new(Vec::new(), "run", "rust");
+        for repo in &report().results {
+            writer.write(repo).unwrap();
+        }
+        let out = String::from_utf8(writer.into_inner()).unwrap();
+
+        let records: Vec<ReportRecord> = out
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.len(), report().results.len());
+        assert!(records
+            .iter()
+            .all(|record| record.run_id == "run" && record.language == "rust"));
+        // Name is used when full name is unknown
+        assert_eq!(records[0].repo, report().results[0].repo_name);
+    }
+
+    #[test]
+    fn compact_summary_is_one_line() {
+        let mut metrics = RunMetrics::default();
+        let repo = |full_name: &str, share: f64| BusFactor {
+            full_name: full_name.to_string(),
+            ..BusFactor::new(
+                "x",
+                1,
+                UserShare {
+                    bus_factor: share,
+                    user_name: "leader".to_string(),
+                    ..UserShare::default()
+                },
+            )
+        };
+        metrics.add_results(&[repo("tokio-rs/x", 0.91), repo("o/y", 0.2)], 0.75);
+
+        let summary = CompactSummary::new("rust", &metrics, 0, Duration::from_millis(3240));
+
+        assert_eq!(
+            summary.to_text(),
+            "rust: 1/2 repos high-risk, worst tokio-rs/x at 91%, 0 errors, 3.2s"
+        );
+        assert_eq!(
+            summary.to_delimited(','),
+            "rust,1,2,tokio-rs/x,0.9100,0,3.240"
+        );
+        assert_eq!(
+            serde_json::to_string(&summary).unwrap(),
+            r#"{"language":"rust","high_risk":1,"processed":2,"worst":"tokio-rs/x","worst_share":0.91,"errors":0,"elapsed_sec":3.24}"#
+        );
+
+        let empty = CompactSummary::new("go", &RunMetrics::default(), 2, Duration::from_secs(1));
+        assert_eq!(empty.to_text(), "go: 0/0 repos high-risk, 2 errors, 1.0s");
+    }
+
+    #[test]
+    fn report_is_compact_unless_pretty() {
+        let report = Report::new("rust", 0, vec![]);
+
+        let compact = to_json(&report, false).unwrap();
+        assert!(!compact.contains('\n'));
+
+        let pretty = to_json(&report, true).unwrap();
+        assert!(pretty.contains("\n  \"meta\": {"));
+
+        // Both describe the same report
+        let compact: serde_json::Value = serde_json::from_str(&compact).unwrap();
+        let pretty: serde_json::Value = serde_json::from_str(&pretty).unwrap();
+        assert_eq!(compact, pretty);
+    }
+
+    #[test]
+    fn meta_tells_where_report_comes_from() {
+        let report = Report::new("rust", 0, vec![]);
+
+        let json: serde_json::Value =
+            serde_json::from_str(&to_json(&report, false).unwrap()).unwrap();
+        assert_eq!(json["meta"]["run_id"], run_id());
+        assert_eq!(json["meta"]["version"], env!("CARGO_PKG_VERSION"));
+        // Results stay as they were
+        assert_eq!(json["results"], serde_json::json!([]));
+
+        let comment = report.meta.comment();
+        assert!(comment.starts_with(&format!(
+            "# bus_factor {}, run {}, ",
+            env!("CARGO_PKG_VERSION"),
+            run_id()
+        )));
+        assert!(!comment.contains('\n'));
+
+        // Reports written before are still read
+        let older: ReportMeta = serde_json::from_str(
+            r#"{"timestamp": "2021-12-24T10:15:30Z", "language": "rust", "project_count": 1}"#,
+        )
+        .unwrap();
+        assert!(older.run_id.is_empty() && older.version.is_empty());
+    }
+}
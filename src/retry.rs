@@ -0,0 +1,345 @@
+use std::{error::Error, io, time::Duration};
+
+use chrono::{DateTime, TimeZone, Utc};
+use reqwest::{
+    header::{HeaderMap, RETRY_AFTER},
+    StatusCode,
+};
+
+use crate::rng::Rng;
+
+/// Wait used when server asks to back off, but it's not clear for how long
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone)]
+/// Describes how failed requests are repeated
+pub struct RetryPolicy {
+    /// How many times request is repeated after the first failure
+    pub max_retries: u32,
+    /// Delay before first retry, doubled for each subsequent one
+    pub backoff: Duration,
+    /// Part of the delay that is random, so clients that failed together don't retry
+    /// together. 0 keeps delays exact, 1 makes them anything up to the full one
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    /// No retries, request fails on first error
+    fn default() -> Self {
+        Self {
+            max_retries: 0,
+            backoff: Duration::from_secs(1),
+            jitter: 0.0,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Returns delay before given retry, attempts are numbered from 0
+    pub fn delay(&self, attempt: u32) -> Duration {
+        // Cap the exponent, so delay does not overflow
+        self.backoff * 2u32.pow(attempt.min(16))
+    }
+
+    /// Delay before given retry, with a random part of it taken off, see jitter
+    pub fn jittered_delay(&self, attempt: u32, rng: &mut Rng) -> Duration {
+        let delay = self.delay(attempt);
+        if self.jitter <= 0.0 {
+            return delay;
+        }
+        delay.mul_f64(1.0 - self.jitter.min(1.0) * rng.unit())
+    }
+
+    /// Server errors and throttling are worth another try, rest of failures
+    /// will not change no matter how many times request is repeated
+    pub fn is_retryable(status: StatusCode) -> bool {
+        status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+    }
+}
+
+/// Tells if request failed before getting any response for a reason that may be gone
+/// on the next try, like connection reset or timeout. Failed DNS lookup is permanent,
+/// the name will not resolve on retry.
+pub fn is_transient_error(err: &(dyn Error + 'static)) -> bool {
+    let mut transient = false;
+    let mut source = Some(err);
+
+    while let Some(err) = source {
+        // Hyper does not expose DNS failure as a type, only in the message
+        let msg = err.to_string();
+        if msg.contains("dns error") || msg.contains("failed to lookup address") {
+            return false;
+        }
+
+        if let Some(err) = err.downcast_ref::<reqwest::Error>() {
+            transient |= err.is_timeout() || err.is_connect() || err.is_request();
+        }
+
+        if let Some(err) = err.downcast_ref::<io::Error>() {
+            transient |= matches!(
+                err.kind(),
+                io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::ConnectionRefused
+                    | io::ErrorKind::BrokenPipe
+                    | io::ErrorKind::TimedOut
+                    | io::ErrorKind::UnexpectedEof
+                    | io::ErrorKind::Interrupted
+            );
+        }
+
+        source = err.source();
+    }
+
+    transient
+}
+
+/// Parses value of Retry-After header, which is either number of seconds
+/// or HTTP-date. Date in the past means request can be repeated right away.
+/// Malformed value falls back to conservative wait.
+pub fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Duration {
+    let value = value.trim();
+
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Duration::from_secs(seconds);
+    }
+
+    // HTTP-date is a subset of RFC 2822, with GMT as a time zone
+    match DateTime::parse_from_rfc2822(value) {
+        Ok(date) => (date.with_timezone(&Utc) - now)
+            .to_std()
+            .unwrap_or(Duration::ZERO),
+        Err(_) => {
+            warn!(
+                "Malformed Retry-After value '{}', waiting {:?}",
+                value, DEFAULT_RETRY_AFTER
+            );
+            DEFAULT_RETRY_AFTER
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+/// Kind of throttling server applied to the request
+pub enum Throttle {
+    /// Primary rate limit, quota is used up until it resets
+    RateLimit { wait: Duration },
+    /// Secondary rate limit, also called abuse detection, client has to back off
+    Abuse { wait: Duration },
+}
+
+impl Throttle {
+    /// How long to wait before the next request
+    pub fn wait(&self) -> Duration {
+        match self {
+            Throttle::RateLimit { wait } | Throttle::Abuse { wait } => *wait,
+        }
+    }
+}
+
+/// Time until the quota resets, from X-RateLimit-Reset with epoch seconds of the reset.
+/// None if the header is missing or malformed
+pub fn rate_limit_reset(headers: &HeaderMap, now: DateTime<Utc>) -> Option<Duration> {
+    let reset = headers
+        .get("x-ratelimit-reset")?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<i64>()
+        .ok()
+        .and_then(|epoch| Utc.timestamp_opt(epoch, 0).single())?;
+
+    Some((reset - now).to_std().unwrap_or(Duration::ZERO))
+}
+
+/// Tells apart rate limits github reports with 403 or 429:
+/// - primary one has X-RateLimit-Remaining: 0, and X-RateLimit-Reset with epoch seconds of the reset
+/// - secondary one has Retry-After, and quota left
+///
+/// Returns None if response is not throttled, like 403 for lack of permissions.
+pub fn classify_throttle(
+    status: StatusCode,
+    headers: &HeaderMap,
+    now: DateTime<Utc>,
+) -> Option<Throttle> {
+    if status != StatusCode::FORBIDDEN && status != StatusCode::TOO_MANY_REQUESTS {
+        return None;
+    }
+
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+    let retry_after = header(RETRY_AFTER.as_str()).map(|value| parse_retry_after(value, now));
+
+    if header("x-ratelimit-remaining").map(str::trim) == Some("0") {
+        let wait = match (rate_limit_reset(headers, now), retry_after) {
+            (Some(reset), _) => reset,
+            (None, Some(retry_after)) => retry_after,
+            (None, None) => DEFAULT_RETRY_AFTER,
+        };
+
+        return Some(Throttle::RateLimit { wait });
+    }
+
+    retry_after.map(|wait| Throttle::Abuse { wait })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delay_grows_exponentially() {
+        let policy = RetryPolicy {
+            max_retries: 3,
+            backoff: Duration::from_millis(100),
+            ..RetryPolicy::default()
+        };
+
+        assert_eq!(policy.delay(0), Duration::from_millis(100));
+        assert_eq!(policy.delay(1), Duration::from_millis(200));
+        assert_eq!(policy.delay(2), Duration::from_millis(400));
+    }
+
+    #[test]
+    fn same_seed_gives_same_jitter() {
+        let policy = RetryPolicy {
+            max_retries: 3,
+            backoff: Duration::from_millis(100),
+            jitter: 0.5,
+        };
+        let delays = |seed: u64| -> Vec<Duration> {
+            let mut rng = Rng::seeded(seed);
+            (0..3)
+                .map(|attempt| policy.jittered_delay(attempt, &mut rng))
+                .collect()
+        };
+
+        assert_eq!(delays(1), delays(1));
+        assert_ne!(delays(1), delays(2));
+        for (attempt, delay) in delays(1).into_iter().enumerate() {
+            let full = policy.delay(attempt as u32);
+            assert!(
+                delay <= full && delay >= full / 2,
+                "{:?} of {:?}",
+                delay,
+                full
+            );
+        }
+
+        // Without jitter nothing is drawn, delays are exact
+        let exact = RetryPolicy {
+            jitter: 0.0,
+            ..policy.clone()
+        };
+        assert_eq!(
+            exact.jittered_delay(1, &mut Rng::seeded(1)),
+            Duration::from_millis(200)
+        );
+    }
+
+    #[test]
+    fn only_transient_statuses_are_retried() {
+        assert!(RetryPolicy::is_retryable(StatusCode::BAD_GATEWAY));
+        assert!(RetryPolicy::is_retryable(StatusCode::TOO_MANY_REQUESTS));
+        assert!(!RetryPolicy::is_retryable(StatusCode::NOT_FOUND));
+        assert!(!RetryPolicy::is_retryable(StatusCode::UNPROCESSABLE_ENTITY));
+    }
+
+    #[test]
+    fn retry_after_in_seconds() {
+        assert_eq!(parse_retry_after("30", Utc::now()), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn retry_after_as_http_date() {
+        let now = DateTime::parse_from_rfc3339("2015-10-21T07:27:30Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT", now),
+            Duration::from_secs(30)
+        );
+
+        // Date already passed, no need to wait
+        let later = now + chrono::Duration::minutes(5);
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT", later),
+            Duration::ZERO
+        );
+    }
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.parse().unwrap(), value.parse().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn exhausted_quota_is_rate_limit() {
+        let now = Utc.timestamp_opt(1_600_000_000, 0).unwrap();
+        let headers = headers(&[
+            ("x-ratelimit-remaining", "0"),
+            ("x-ratelimit-reset", "1600000120"),
+        ]);
+
+        assert_eq!(
+            classify_throttle(StatusCode::FORBIDDEN, &headers, now),
+            Some(Throttle::RateLimit {
+                wait: Duration::from_secs(120)
+            })
+        );
+    }
+
+    #[test]
+    fn retry_after_with_quota_left_is_abuse() {
+        let headers = headers(&[("retry-after", "45"), ("x-ratelimit-remaining", "4000")]);
+
+        assert_eq!(
+            classify_throttle(StatusCode::FORBIDDEN, &headers, Utc::now()),
+            Some(Throttle::Abuse {
+                wait: Duration::from_secs(45)
+            })
+        );
+    }
+
+    #[test]
+    fn forbidden_without_limit_headers_is_not_throttled() {
+        let headers = headers(&[("x-ratelimit-remaining", "4000")]);
+
+        assert_eq!(
+            classify_throttle(StatusCode::FORBIDDEN, &headers, Utc::now()),
+            None
+        );
+        assert_eq!(
+            classify_throttle(StatusCode::NOT_FOUND, &HeaderMap::new(), Utc::now()),
+            None
+        );
+    }
+
+    #[test]
+    fn connection_reset_is_transient() {
+        let err = io::Error::new(io::ErrorKind::ConnectionReset, "connection reset by peer");
+        assert!(is_transient_error(&err));
+
+        let err = io::Error::new(io::ErrorKind::PermissionDenied, "permission denied");
+        assert!(!is_transient_error(&err));
+    }
+
+    #[test]
+    fn dns_failure_is_permanent() {
+        // Failed lookup wrapped in an otherwise transient error
+        let dns = io::Error::other(
+            "dns error: failed to lookup address information: Name or service not known",
+        );
+        let err = io::Error::new(io::ErrorKind::TimedOut, dns);
+
+        assert!(!is_transient_error(&err));
+    }
+
+    #[test]
+    fn malformed_retry_after_waits_conservatively() {
+        assert_eq!(parse_retry_after("soon", Utc::now()), DEFAULT_RETRY_AFTER);
+        assert_eq!(parse_retry_after("-5", Utc::now()), DEFAULT_RETRY_AFTER);
+    }
+}
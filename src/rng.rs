@@ -0,0 +1,94 @@
+//! Random numbers for jitter and sampling, seedable so a run can be repeated exactly.
+//! Everything random takes its numbers from an Rng it is given, nothing draws them on its own.
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
+
+/// FNV-1a hash of the text, the same on every run and platform, unlike the std hasher
+pub fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xCBF2_9CE4_8422_2325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01B3)
+    })
+}
+
+/// Small, seedable generator (SplitMix64), statistical quality is not a concern here
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    /// The same seed gives the same numbers
+    pub fn seeded(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    /// Seeded differently on every run
+    pub fn from_entropy() -> Self {
+        // Keys of RandomState are random for each process
+        Self(RandomState::new().build_hasher().finish())
+    }
+
+    /// Seeded if one is given, from entropy otherwise
+    pub fn new(seed: Option<u64>) -> Self {
+        seed.map_or_else(Self::from_entropy, Self::seeded)
+    }
+
+    /// Seeded by the seed, key and number together, so numbers for a key don't depend on
+    /// what was drawn for other keys before, like by other tasks
+    pub fn derived(seed: u64, key: &str, n: u64) -> Self {
+        let mut rng = Self::seeded(seed ^ fnv1a(key));
+        Self::seeded(rng.next_u64() ^ n)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// In [0, 1)
+    pub fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// In [min, max], max included
+    pub fn between(&mut self, min: u64, max: u64) -> u64 {
+        if max <= min {
+            return min;
+        }
+        match (max - min).checked_add(1) {
+            Some(span) => min + self.next_u64() % span,
+            None => self.next_u64(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_gives_same_numbers() {
+        let numbers =
+            |mut rng: Rng| -> Vec<u64> { (0..10).map(|_| rng.between(0, 1000)).collect() };
+
+        assert_eq!(numbers(Rng::seeded(7)), numbers(Rng::seeded(7)));
+        assert_ne!(numbers(Rng::seeded(7)), numbers(Rng::seeded(8)));
+        assert!(numbers(Rng::seeded(7)).iter().all(|&n| n <= 1000));
+
+        let mut rng = Rng::new(None);
+        assert!((0.0..1.0).contains(&rng.unit()));
+    }
+
+    #[test]
+    fn derived_depends_only_on_its_inputs() {
+        let first = |seed, key, n| Rng::derived(seed, key, n).next_u64();
+
+        assert_eq!(first(7, "a", 1), first(7, "a", 1));
+        assert_ne!(first(7, "a", 1), first(7, "a", 2));
+        assert_ne!(first(7, "a", 1), first(7, "b", 1));
+        assert_ne!(first(7, "a", 1), first(8, "a", 1));
+    }
+}
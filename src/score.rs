@@ -0,0 +1,27 @@
+//! Scores telling how risky a repo is, significance threshold is compared with them.
+use crate::{github_data::RepoData, share::RepoShare};
+
+/// Scores a repo from its shares and metadata, the higher the riskier.
+/// Repo is significant when its score reaches the threshold. Closures taking
+/// the share and the repo are scorers too
+pub trait Scorer: Send + Sync {
+    fn score(&self, share: &RepoShare, repo: &RepoData) -> f64;
+}
+
+/// Leader's share, what the threshold is compared with unless another scorer is given
+pub struct LeaderShare;
+
+impl Scorer for LeaderShare {
+    fn score(&self, share: &RepoShare, _repo: &RepoData) -> f64 {
+        share.leader.bus_factor
+    }
+}
+
+impl<F> Scorer for F
+where
+    F: Fn(&RepoShare, &RepoData) -> f64 + Send + Sync,
+{
+    fn score(&self, share: &RepoShare, repo: &RepoData) -> f64 {
+        self(share, repo)
+    }
+}
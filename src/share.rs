@@ -0,0 +1,568 @@
+//! Pure computation of contributors' shares, independent of where the data came from.
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api_errors::InvalidQueryError,
+    github_api::UserShare,
+    github_data::{self, Contributions, ContributorData},
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+/// What is counted as a unit of work when calculating shares
+pub enum ShareBasis {
+    /// Commits attributed to the login, as reported by the contributors endpoint
+    #[default]
+    Commits,
+    /// Added and removed lines, not implemented yet
+    LinesChanged,
+    /// Merged pull requests authored by the login, as found by the search of issues.
+    /// Costs search requests, which have a much lower rate limit
+    PullRequests,
+}
+
+impl FromStr for ShareBasis {
+    type Err = InvalidQueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "commits" => Ok(ShareBasis::Commits),
+            "lines-changed" => Ok(ShareBasis::LinesChanged),
+            "pull-requests" => Ok(ShareBasis::PullRequests),
+            _ => Err(InvalidQueryError::new(&format!(
+                "Unknown share basis '{}', expected one of: commits, lines-changed, pull-requests",
+                s
+            ))),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+/// Origin of the contributor data used for calculation
+pub enum ShareSource {
+    /// Contributors endpoint of github API
+    Api,
+    /// Commits of a local clone, see local_git
+    LocalGit,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Outcome of calculating shares of contributors in a single repository
+pub struct RepoShare {
+    /// Most active contributor
+    pub leader: UserShare,
+    /// Shares of all considered contributors, in descending order
+    pub contributors: Vec<UserShare>,
+    /// Number of contributors taken into account
+    pub considered: usize,
+    /// Minimal number of top contributors that together did more than half of the work
+    pub bus_factor: usize,
+    /// Set if there might be more contributors than considered, so shares
+    /// are relative to the considered ones only
+    pub approximate: bool,
+    /// Sum of contributions shares are relative to
+    pub total_contributions: u64,
+    /// Contributions of the leader, its share is that over total_contributions
+    pub leader_contributions: u64,
+    /// Sum of contributions of the considered contributors, the same as
+    /// total_contributions unless normalized
+    pub considered_contributions: u64,
+    /// Set if shares are relative to all contributions in the repo, see normalize
+    pub normalized: bool,
+    pub source: ShareSource,
+}
+
+/// Calculates shares of contributors. Contributions are expected to be sorted
+/// in descending order, as returned by the API. Returns None if there are no
+/// contributions to consider.
+/// users_to_consider is the number of contributors that were requested,
+/// if that many were returned, list is probably truncated.
+/// If members are given, only their contributions are taken into account,
+/// members are expected to be github_data::login_key of the logins.
+pub fn compute_repo_share(
+    contributions: &[ContributorData],
+    users_to_consider: usize,
+    members: Option<&HashSet<String>>,
+    source: ShareSource,
+) -> Option<RepoShare> {
+    let approximate = contributions.len() >= users_to_consider;
+    let contributions: Vec<&ContributorData> = contributions
+        .iter()
+        .filter(|contr| {
+            members.is_none_or(|members| members.contains(&github_data::login_key(&contr.login)))
+        })
+        .collect();
+
+    let total_contributions: u64 = contributions.iter().map(|contr| contr.contributions).sum();
+
+    if total_contributions == 0 {
+        return None;
+    }
+
+    let contributors: Vec<UserShare> = contributions
+        .iter()
+        .map(|contr| UserShare {
+            user_name: contr.login.to_string(),
+            bus_factor: contr.contributions as f64 / total_contributions as f64,
+            profile_url: contr.html_url.clone(),
+            avatar_url: contr.avatar_url.clone(),
+        })
+        .collect();
+    let leader_contributions = contributions[0].contributions;
+
+    Some(RepoShare {
+        leader: contributors[0].clone(),
+        considered: contributors.len(),
+        approximate,
+        bus_factor: integer_bus_factor(&contributors),
+        contributors,
+        total_contributions,
+        leader_contributions,
+        considered_contributions: total_contributions,
+        normalized: false,
+        source,
+    })
+}
+
+/// Counts how many times each login authored something, like a pull request, so shares
+/// can be computed from that. Most active first, ties by login, so the order is stable
+pub fn count_authors<'a>(logins: impl IntoIterator<Item = &'a str>) -> Contributions {
+    let mut counts = HashMap::<&str, u64>::new();
+    for login in logins {
+        *counts.entry(login).or_default() += 1;
+    }
+
+    let mut contributions: Contributions = counts
+        .into_iter()
+        .map(|(login, contributions)| ContributorData {
+            login: login.to_string(),
+            contributions,
+            ..ContributorData::default()
+        })
+        .collect();
+    contributions.sort_by(|a, b| {
+        b.contributions
+            .cmp(&a.contributions)
+            .then_with(|| a.login.cmp(&b.login))
+    });
+
+    contributions
+}
+
+/// Counts how many top contributors are needed to cover more than half of the work.
+/// If they don't cover it all together, that's all of them.
+fn integer_bus_factor(contributors: &[UserShare]) -> usize {
+    let mut covered = 0.0;
+
+    contributors
+        .iter()
+        .take_while(|share| {
+            let done = covered > 0.5;
+            covered += share.bus_factor;
+            !done
+        })
+        .count()
+}
+
+/// Truck factor: minimal number of top contributors whose shares together reach the coverage,
+/// like 3 if three developers did 80% of the work, for coverage of 0.8. Contributors are expected
+/// to be sorted in descending order. If they don't reach it all together, like normalized shares
+/// of considered contributors, that's all of them
+pub fn truck_factor(contributors: &[UserShare], coverage: f64) -> usize {
+    // Sum of shares like 0.7 + 0.1 is a bit below 0.8
+    const TOLERANCE: f64 = 1e-9;
+    let mut covered = 0.0;
+
+    contributors
+        .iter()
+        .take_while(|share| {
+            let done = covered + TOLERANCE >= coverage;
+            covered += share.bus_factor;
+            !done
+        })
+        .count()
+}
+
+/// Makes shares relative to all contributions in the repo, instead of the considered ones.
+/// Without it, long tail of contributors outside of users_to_consider is ignored,
+/// which inflates every share. Total lower than sum of considered contributions
+/// (counted differently by the API) leaves shares as they are.
+pub fn normalize(share: RepoShare, repo_total: u64) -> RepoShare {
+    let total = repo_total.max(share.considered_contributions);
+    let considered = share.considered_contributions as f64;
+
+    // Back from shares to counts and over the new total, so a share is exactly
+    // contributions over the total, the same as without normalization
+    let contributors: Vec<UserShare> = share
+        .contributors
+        .into_iter()
+        .map(|user| UserShare {
+            bus_factor: (user.bus_factor * considered).round() / total as f64,
+            ..user
+        })
+        .collect();
+
+    RepoShare {
+        leader: contributors[0].clone(),
+        bus_factor: integer_bus_factor(&contributors),
+        contributors,
+        total_contributions: total,
+        normalized: true,
+        ..share
+    }
+}
+
+/// Blends all-time shares with shares of recent activity: each one becomes
+/// (1 - weight) * all-time share + weight * recent share, 0 for those who did nothing recently,
+/// so a leader who is no longer active is discounted. Logins are compared as login_key,
+/// those active only recently are added. Without recent activity all-time shares are kept,
+/// there is nothing to discount them by. Counts are the all-time ones of the new leader
+pub fn blend_recent(all_time: RepoShare, recent: Option<&RepoShare>, weight: f64) -> RepoShare {
+    let recent = match recent {
+        Some(recent) if weight > 0.0 => recent,
+        _ => return all_time,
+    };
+
+    let mut recent_shares: HashMap<String, f64> = recent
+        .contributors
+        .iter()
+        .map(|user| (github_data::login_key(&user.user_name), user.bus_factor))
+        .collect();
+
+    let mut contributors: Vec<UserShare> = all_time
+        .contributors
+        .iter()
+        .map(|user| {
+            let recent = recent_shares
+                .remove(&github_data::login_key(&user.user_name))
+                .unwrap_or(0.0);
+            UserShare {
+                bus_factor: (1.0 - weight) * user.bus_factor + weight * recent,
+                ..user.clone()
+            }
+        })
+        .collect();
+    // Keeps the order of the recent ones, missing from all-time contributors
+    contributors.extend(recent.contributors.iter().filter_map(|user| {
+        let share = recent_shares.remove(&github_data::login_key(&user.user_name))?;
+        Some(UserShare {
+            bus_factor: weight * share,
+            ..user.clone()
+        })
+    }));
+    // Stable, ties keep the all-time order
+    contributors.sort_by(|a, b| b.bus_factor.total_cmp(&a.bus_factor));
+
+    let leader = contributors[0].clone();
+    let leader_contributions = all_time
+        .contributors
+        .iter()
+        .find(|user| user.user_name == leader.user_name)
+        .map_or(0, |user| {
+            (user.bus_factor * all_time.total_contributions as f64).round() as u64
+        });
+
+    RepoShare {
+        considered: contributors.len(),
+        bus_factor: integer_bus_factor(&contributors),
+        leader,
+        contributors,
+        leader_contributions,
+        ..all_time
+    }
+}
+
+/// Value below which given fraction of values falls, interpolated between
+/// the closest ones. NaN values are ignored, None if nothing is left
+pub fn percentile(values: &[f64], fraction: f64) -> Option<f64> {
+    let mut values: Vec<f64> = values
+        .iter()
+        .cloned()
+        .filter(|value| !value.is_nan())
+        .collect();
+    if values.is_empty() {
+        return None;
+    }
+
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+    let rank = fraction.clamp(0.0, 1.0) * (values.len() - 1) as f64;
+    let (low, high) = (rank.floor() as usize, rank.ceil() as usize);
+
+    Some(values[low] + (values[high] - values[low]) * (rank - low as f64))
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_approx_eq::assert_approx_eq;
+
+    use super::*;
+
+    fn contributions(counts: &[u64]) -> Vec<ContributorData> {
+        counts
+            .iter()
+            .enumerate()
+            .map(|(idx, &contributions)| ContributorData {
+                contributions,
+                login: format!("user{}", idx),
+                ..ContributorData::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn leader_share_is_relative_to_considered_contributors() {
+        let share =
+            compute_repo_share(&contributions(&[75, 20, 5]), 25, None, ShareSource::Api).unwrap();
+
+        assert_eq!(share.leader.user_name, "user0");
+        assert_approx_eq!(share.leader.bus_factor, 0.75);
+        assert_eq!(share.considered, 3);
+        assert_eq!(share.contributors.len(), 3);
+        assert_approx_eq!(share.contributors[2].bus_factor, 0.05);
+        assert!(!share.approximate);
+    }
+
+    #[test]
+    fn integer_bus_factor_covers_more_than_half() {
+        let share =
+            compute_repo_share(&contributions(&[75, 20, 5]), 25, None, ShareSource::Api).unwrap();
+        assert_eq!(share.bus_factor, 1);
+
+        let share = compute_repo_share(
+            &contributions(&[30, 20, 20, 20, 10]),
+            25,
+            None,
+            ShareSource::Api,
+        )
+        .unwrap();
+        assert_eq!(share.bus_factor, 3);
+
+        // Exactly half is not more than half
+        let share =
+            compute_repo_share(&contributions(&[50, 50]), 25, None, ShareSource::Api).unwrap();
+        assert_eq!(share.bus_factor, 2);
+    }
+
+    #[test]
+    fn truck_factor_reaches_coverage() {
+        let shares = |counts: &[u64]| {
+            compute_repo_share(&contributions(counts), 25, None, ShareSource::Api)
+                .unwrap()
+                .contributors
+        };
+
+        // Single dominant leader
+        assert_eq!(truck_factor(&shares(&[75, 20, 5]), 0.5), 1);
+        assert_eq!(truck_factor(&shares(&[75, 20, 5]), 0.8), 2);
+        assert_eq!(truck_factor(&shares(&[75, 20, 5]), 1.0), 3);
+        // Coverage reached exactly counts, unlike the integer bus factor
+        assert_eq!(truck_factor(&shares(&[50, 50]), 0.5), 1);
+        assert_eq!(truck_factor(&shares(&[70, 10, 10, 10]), 0.8), 2);
+        // Evenly spread work
+        assert_eq!(truck_factor(&shares(&[10; 10]), 0.8), 8);
+        // Long tail, Pareto like, top 20% did 80% of the work
+        assert_eq!(
+            truck_factor(&shares(&[40, 40, 3, 3, 3, 3, 2, 2, 2, 2]), 0.8),
+            2
+        );
+
+        // Normalized shares don't reach the coverage, every considered one is counted
+        let share = normalize(
+            compute_repo_share(&contributions(&[60, 30, 10]), 3, None, ShareSource::Api).unwrap(),
+            200,
+        );
+        assert_eq!(truck_factor(&share.contributors, 0.8), 3);
+        assert_eq!(truck_factor(&[], 0.8), 0);
+    }
+
+    #[test]
+    fn authors_are_counted() {
+        let contributions = count_authors(["c", "b", "a", "b", "c", "a", "a"]);
+
+        let counted: Vec<(&str, u64)> = contributions
+            .iter()
+            .map(|contr| (contr.login.as_str(), contr.contributions))
+            .collect();
+        // Equal counts are ordered by login
+        assert_eq!(counted, vec![("a", 3), ("b", 2), ("c", 2)]);
+        assert!(count_authors([]).is_empty());
+    }
+
+    #[test]
+    fn share_basis_from_str() {
+        assert_eq!(
+            "commits".parse::<ShareBasis>().unwrap(),
+            ShareBasis::Commits
+        );
+        assert_eq!(
+            "pull-requests".parse::<ShareBasis>().unwrap(),
+            ShareBasis::PullRequests
+        );
+        assert!("reviews".parse::<ShareBasis>().is_err());
+    }
+
+    #[test]
+    fn full_page_of_contributors_is_approximate() {
+        let share =
+            compute_repo_share(&contributions(&[3, 2, 1]), 3, None, ShareSource::Api).unwrap();
+
+        assert!(share.approximate);
+    }
+
+    #[test]
+    fn only_members_are_considered() {
+        let members: HashSet<String> = ["user1", "user2"].iter().map(|m| m.to_string()).collect();
+
+        let share = compute_repo_share(
+            &contributions(&[75, 20, 5]),
+            25,
+            Some(&members),
+            ShareSource::Api,
+        )
+        .unwrap();
+
+        assert_eq!(share.leader.user_name, "user1");
+        assert_approx_eq!(share.leader.bus_factor, 0.8);
+        assert_eq!(share.considered, 2);
+
+        // Api gives logins the way users typed them, casing is kept
+        let mut mixed_case = contributions(&[75, 20, 5]);
+        mixed_case[1].login = "User1".to_string();
+        let share = compute_repo_share(&mixed_case, 25, Some(&members), ShareSource::Api).unwrap();
+        assert_eq!(share.leader.user_name, "User1");
+        assert_eq!(share.considered, 2);
+
+        let outsiders: HashSet<String> = std::iter::once("user7".to_string()).collect();
+        assert!(compute_repo_share(
+            &contributions(&[75, 20, 5]),
+            25,
+            Some(&outsiders),
+            ShareSource::Api
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn normalized_share_is_relative_to_repo_total() {
+        // Top 3 out of 200 commits
+        let share =
+            compute_repo_share(&contributions(&[60, 30, 10]), 3, None, ShareSource::Api).unwrap();
+        assert_approx_eq!(share.leader.bus_factor, 0.6);
+        assert_eq!(share.bus_factor, 1);
+
+        let share = normalize(share, 200);
+
+        assert!(share.normalized);
+        assert_eq!(share.total_contributions, 200);
+        assert_approx_eq!(share.leader.bus_factor, 0.3);
+        assert_approx_eq!(share.contributors[2].bus_factor, 0.05);
+        // Considered ones don't cover the half anymore
+        assert_eq!(share.bus_factor, 3);
+    }
+
+    #[test]
+    fn normalization_never_inflates_shares() {
+        let share =
+            compute_repo_share(&contributions(&[60, 40]), 25, None, ShareSource::Api).unwrap();
+
+        let share = normalize(share, 50);
+
+        assert_eq!(share.total_contributions, 100);
+        assert_approx_eq!(share.leader.bus_factor, 0.6);
+    }
+
+    #[test]
+    fn counts_reconstruct_the_share() {
+        let share =
+            compute_repo_share(&contributions(&[61, 29, 7]), 3, None, ShareSource::Api).unwrap();
+        assert_eq!(
+            (share.leader_contributions, share.considered_contributions),
+            (61, 97)
+        );
+        assert_eq!(share.leader.bus_factor, 61.0 / 97.0);
+
+        let share = normalize(share, 1013);
+        assert_eq!(
+            (share.leader_contributions, share.considered_contributions),
+            (61, 97)
+        );
+        assert_eq!(share.total_contributions, 1013);
+        assert_eq!(share.leader.bus_factor, 61.0 / 1013.0);
+        assert_eq!(share.contributors[2].bus_factor, 7.0 / 1013.0);
+    }
+
+    #[test]
+    fn recent_activity_is_blended() {
+        let all_time =
+            compute_repo_share(&contributions(&[80, 20]), 25, None, ShareSource::Api).unwrap();
+        // user1 took over, user2 is new, user0 did nothing recently
+        let recent = count_authors(std::iter::repeat_n("User1", 9).chain(std::iter::once("user2")));
+        let recent = compute_repo_share(&recent, usize::MAX, None, ShareSource::Api).unwrap();
+
+        let blended = blend_recent(all_time.clone(), Some(&recent), 0.5);
+        let shares: Vec<(&str, f64)> = blended
+            .contributors
+            .iter()
+            .map(|user| (user.user_name.as_str(), user.bus_factor))
+            .collect();
+        assert_eq!(shares.len(), 3);
+        // Logins are case insensitive, all-time one is kept
+        assert_eq!(shares[0].0, "user1");
+        assert_approx_eq!(shares[0].1, 0.55);
+        assert_eq!(shares[1].0, "user0");
+        assert_approx_eq!(shares[1].1, 0.4);
+        assert_eq!(shares[2].0, "user2");
+        assert_approx_eq!(shares[2].1, 0.05);
+        assert_eq!(blended.leader.user_name, "user1");
+        assert_eq!(blended.leader_contributions, 20);
+        assert_eq!((blended.bus_factor, blended.considered), (1, 3));
+
+        // Recent activity alone
+        let recent_only = blend_recent(all_time.clone(), Some(&recent), 1.0);
+        assert_approx_eq!(recent_only.leader.bus_factor, 0.9);
+        assert_eq!(recent_only.contributors[2].bus_factor, 0.0);
+
+        // Nothing to blend
+        assert_eq!(blend_recent(all_time.clone(), Some(&recent), 0.0), all_time);
+        assert_eq!(blend_recent(all_time.clone(), None, 0.5), all_time);
+    }
+
+    #[test]
+    fn percentile_interpolates() {
+        let values = [0.5, 0.1, f64::NAN, 0.3, 0.9, 0.7];
+
+        assert_approx_eq!(percentile(&values, 0.0).unwrap(), 0.1);
+        assert_approx_eq!(percentile(&values, 0.5).unwrap(), 0.5);
+        assert_approx_eq!(percentile(&values, 0.9).unwrap(), 0.82);
+        assert_approx_eq!(percentile(&values, 1.0).unwrap(), 0.9);
+        assert!(percentile(&[f64::NAN], 0.5).is_none());
+    }
+
+    #[test]
+    fn percentile_of_many_shares() {
+        use crate::synthetic::{ShareDistribution, Synthetic};
+
+        let shares: Vec<f64> = Synthetic {
+            repos: 2000,
+            shares: ShareDistribution::Uniform { min: 0.2, max: 0.8 },
+            contributors: 10..=10,
+            ..Synthetic::default()
+        }
+        .bus_factors()
+        .iter()
+        .map(|repo| repo.leader.bus_factor)
+        .collect();
+
+        assert_approx_eq!(percentile(&shares, 0.5).unwrap(), 0.5, 0.03);
+        assert_approx_eq!(percentile(&shares, 0.9).unwrap(), 0.74, 0.03);
+    }
+
+    #[test]
+    fn no_contributions_gives_nothing() {
+        assert!(compute_repo_share(&[], 25, None, ShareSource::Api).is_none());
+        assert!(compute_repo_share(&contributions(&[0]), 25, None, ShareSource::Api).is_none());
+    }
+}
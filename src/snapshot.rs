@@ -0,0 +1,517 @@
+//! Snapshot is a report of the previous run stored on disk. Comparing it
+//! with the current run shows how bus factor of repositories drifts over time.
+//! Any two reports saved with --format json can be compared the same way.
+use std::{fs, io::ErrorKind, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api_errors::{InvalidQueryError, SendError},
+    github_api::BusFactor,
+    report::{self, BatchReport, Report},
+};
+
+#[derive(Debug, PartialEq)]
+/// Change of the bus factor and stars for single repository between two runs.
+/// None means repo was not reported in given run.
+pub struct RepoDiff {
+    /// Repo name if the full one is not known, as in reports of older versions
+    pub full_name: String,
+    pub before: Option<f64>,
+    pub after: Option<f64>,
+    pub stars_before: Option<u64>,
+    pub stars_after: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+/// Report saved by --snapshot, with results of every repo next to the reported ones,
+/// so a repo that crossed the threshold since then is a change, not new or gone
+pub struct Snapshot {
+    #[serde(flatten)]
+    pub report: Report,
+    /// Every result computed in the run, reported or not. Empty in snapshots written
+    /// by older versions
+    #[serde(default)]
+    pub computed: Vec<BusFactor>,
+}
+
+impl Snapshot {
+    /// Results to compare, reported ones stand for all of them in older snapshots
+    pub fn results(&self) -> &[BusFactor] {
+        if self.computed.is_empty() {
+            &self.report.results
+        } else {
+            &self.computed
+        }
+    }
+}
+
+impl RepoDiff {
+    /// How many stars repo gained, None if it's not in both runs
+    pub fn star_delta(&self) -> Option<i64> {
+        Some(self.stars_after? as i64 - self.stars_before? as i64)
+    }
+}
+
+/// Reads snapshot from the file, returns None if there is no snapshot yet
+pub fn load(path: &Path) -> Result<Option<Snapshot>, SendError> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(Box::new(e)),
+    };
+
+    let snapshot = serde_json::from_str(&content)?;
+
+    Ok(Some(snapshot))
+}
+
+/// Reads report saved with --format json, written by this or another version. Report of a
+/// language file is taken if it has a single language, other JSON is an error telling what was expected
+pub fn load_report(path: &Path) -> Result<Report, SendError> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Can't read report {}: {}", path.display(), e))?;
+    let value: serde_json::Value = serde_json::from_str(&content)
+        .map_err(|e| format!("Report {} is not JSON: {}", path.display(), e))?;
+
+    let not_report = |what: &str| -> SendError {
+        Box::new(InvalidQueryError::new(&format!(
+            "{} is {}, expected a report saved with --format json",
+            path.display(),
+            what
+        )))
+    };
+
+    if value.get("languages").is_some() {
+        let batch: BatchReport = serde_json::from_value(value)?;
+        let languages: Vec<String> = batch.languages.keys().cloned().collect();
+        return match batch.languages.into_values().next() {
+            Some(report) if languages.len() == 1 => Ok(report),
+            _ => Err(not_report(&format!(
+                "a report of languages {}, not of a single one",
+                languages.join(", ")
+            ))),
+        };
+    }
+    if value.is_array() {
+        return Err(not_report("a list of results, like of --format json-array"));
+    }
+
+    // Fields added later have defaults, missing older ones mean it's not a report
+    let version = value["meta"]["version"]
+        .as_str()
+        .unwrap_or("unknown")
+        .to_string();
+    serde_json::from_value(value).map_err(|e| {
+        format!(
+            "Can't read report {} written by version {}: {}",
+            path.display(),
+            version,
+            e
+        )
+        .into()
+    })
+}
+
+/// Differences between reports that make the comparison less meaningful,
+/// like other language or version, one line each, empty if there are none
+pub fn mismatches(previous: &Report, current: &Report) -> Vec<String> {
+    let mut notes = vec![];
+    let (before, after) = (&previous.meta, &current.meta);
+
+    if before.language != after.language {
+        notes.push(format!(
+            "Reports are of different languages, {} and {}",
+            before.language, after.language
+        ));
+    }
+    if before.version != after.version {
+        let version = |version: &str| {
+            if version.is_empty() {
+                "an unknown version".to_string()
+            } else {
+                version.to_string()
+            }
+        };
+        notes.push(format!(
+            "Reports are written by {} and {}, shares may be counted differently",
+            version(&before.version),
+            version(&after.version)
+        ));
+    }
+    if before.timestamp > after.timestamp {
+        notes.push(format!(
+            "Report compared against is the newer one, from {}",
+            before.timestamp
+        ));
+    }
+
+    notes
+}
+
+/// Writes snapshot to the file, previous one is overwritten
+pub fn save(path: &Path, snapshot: &Snapshot) -> Result<(), SendError> {
+    let content = serde_json::to_string_pretty(snapshot)?;
+    fs::write(path, content)?;
+
+    Ok(())
+}
+
+/// Full name of the repo, names alone are ambiguous across owners
+fn full_name(repo: &BusFactor) -> String {
+    if repo.full_name.is_empty() {
+        repo.repo_name.clone()
+    } else {
+        repo.full_name.clone()
+    }
+}
+
+/// Compares results of two runs. Repos from the current results go first,
+/// in the same order, followed by repos that are no longer there.
+/// Repos are matched by id, so renamed repo is not reported as gone and new.
+pub fn diff(previous: &[BusFactor], current: &[BusFactor]) -> Vec<RepoDiff> {
+    let find = |results: &[BusFactor], repo: &BusFactor| -> Option<(f64, u64)> {
+        results
+            .iter()
+            .find(|other| other.is_same_repo(repo))
+            .map(|other| (other.leader.bus_factor, other.stars))
+    };
+
+    let mut res: Vec<RepoDiff> = current
+        .iter()
+        .map(|repo| {
+            let before = find(previous, repo);
+            RepoDiff {
+                full_name: full_name(repo),
+                before: before.map(|(share, _)| share),
+                after: Some(repo.leader.bus_factor),
+                stars_before: before.map(|(_, stars)| stars),
+                stars_after: Some(repo.stars),
+            }
+        })
+        .collect();
+
+    // Repos that dropped from the results
+    for repo in previous {
+        if find(current, repo).is_none() {
+            res.push(RepoDiff {
+                full_name: full_name(repo),
+                before: Some(repo.leader.bus_factor),
+                after: None,
+                stars_before: Some(repo.stars),
+                stars_after: None,
+            });
+        }
+    }
+
+    res
+}
+
+/// Text listing of the differences since previous report, named by what,
+/// optionally with the change of stars. Line per repo, after the heading
+pub fn diff_to_text(what: &str, previous: &Report, diffs: &[RepoDiff], show_stars: bool) -> String {
+    let mut lines = vec![format!(
+        "Changes since {} from {}:",
+        what, previous.meta.timestamp
+    )];
+
+    let width = report::column_width(
+        diffs.iter().map(|diff| diff.full_name.as_str()),
+        report::MAX_NAME_WIDTH,
+    );
+    for diff in diffs {
+        let project = report::fit_width(&diff.full_name, width);
+        let stars = match diff.star_delta() {
+            Some(delta) if show_stars => format!(" stars: {:+}", delta),
+            _ => String::new(),
+        };
+
+        match (diff.before, diff.after) {
+            (Some(before), Some(after)) => lines.push(format!(
+                "project: {project} percentage: {before:.2} -> {after:.2} ({delta:+.2}){stars}",
+                project = project,
+                before = before,
+                after = after,
+                delta = after - before,
+                stars = stars
+            )),
+            (None, Some(after)) => lines.push(format!(
+                "project: {project} percentage: {after:.2} (new)",
+                project = project,
+                after = after
+            )),
+            (Some(before), None) => lines.push(format!(
+                "project: {project} percentage: {before:.2} (gone)",
+                project = project,
+                before = before
+            )),
+            (None, None) => {}
+        }
+    }
+
+    lines.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::github_api::{BusFactor, UserShare};
+
+    use super::*;
+
+    fn bus_factor(repo_name: &str, bus_factor: f64) -> BusFactor {
+        BusFactor::new(
+            repo_name,
+            0,
+            UserShare {
+                bus_factor,
+                user_name: "user".to_string(),
+                ..UserShare::default()
+            },
+        )
+    }
+
+    fn starred(repo_id: u64, repo_name: &str, stars: u64) -> BusFactor {
+        BusFactor {
+            repo_id,
+            stars,
+            ..bus_factor(repo_name, 0.8)
+        }
+    }
+
+    #[test]
+    fn diff_detects_changed_new_and_gone_repos() {
+        let previous = Report::new(
+            "rust",
+            2,
+            vec![bus_factor("changed", 0.8), bus_factor("gone", 0.9)],
+        );
+        let current = Report::new(
+            "rust",
+            2,
+            vec![bus_factor("new", 0.76), bus_factor("changed", 0.85)],
+        );
+
+        let diffs = diff(&previous.results, &current.results);
+
+        assert_eq!(
+            diffs,
+            vec![
+                RepoDiff {
+                    full_name: "new".to_string(),
+                    before: None,
+                    after: Some(0.76),
+                    stars_before: None,
+                    stars_after: Some(0),
+                },
+                RepoDiff {
+                    full_name: "changed".to_string(),
+                    before: Some(0.8),
+                    after: Some(0.85),
+                    stars_before: Some(0),
+                    stars_after: Some(0),
+                },
+                RepoDiff {
+                    full_name: "gone".to_string(),
+                    before: Some(0.9),
+                    after: None,
+                    stars_before: Some(0),
+                    stars_after: None,
+                },
+            ]
+        );
+
+        let text = diff_to_text("snapshot", &previous, &diffs, false);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("Changes since snapshot from "));
+        assert!(lines[1].ends_with("percentage: 0.76 (new)"), "{}", lines[1]);
+        assert!(
+            lines[2].ends_with("percentage: 0.80 -> 0.85 (+0.05)"),
+            "{}",
+            lines[2]
+        );
+        assert!(
+            lines[3].ends_with("percentage: 0.90 (gone)"),
+            "{}",
+            lines[3]
+        );
+    }
+
+    #[test]
+    fn star_delta_follows_renamed_repo() {
+        let previous = Report::new(
+            "rust",
+            2,
+            vec![starred(1, "old-name", 100), starred(2, "b", 50)],
+        );
+        let current = Report::new(
+            "rust",
+            2,
+            vec![starred(1, "new-name", 180), starred(2, "b", 40)],
+        );
+
+        let diffs = diff(&previous.results, &current.results);
+
+        assert_eq!(diffs.len(), 2);
+        assert_eq!(diffs[0].full_name, "new-name");
+        assert_eq!(diffs[0].star_delta(), Some(80));
+        assert_eq!(diffs[1].star_delta(), Some(-10));
+    }
+
+    #[test]
+    fn diff_shows_full_names_aligned() {
+        let owned = |repo_id: u64, full_name: &str| BusFactor {
+            repo_id,
+            full_name: full_name.to_string(),
+            ..bus_factor("repo", 0.8)
+        };
+        let previous = Report::new("rust", 2, vec![owned(1, "a/repo")]);
+        let current = Report::new(
+            "rust",
+            2,
+            vec![owned(1, "a/repo"), owned(2, "long-owner/repo")],
+        );
+
+        let diffs = diff(&previous.results, &current.results);
+        let text = diff_to_text("snapshot", &previous, &diffs, false);
+        let lines: Vec<&str> = text.lines().skip(1).collect();
+
+        assert!(lines[0].starts_with("project: a/repo "), "{}", lines[0]);
+        assert!(
+            lines[1].starts_with("project: long-owner/repo "),
+            "{}",
+            lines[1]
+        );
+        assert_eq!(lines[0].find("percentage"), lines[1].find("percentage"));
+    }
+
+    #[test]
+    fn missing_snapshot_is_not_an_error() {
+        let mut path = std::env::temp_dir();
+        path.push("bus_factor_missing_snapshot.json");
+
+        assert!(load(&path).unwrap().is_none());
+    }
+
+    #[test]
+    fn saved_reports_are_loaded() {
+        let dir = std::env::temp_dir().join(format!("bus_factor_reports_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let write = |name: &str, content: String| {
+            let path = dir.join(name);
+            fs::write(&path, content).unwrap();
+            path
+        };
+        let report = Report::new("rust", 1, vec![bus_factor("repo", 0.8)]);
+
+        let single = write("single.json", report::to_json(&report, true).unwrap());
+        assert_eq!(load_report(&single).unwrap().results, report.results);
+
+        let mut batch = BatchReport::default();
+        batch.languages.insert(
+            "rust".to_string(),
+            Report::new("rust", 1, vec![bus_factor("repo", 0.8)]),
+        );
+        let one_language = write("batch.json", report::to_json(&batch, false).unwrap());
+        assert_eq!(load_report(&one_language).unwrap().meta.language, "rust");
+        batch
+            .languages
+            .insert("go".to_string(), Report::new("go", 1, vec![]));
+        let languages = write("languages.json", report::to_json(&batch, false).unwrap());
+        let err = load_report(&languages).unwrap_err();
+        assert!(err.to_string().contains("languages go, rust"), "{}", err);
+
+        let array = write(
+            "array.json",
+            report::to_json(&report.results, false).unwrap(),
+        );
+        assert!(load_report(&array)
+            .unwrap_err()
+            .to_string()
+            .contains("json-array"));
+
+        // Written before run id and version were there
+        let older = write(
+            "older.json",
+            r#"{"meta": {"timestamp": "2021-12-24T10:15:30Z", "language": "rust", "project_count": 1},
+                "results": []}"#
+                .to_string(),
+        );
+        assert!(load_report(&older).unwrap().meta.version.is_empty());
+        let broken = write(
+            "broken.json",
+            r#"{"meta": {"version": "0.0.1"}, "results": []}"#.to_string(),
+        );
+        assert!(load_report(&broken)
+            .unwrap_err()
+            .to_string()
+            .contains("version 0.0.1"));
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(load_report(&single)
+            .unwrap_err()
+            .to_string()
+            .contains("single.json"));
+    }
+
+    #[test]
+    fn mismatched_reports_are_noted() {
+        let same = Report::new("rust", 1, vec![]);
+        let current = Report::new("rust", 1, vec![]);
+        assert!(mismatches(&same, &current).is_empty());
+
+        let mut previous = Report::new("go", 1, vec![]);
+        previous.meta.version = String::new();
+        previous.meta.timestamp = current.meta.timestamp + chrono::Duration::days(1);
+
+        let notes = mismatches(&previous, &current);
+        assert_eq!(notes.len(), 3);
+        assert!(notes[0].contains("go and rust"));
+        assert!(notes[1].contains("an unknown version and"));
+        assert!(notes[2].contains("newer one"));
+    }
+
+    #[test]
+    fn snapshot_round_trip() {
+        let dir = std::env::temp_dir().join(format!("bus_factor_snapshots_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("snapshot.json");
+
+        let snapshot = Snapshot {
+            report: Report::new("rust", 2, vec![bus_factor("repo", 0.8)]),
+            computed: vec![bus_factor("repo", 0.8), bus_factor("healthy", 0.3)],
+        };
+        save(&path, &snapshot).unwrap();
+
+        let loaded = load(&path).unwrap().unwrap();
+        assert_eq!(loaded.report.results, snapshot.report.results);
+        assert_eq!(loaded.report.meta.timestamp, snapshot.report.meta.timestamp);
+        assert_eq!(loaded.results(), &snapshot.computed[..]);
+
+        // Written before computed results were saved, it's a report
+        let older = dir.join("older.json");
+        fs::write(&older, report::to_json(&snapshot.report, false).unwrap()).unwrap();
+        let loaded = load(&older).unwrap().unwrap();
+        assert_eq!(loaded.results(), &snapshot.report.results[..]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn repo_crossing_threshold_is_a_change() {
+        // Below the threshold before, so it was not reported
+        let previous = Snapshot {
+            report: Report::new("rust", 1, vec![]),
+            computed: vec![bus_factor("repo", 0.6)],
+        };
+        let current = Snapshot {
+            report: Report::new("rust", 1, vec![bus_factor("repo", 0.8)]),
+            computed: vec![bus_factor("repo", 0.8)],
+        };
+
+        let diffs = diff(previous.results(), current.results());
+
+        assert_eq!(diffs.len(), 1);
+        assert_eq!((diffs[0].before, diffs[0].after), (Some(0.6), Some(0.8)));
+    }
+}
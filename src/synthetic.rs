@@ -0,0 +1,246 @@
+//! Synthetic repos and contributions for tests and benchmarks, no network or hand-written
+//! fixtures needed. Built for tests, or with the "testing" feature.
+use std::ops::RangeInclusive;
+
+use crate::{
+    github_api::BusFactor,
+    github_data::{Contributions, ContributorData, OwnerData, RepoData, Repos},
+    rng::Rng,
+    share::{self, ShareSource},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// How leader's shares are spread over generated repos
+pub enum ShareDistribution {
+    /// Every repo has the same share
+    Constant(f64),
+    /// Equally likely anywhere between min and max
+    Uniform { min: f64, max: f64 },
+    /// Uniform share raised to the exponent, below 1 most repos have a dominant leader,
+    /// above 1 most have the work spread
+    Skewed { exponent: f64 },
+}
+
+#[derive(Debug, Clone)]
+/// Describes the data to generate, the same description gives the same data
+pub struct Synthetic {
+    pub repos: usize,
+    pub shares: ShareDistribution,
+    pub stars: RangeInclusive<u64>,
+    /// Contributors of each repo, at least one
+    pub contributors: RangeInclusive<u32>,
+    pub language: String,
+    pub seed: u64,
+}
+
+impl Default for Synthetic {
+    fn default() -> Self {
+        Self {
+            repos: 100,
+            shares: ShareDistribution::Uniform { min: 0.0, max: 1.0 },
+            stars: 0..=100_000,
+            contributors: 1..=30,
+            language: "rust".to_string(),
+            seed: 0,
+        }
+    }
+}
+
+/// Repos as the search gives them, and contributions of each, in the same order
+pub struct SyntheticData {
+    pub repos: Repos,
+    pub contributions: Vec<Contributions>,
+}
+
+impl Synthetic {
+    /// Repos are named "repo-N", contributors "user-N", the leader is always "user-0".
+    /// Drawn share is clamped, so the leader stays the most active, and contributions
+    /// are whole numbers, the share computed from them is close to the drawn one
+    pub fn generate(&self) -> SyntheticData {
+        let mut rng = Rng::seeded(self.seed);
+        let mut items = Vec::with_capacity(self.repos);
+        let mut contributions = Vec::with_capacity(self.repos);
+
+        for i in 0..self.repos {
+            let name = format!("repo-{}", i);
+            let full_name = format!("owner-{}/{}", i, name);
+            items.push(RepoData {
+                id: i as u64 + 1,
+                contributors_url: format!("/repos/{}/contributors", full_name),
+                html_url: format!("https://github.com/{}", full_name),
+                full_name,
+                name,
+                stargazers_count: rng.between(*self.stars.start(), *self.stars.end()),
+                language: self.language.clone(),
+                owner: OwnerData {
+                    login: format!("owner-{}", i),
+                },
+                ..RepoData::default()
+            });
+
+            let count = rng.between(
+                (*self.contributors.start()).max(1) as u64,
+                (*self.contributors.end()).max(1) as u64,
+            );
+            let share = self.draw_share(&mut rng);
+            contributions.push(contributions_with_share(count, share));
+        }
+
+        // Search lists the most starred first
+        let mut order: Vec<usize> = (0..items.len()).collect();
+        order.sort_by_key(|&i| std::cmp::Reverse(items[i].stargazers_count));
+
+        SyntheticData {
+            repos: Repos {
+                total_count: Some(items.len() as u64),
+                items: order.iter().map(|&i| items[i].clone()).collect(),
+                ..Repos::default()
+            },
+            contributions: order.iter().map(|&i| contributions[i].clone()).collect(),
+        }
+    }
+
+    /// Results as the api would give them for generated data, all contributors considered
+    pub fn bus_factors(&self) -> Vec<BusFactor> {
+        let data = self.generate();
+
+        data.repos
+            .items
+            .iter()
+            .zip(&data.contributions)
+            .map(|(repo, contributions)| {
+                let share =
+                    share::compute_repo_share(contributions, usize::MAX, None, ShareSource::Api)
+                        .expect("Generated repos always have contributions");
+
+                BusFactor {
+                    repo_id: repo.id,
+                    full_name: repo.full_name.clone(),
+                    language: repo.language.clone(),
+                    ..BusFactor::new(&repo.name, repo.stargazers_count, share.leader)
+                }
+            })
+            .collect()
+    }
+
+    fn draw_share(&self, rng: &mut Rng) -> f64 {
+        let share = match self.shares {
+            ShareDistribution::Constant(share) => share,
+            ShareDistribution::Uniform { min, max } => min + (max - min) * rng.unit(),
+            ShareDistribution::Skewed { exponent } => rng.unit().powf(exponent),
+        };
+        share.clamp(0.0, 1.0)
+    }
+}
+
+/// Leader gets the share of all contributions, the others split the rest, less for each next one
+fn contributions_with_share(count: u64, share: f64) -> Contributions {
+    let total = 100 * count;
+    // Leader has at least as much as anyone else
+    let share = share.max(1.0 / count as f64);
+    let leader = ((share * total as f64).round() as u64).max(1);
+
+    let rest = total.saturating_sub(leader);
+    let weights: Vec<f64> = (1..count).map(|k| 1.0 / k as f64).collect();
+    let weights_sum: f64 = weights.iter().sum();
+
+    let mut contributions = vec![ContributorData {
+        login: "user-0".to_string(),
+        contributions: leader,
+        ..ContributorData::default()
+    }];
+    for (k, weight) in weights.iter().enumerate() {
+        let count = (rest as f64 * weight / weights_sum).round() as u64;
+        contributions.push(ContributorData {
+            login: format!("user-{}", k + 1),
+            contributions: count.min(leader),
+            ..ContributorData::default()
+        });
+    }
+
+    contributions
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_approx_eq::assert_approx_eq;
+
+    use super::*;
+
+    #[test]
+    fn data_follows_the_description() {
+        let synthetic = Synthetic {
+            repos: 50,
+            shares: ShareDistribution::Uniform { min: 0.4, max: 0.8 },
+            stars: 10..=20,
+            contributors: 5..=10,
+            ..Synthetic::default()
+        };
+
+        let data = synthetic.generate();
+        assert_eq!(data.repos.items.len(), 50);
+        assert!(data
+            .repos
+            .items
+            .windows(2)
+            .all(|pair| pair[0].stargazers_count >= pair[1].stargazers_count));
+
+        for (repo, contributions) in data.repos.items.iter().zip(&data.contributions) {
+            assert!((10..=20).contains(&repo.stargazers_count));
+            assert!(
+                (5..=10).contains(&contributions.len()),
+                "{}",
+                contributions.len()
+            );
+            assert!(contributions
+                .iter()
+                .all(|contr| contr.contributions <= contributions[0].contributions));
+        }
+
+        for repo in synthetic.bus_factors() {
+            // Rounding of contributions moves the share a little
+            assert!(
+                (0.39..=0.81).contains(&repo.leader.bus_factor),
+                "{}",
+                repo.leader.bus_factor
+            );
+            assert_eq!(repo.leader.user_name, "user-0");
+        }
+    }
+
+    #[test]
+    fn same_seed_gives_same_data() {
+        let synthetic = Synthetic::default();
+
+        assert_eq!(synthetic.bus_factors(), synthetic.bus_factors());
+        assert_ne!(
+            synthetic.bus_factors(),
+            Synthetic {
+                seed: 1,
+                ..Synthetic::default()
+            }
+            .bus_factors()
+        );
+    }
+
+    #[test]
+    fn share_is_kept_where_possible() {
+        let contributions = contributions_with_share(4, 0.7);
+        let share =
+            share::compute_repo_share(&contributions, usize::MAX, None, ShareSource::Api).unwrap();
+        assert_approx_eq!(share.leader.bus_factor, 0.7, 0.01);
+
+        // Single contributor does everything, whatever the share
+        assert_eq!(contributions_with_share(1, 0.2).len(), 1);
+
+        // Leader can't have less than an equal split
+        let contributions = contributions_with_share(4, 0.1);
+        let share =
+            share::compute_repo_share(&contributions, usize::MAX, None, ShareSource::Api).unwrap();
+        assert!(
+            share.leader.bus_factor >= 0.25,
+            "{}",
+            share.leader.bus_factor
+        );
+    }
+}
@@ -0,0 +1,70 @@
+use std::{sync::Mutex, time::Duration};
+
+use crate::share;
+
+/// Durations recorded concurrently, like latencies of repos processed by many workers
+#[derive(Debug, Default)]
+pub struct Latencies {
+    recorded: Mutex<Vec<Duration>>,
+}
+
+impl Latencies {
+    pub fn record(&self, duration: Duration) {
+        self.recorded.lock().unwrap().push(duration);
+    }
+
+    /// Returns recorded durations, and starts over
+    pub fn take(&self) -> Vec<Duration> {
+        std::mem::take(&mut *self.recorded.lock().unwrap())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Distribution of durations, percentiles are interpolated
+pub struct LatencyStats {
+    pub count: usize,
+    pub min: Duration,
+    pub median: Duration,
+    pub p95: Duration,
+    pub max: Duration,
+}
+
+impl LatencyStats {
+    /// None if there are no durations
+    pub fn of(durations: &[Duration]) -> Option<Self> {
+        let secs: Vec<f64> = durations.iter().map(Duration::as_secs_f64).collect();
+        let at = |fraction| share::percentile(&secs, fraction).map(Duration::from_secs_f64);
+
+        Some(Self {
+            count: durations.len(),
+            min: at(0.0)?,
+            median: at(0.5)?,
+            p95: at(0.95)?,
+            max: at(1.0)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_of_latencies() {
+        let latencies = Latencies::default();
+        for ms in (1..=100).rev() {
+            latencies.record(Duration::from_millis(ms));
+        }
+
+        let stats = LatencyStats::of(&latencies.take()).unwrap();
+
+        assert_eq!(stats.count, 100);
+        assert_eq!(stats.min, Duration::from_millis(1));
+        assert_eq!(stats.median.as_micros(), 50_500);
+        assert_eq!(stats.p95.as_micros(), 95_050);
+        assert_eq!(stats.max, Duration::from_millis(100));
+
+        // Taken ones are gone
+        assert_eq!(LatencyStats::of(&latencies.take()), None);
+    }
+}
@@ -0,0 +1,121 @@
+use std::fs;
+
+use futures::future::BoxFuture;
+use reqwest::{header::HeaderMap, StatusCode};
+
+use crate::{api_errors::SendError, github_client::TransportConfig};
+
+#[derive(Debug, Clone)]
+/// Response as it came from the server, before any interpretation
+pub struct RawResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: String,
+}
+
+/// Delivers responses for requests. Abstracted, so they can come
+/// from somewhere else than network, like recorded fixtures.
+pub trait Transport: Send + Sync {
+    /// Sends GET request with given headers to the endpoint
+    fn get<'a>(
+        &'a self,
+        endpoint: &'a str,
+        headers: HeaderMap,
+    ) -> BoxFuture<'a, Result<RawResponse, SendError>>;
+}
+
+/// Transport that talks to the actual server
+pub struct HttpTransport {
+    inner: reqwest::Client,
+}
+
+impl HttpTransport {
+    /// Fails if config contains invalid values, like malformed proxy url
+    /// or CA certificate that can't be read
+    pub fn new(config: &TransportConfig) -> Result<Self, SendError> {
+        let mut builder =
+            reqwest::Client::builder().danger_accept_invalid_certs(config.accept_invalid_certs);
+
+        if config.accept_invalid_certs {
+            warn!("Certificates are not verified, the connection is not secure");
+        }
+
+        if let Some(path) = &config.ca_cert {
+            let cert = fs::read(path)
+                .map_err(SendError::from)
+                .and_then(|pem| Ok(reqwest::Certificate::from_pem(&pem)?))
+                .map_err(|e| format!("Can't read CA certificate {}: {}", path.display(), e))?;
+            // Added to the system roots, those stay trusted
+            builder = builder.add_root_certificate(cert);
+        }
+
+        if let Some(proxy) = &config.proxy {
+            builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+        }
+
+        if let Some(timeout) = config.timeout {
+            builder = builder.timeout(timeout);
+        }
+
+        Ok(Self {
+            inner: builder.build()?,
+        })
+    }
+}
+
+impl Transport for HttpTransport {
+    fn get<'a>(
+        &'a self,
+        endpoint: &'a str,
+        headers: HeaderMap,
+    ) -> BoxFuture<'a, Result<RawResponse, SendError>> {
+        Box::pin(async move {
+            let res = self.inner.get(endpoint).headers(headers).send().await?;
+
+            let status = res.status();
+            let headers = res.headers().clone();
+            let body = res.text().await?;
+
+            Ok(RawResponse {
+                status,
+                headers,
+                body,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn with_ca_cert(path: PathBuf) -> Result<HttpTransport, SendError> {
+        HttpTransport::new(&TransportConfig {
+            ca_cert: Some(path),
+            ..TransportConfig::default()
+        })
+    }
+
+    #[test]
+    fn ca_cert_is_read_from_pem() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("tests/certs/ca.pem");
+        assert!(with_ca_cert(path).is_ok());
+
+        let err = with_ca_cert(PathBuf::from("/nonexistent/ca.pem"))
+            .err()
+            .unwrap();
+        assert!(
+            err.to_string()
+                .contains("Can't read CA certificate /nonexistent/ca.pem"),
+            "{}",
+            err
+        );
+
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("Cargo.toml");
+        assert!(with_ca_cert(path).is_err());
+    }
+}
@@ -0,0 +1,65 @@
+//! Warnings of a run, kept besides being logged, so reports can carry them.
+use std::{fmt, sync::Mutex};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+/// What the warning is about, so consumers can tell them apart without parsing messages
+pub enum WarningCategory {
+    /// Repo is not in the results, it failed, timed out, or can't be fetched at all
+    SkippedRepo,
+    /// Request was throttled, by the primary or secondary rate limit
+    RateLimit,
+    /// Request failed and is repeated
+    Retry,
+    /// Search timed out on github side, and gave only some of the results
+    IncompleteResults,
+    /// Not all contributions were fetched, share is approximate
+    ApproximateShare,
+    /// Optional data of a repo is missing, like usage, repo is still reported
+    MissingData,
+}
+
+impl fmt::Display for WarningCategory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            WarningCategory::SkippedRepo => "skipped-repo",
+            WarningCategory::RateLimit => "rate-limit",
+            WarningCategory::Retry => "retry",
+            WarningCategory::IncompleteResults => "incomplete-results",
+            WarningCategory::ApproximateShare => "approximate-share",
+            WarningCategory::MissingData => "missing-data",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+/// Single warning, message is the same as the one logged
+pub struct RunWarning {
+    pub category: WarningCategory,
+    pub message: String,
+}
+
+/// Warnings raised concurrently, like by many workers
+#[derive(Debug, Default)]
+pub struct Warnings {
+    raised: Mutex<Vec<RunWarning>>,
+}
+
+impl Warnings {
+    /// Logs the warning and keeps it
+    pub fn raise(&self, category: WarningCategory, message: String) {
+        warn!("{}", message);
+        self.raised
+            .lock()
+            .unwrap()
+            .push(RunWarning { category, message });
+    }
+
+    /// Returns raised warnings, and starts over
+    pub fn take(&self) -> Vec<RunWarning> {
+        std::mem::take(&mut *self.raised.lock().unwrap())
+    }
+}
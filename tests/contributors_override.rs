@@ -0,0 +1,74 @@
+//! Checks that contributors are requested from the overridden base,
+//! using a real local HTTP server, so it goes through the whole network stack.
+use std::sync::{Arc, Mutex};
+
+use bus_factor::{
+    github_api::{BusFactorQuery, GithubApi},
+    github_data::{RepoData, Repos},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+
+const CONTRIBUTORS: &str =
+    r#"[{"login": "leader", "contributions": 9}, {"login": "other", "contributions": 1}]"#;
+
+/// Answers every request with the same contributors, remembers request lines
+async fn mock_server() -> (String, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(Vec::new()));
+
+    let seen = requests.clone();
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+
+            // Requests are small, single read gets the whole head
+            let mut buf = vec![0; 4096];
+            let len = socket.read(&mut buf).await.unwrap();
+            let request = String::from_utf8_lossy(&buf[..len]);
+            seen.lock()
+                .unwrap()
+                .push(request.lines().next().unwrap_or_default().to_string());
+
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                CONTRIBUTORS.len(),
+                CONTRIBUTORS
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        }
+    });
+
+    (base, requests)
+}
+
+#[tokio::test]
+async fn contributors_are_requested_from_override() {
+    let (base, requests) = mock_server().await;
+
+    let api = GithubApi::new("token").with_contributors_base(&base);
+
+    let repos = Repos {
+        items: vec![RepoData {
+            name: "repo".to_string(),
+            contributors_url: "https://api.github.com/repos/owner/repo/contributors".to_string(),
+            ..RepoData::default()
+        }],
+        ..Repos::default()
+    };
+
+    let res = api
+        .get_repos_bus_factor(&repos, &BusFactorQuery::default())
+        .await
+        .unwrap();
+
+    assert_eq!(res.len(), 1);
+    assert_eq!(res[0].leader.user_name, "leader");
+    assert_eq!(
+        requests.lock().unwrap().as_slice(),
+        ["GET /repos/owner/repo/contributors?per_page=25 HTTP/1.1"]
+    );
+}
@@ -0,0 +1,199 @@
+//! Checks that streaming path keeps memory flat regardless of the count.
+//! Lives in its own test binary, since it replaces the global allocator
+//! to measure peak memory usage.
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use bus_factor::{
+    api_errors::SendError,
+    github_api::{BusFactorQuery, GithubApi, RepoQuery},
+    github_client::{GithubClient, TransportConfig},
+    transport::{RawResponse, Transport},
+};
+use futures::future::BoxFuture;
+use reqwest::{header::HeaderMap, StatusCode};
+use tokio::sync::Mutex;
+
+/// Allocator that keeps track of the peak of allocated memory
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let current = ALLOCATED.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+        PEAK.fetch_max(current, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::SeqCst);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Tests run in parallel, but peak is global, so each of them holds it while running
+static MEASURING: Mutex<()> = Mutex::const_new(());
+
+/// Generates search pages and contributor lists on the fly, for any count
+struct SyntheticTransport;
+
+impl SyntheticTransport {
+    fn query_param(endpoint: &str, name: &str) -> usize {
+        endpoint
+            .split(['?', '&'])
+            .find_map(|param| param.strip_prefix(&format!("{}=", name)))
+            .and_then(|value| value.parse().ok())
+            .unwrap()
+    }
+
+    /// Like github, gives only the first 1000 results, pages past them are rejected
+    fn search_page(endpoint: &str) -> (StatusCode, String) {
+        let per_page = Self::query_param(endpoint, "per_page");
+        let page = Self::query_param(endpoint, "page");
+        if (page - 1) * per_page >= 1000 {
+            return (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                r#"{"message": "Only the first 1000 search results are available"}"#.to_string(),
+            );
+        }
+
+        let items: Vec<String> = ((page - 1) * per_page..page * per_page)
+            .map(|i| {
+                format!(
+                    r#"{{"name": "repo-{i}", "stargazers_count": {i},
+                    "contributors_url": "https://api.github.com/repos/owner/repo-{i}/contributors"}}"#,
+                    i = i
+                )
+            })
+            .collect();
+
+        (
+            StatusCode::OK,
+            format!(r#"{{"items": [{}]}}"#, items.join(",")),
+        )
+    }
+}
+
+impl Transport for SyntheticTransport {
+    fn get<'a>(
+        &'a self,
+        endpoint: &'a str,
+        _headers: HeaderMap,
+    ) -> BoxFuture<'a, Result<RawResponse, SendError>> {
+        Box::pin(async move {
+            let (status, body) = if endpoint.contains("/search/repositories") {
+                Self::search_page(endpoint)
+            } else {
+                (
+                    StatusCode::OK,
+                    r#"[{"login": "a", "contributions": 8}, {"login": "b", "contributions": 2}]"#
+                        .to_string(),
+                )
+            };
+
+            Ok(RawResponse {
+                status,
+                headers: HeaderMap::new(),
+                body,
+            })
+        })
+    }
+}
+
+fn synthetic_api() -> GithubApi {
+    GithubApi::with_client(GithubClient::with_transport(
+        "",
+        &TransportConfig::default(),
+        Arc::new(SyntheticTransport),
+    ))
+}
+
+/// Returns number of repos processed in streaming mode for the count, and peak memory
+/// allocated meanwhile
+async fn streaming_peak(count: u32) -> (usize, usize) {
+    let api = synthetic_api();
+    let repo_query = RepoQuery {
+        language: Some("rust"),
+        count,
+        ..RepoQuery::default()
+    };
+
+    PEAK.store(ALLOCATED.load(Ordering::SeqCst), Ordering::SeqCst);
+    let base = PEAK.load(Ordering::SeqCst);
+
+    let mut processed = 0;
+    api.for_each_batch(&repo_query, &BusFactorQuery::default(), |batch, _| {
+        processed += batch.len()
+    })
+    .await
+    .unwrap();
+
+    (processed, PEAK.load(Ordering::SeqCst) - base)
+}
+
+/// Returns peak memory allocated while processing count repos in eager mode
+async fn eager_peak(count: u32) -> usize {
+    let api = synthetic_api();
+    let repo_query = RepoQuery {
+        language: Some("rust"),
+        count,
+        ..RepoQuery::default()
+    };
+
+    PEAK.store(ALLOCATED.load(Ordering::SeqCst), Ordering::SeqCst);
+    let base = PEAK.load(Ordering::SeqCst);
+
+    let repos = api.get_repos(&repo_query).await.unwrap();
+    let res = api
+        .get_repos_bus_factor(&repos, &BusFactorQuery::default())
+        .await
+        .unwrap();
+
+    assert_eq!(res.len(), count as usize);
+
+    PEAK.load(Ordering::SeqCst) - base
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn streaming_memory_does_not_grow_with_count() {
+    let _measuring = MEASURING.lock().await;
+    let (processed, small) = streaming_peak(200).await;
+    assert_eq!(processed, 200);
+    let (processed, large) = streaming_peak(1_000).await;
+    assert_eq!(processed, 1_000);
+
+    // 5 times more repos, memory should stay roughly the same
+    assert!(
+        large < small * 2,
+        "streaming peak for 200 repos {}B, for 1000 repos {}B",
+        small,
+        large
+    );
+
+    // Sanity check that measurement catches proportional growth
+    let eager = eager_peak(1_000).await;
+    assert!(
+        eager > large * 4,
+        "eager peak {}B, streaming peak {}B",
+        eager,
+        large
+    );
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn count_above_search_cap_streams_what_search_gives() {
+    let _measuring = MEASURING.lock().await;
+    let (processed, _) = streaming_peak(1_500).await;
+
+    assert_eq!(processed, 1_000);
+}